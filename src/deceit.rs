@@ -1,16 +1,26 @@
 //! Deceit is the unit responsible for processing serveral status URIs or path patters.
 //! All deceit related logic is placed into this module.
 
-use std::sync::{Arc, atomic::AtomicU16};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, LazyLock, Mutex, RwLock, atomic::AtomicU16},
+};
 
 use actix_router::{Path, ResourceDef};
 use actix_web::http::StatusCode;
+use color_eyre::eyre::{bail, eyre};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{MapAccess, SeqAccess, Visitor},
+};
 
 use crate::{
-    ApateConfig, ApateCounters, RequestContext, ResourceRef,
-    matchers::{Matcher, matchers_and},
+    ApateConfig, ApateCounters, RequestContext, ResourceRef, ScenarioStates,
+    matchers::{MatchMode, Matcher, matchers_by_mode},
     output::OutputType,
     processors::Processor,
     rhai::RhaiState,
@@ -18,15 +28,73 @@ use crate::{
 
 pub const DEFAULT_RESPONSE_CODE: StatusCode = StatusCode::OK;
 
+/// Prefix for counter keys that must not clash with the same keys from other deceits.
+pub const LOCAL_COUNTER_PREFIX: &str = "@local:";
+
+/// Path arguments of actix URI pattern with their segment regex.
+/// `{name}` segment uses default `[^/]+` regex and `{name}*` tail matches anything.
+fn uri_pattern_args(pattern: &str) -> Vec<(String, String)> {
+    let mut args = Vec::new();
+    let mut chars = pattern.char_indices();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '{' {
+            continue;
+        }
+
+        // Regex could contain braces like `{id:\d{3}}`
+        let mut depth = 1;
+        let Some((end, _)) = chars.by_ref().find(|(_, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        }) else {
+            break;
+        };
+
+        let segment = &pattern[start + 1..end];
+        let (name, regex) = match segment.split_once(':') {
+            Some((name, regex)) => (name, regex),
+            None if pattern[end + 1..].starts_with('*') => (segment, ".*"),
+            None => (segment, "[^/]+"),
+        };
+        args.push((name.to_string(), regex.to_string()));
+    }
+
+    args
+}
+
 /// Specification unit that applies to one or several URI paths.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Deceit {
+    /// Human readable identifier used in logs instead of deceit index.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Disabled deceits are skipped as if they were not defined.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Deceits with higher priority are matched first, equal ones keep specs order.
+    #[serde(default)]
+    pub priority: i32,
+
     /// List of URIs that could be string prefixed with '/'
     /// or a pattern with arguments like '/user/{user_id}'.
+    #[serde(default)]
     pub uris: Vec<String>,
 
-    /// Common response headers for current configuration unit.
+    /// Shell-like globs like '/files/*.json', checked only when none of `uris` matched.
+    /// `*` does not cross '/' while `**` does, globs do not produce path arguments.
     #[serde(default)]
+    pub uri_glob: Vec<String>,
+
+    /// Common response headers for current configuration unit.
+    /// Could be a list of `[name, value]` pairs or a map.
+    #[serde(default, deserialize_with = "deserialize_headers")]
     pub headers: Vec<(String, String)>,
 
     /// Set of simple rules to run against input request.
@@ -34,56 +102,336 @@ pub struct Deceit {
     #[serde(default)]
     pub matchers: Vec<Matcher>,
 
+    /// Combine `matchers` with OR (`any`) instead of AND (`all`, default).
+    #[serde(default)]
+    pub match_mode: MatchMode,
+
     #[serde(default)]
     pub processors: Vec<Processor>,
 
     /// Responses that can be applied after deceit level checks/matchers completed.
     #[serde(default)]
     pub responses: Vec<DeceitResponse>,
+
+    /// Response used when deceit URIs & matchers passed but none of the `responses` matched.
+    /// Unlike global 404 fallback request is considered handled by this deceit,
+    /// so next deceits are not checked.
+    #[serde(default)]
+    pub default_response: Option<DeceitResponse>,
+
+    /// Log request and rendered response bodies at debug level for this deceit only.
+    #[serde(default)]
+    pub log_bodies: bool,
+
+    /// Pretty print JSON responses on `?pretty=true` query argument and minify them on `?pretty=false`.
+    #[serde(default)]
+    pub respect_pretty_param: bool,
+
+    /// Answer CORS preflight requests for deceit URIs with this configuration.
+    /// Preflight is handled before any matchers.
+    #[serde(default)]
+    pub cors: Option<CorsSpec>,
+
+    /// Same as for [`DeceitResponse`], used for responses that do not define their own one.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+
+    /// Answer with 429 when deceit gets more requests than allowed.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+
+    /// Same as for [`DeceitResponse`], used for responses that do not define their own one.
+    #[serde(default)]
+    pub fault: Option<Fault>,
+
+    /// Values for path arguments that are empty (like `/items//5`) or not captured by matched URI.
+    #[serde(default)]
+    pub path_arg_defaults: HashMap<String, String>,
+}
+
+/// Token bucket that allows `requests` per `per_secs` seconds, bursts up to `requests` are allowed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub requests: u64,
+
+    pub per_secs: u64,
+
+    /// Separate bucket for every client IP, connection peer address is used by default.
+    #[serde(default)]
+    pub per_client: bool,
+
+    /// Use the first `X-Forwarded-For` address as client IP when header is present.
+    /// Clients could forge it to bypass the limit, so use it only behind a trusted proxy.
+    #[serde(default)]
+    pub forwarded: bool,
+}
+
+/// CORS configuration used to answer preflight `OPTIONS` requests.
+/// Allowed origin is also added to regular deceit responses.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CorsSpec {
+    #[serde(default = "default_cors_origin")]
+    pub allow_origin: String,
+
+    #[serde(default)]
+    pub allow_methods: Vec<String>,
+
+    #[serde(default)]
+    pub allow_headers: Vec<String>,
+
+    /// Seconds preflight response could be cached by the client
+    #[serde(default)]
+    pub max_age: Option<u64>,
+}
+
+/// Initial state of every scenario.
+pub const SCENARIO_STARTED: &str = "Started";
+
+/// Response is used only while named scenario is in `required_state` (any state if not set).
+/// When response is used scenario moves to `new_state` (if set).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScenarioSpec {
+    pub name: String,
+
+    #[serde(default)]
+    pub required_state: Option<String>,
+
+    #[serde(default)]
+    pub new_state: Option<String>,
+}
+
+impl ScenarioSpec {
+    pub fn new(name: &str, required_state: Option<&str>, new_state: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            required_state: required_state.map(str::to_string),
+            new_state: new_state.map(str::to_string),
+        }
+    }
+
+    fn is_active(&self, scenarios: &ScenarioStates) -> bool {
+        self.required_state
+            .as_ref()
+            .is_none_or(|required| *required == scenarios.get(&self.name))
+    }
+
+    fn transition(&self, scenarios: &ScenarioStates) {
+        if let Some(new_state) = &self.new_state {
+            log::debug!("Scenario {} moved to state {new_state}", self.name);
+            scenarios.set(&self.name, new_state);
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_cors_origin() -> String {
+    "*".to_string()
+}
+
+impl Default for CorsSpec {
+    fn default() -> Self {
+        Self {
+            allow_origin: default_cors_origin(),
+            allow_methods: Default::default(),
+            allow_headers: Default::default(),
+            max_age: None,
+        }
+    }
 }
 
 impl Deceit {
+    /// Deceit name if present or its index otherwise.
+    pub fn label(&self, idx: impl Display) -> String {
+        self.name.clone().unwrap_or_else(|| idx.to_string())
+    }
+
     pub fn match_againtst_uris(&self, request_path: &str) -> Option<Path<String>> {
         log::debug!(
-            "Checking path: {request_path} against deceit URIs: {:?}",
-            self.uris
+            "Checking path: {request_path} against deceit URIs: {:?} globs: {:?}",
+            self.uris,
+            self.uri_glob
         );
 
         let mut path = Path::new(request_path.to_string());
 
-        let resource = ResourceDef::new(self.uris.clone());
+        if !self.uris.is_empty() {
+            let resource = ResourceDef::new(self.uris.clone());
+            if resource.capture_match_info(&mut path) {
+                return Some(path);
+            }
+        }
 
-        if resource.capture_match_info(&mut path) {
-            Some(path)
-        } else {
-            None
+        self.match_uri_glob(request_path).then_some(path)
+    }
+
+    /// Collect path arguments captured by [`Self::match_againtst_uris`] filling gaps with [`Self::path_arg_defaults`].
+    pub fn path_args(&self, path: &Path<String>) -> HashMap<String, String> {
+        let mut args: HashMap<String, String> = path
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        for (name, default) in &self.path_arg_defaults {
+            let arg = args.entry(name.clone()).or_default();
+            if arg.is_empty() {
+                arg.clone_from(default);
+            }
+        }
+
+        args
+    }
+
+    /// Same checks as done for all deceits on server start.
+    pub fn check_uris(&self) -> color_eyre::Result<()> {
+        self.compile_uri_glob()?;
+        self.check_path_arg_defaults()
+    }
+
+    /// Every [`Self::path_arg_defaults`] key must be missing in some URI or captured by a segment that could be empty,
+    /// otherwise the default would never be used.
+    pub fn check_path_arg_defaults(&self) -> color_eyre::Result<()> {
+        // Globs do not produce path arguments at all
+        if !self.uri_glob.is_empty() {
+            return Ok(());
+        }
+
+        for name in self.path_arg_defaults.keys() {
+            let used = self.uris.iter().any(|uri| {
+                match uri_pattern_args(uri)
+                    .into_iter()
+                    .find(|(arg, _)| arg == name)
+                {
+                    None => true,
+                    Some((_, regex)) => {
+                        regex::Regex::new(&format!("^(?:{regex})$")).is_ok_and(|re| re.is_match(""))
+                    }
+                }
+            });
+            if !used {
+                bail!(
+                    "Path arg default \"{name}\" is never used, none of URIs {:?} could miss it or capture it empty",
+                    self.uris
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile all [`Self::uri_glob`] into a single matcher, so it is not done per request.
+    /// Matchers are cached by globs source, so there is no need to invalidate them on specs change.
+    pub fn compile_uri_glob(&self) -> color_eyre::Result<Arc<GlobSet>> {
+        static GLOB_SETS: LazyLock<RwLock<HashMap<Vec<String>, Arc<GlobSet>>>> =
+            LazyLock::new(Default::default);
+
+        if let Some(set) = GLOB_SETS.read().expect("RwLock failed").get(&self.uri_glob) {
+            return Ok(set.clone());
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        for glob in &self.uri_glob {
+            builder.add(
+                GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| eyre!("Invalid URI glob \"{glob}\": {e}"))?,
+            );
         }
+        let set = Arc::new(builder.build()?);
+        GLOB_SETS
+            .write()
+            .expect("RwLock failed")
+            .insert(self.uri_glob.clone(), set.clone());
+        Ok(set)
     }
 
+    fn match_uri_glob(&self, request_path: &str) -> bool {
+        if self.uri_glob.is_empty() {
+            return false;
+        }
+
+        match self.compile_uri_glob() {
+            Ok(set) => set.is_match(request_path),
+            Err(e) => {
+                log::error!("{e}");
+                false
+            }
+        }
+    }
+
+    /// Find response to process along with its index.
+    /// Default response (if any) goes after all other responses so it has `responses.len()` index.
+    /// Responses that reached `max_matches` are skipped, `hits` keeps their match counts.
+    /// Scenario responses are skipped unless scenario is in the required state.
     pub fn match_response(
         &self,
         rref: &ResourceRef,
         ctx: &RequestContext,
         rhai: &RhaiState,
-    ) -> Option<usize> {
-        if !matchers_and(rref, rhai, ctx, &self.matchers) {
+        hits: &ApateCounters,
+        scenarios: &ScenarioStates,
+    ) -> Option<(usize, &DeceitResponse)> {
+        if !self.match_matchers(rref, ctx, rhai) {
             return None;
         }
+        self.pick_response(rref, ctx, rhai, hits, scenarios)
+    }
 
-        // Deceit level matchers
+    /// Check deceit level matchers only.
+    pub fn match_matchers(
+        &self,
+        rref: &ResourceRef,
+        ctx: &RequestContext,
+        rhai: &RhaiState,
+    ) -> bool {
+        matchers_by_mode(self.match_mode, rref, rhai, ctx, &self.matchers)
+    }
+
+    /// Same as [`Self::match_response`] but deceit level matchers are expected to be checked already.
+    pub fn pick_response(
+        &self,
+        rref: &ResourceRef,
+        ctx: &RequestContext,
+        rhai: &RhaiState,
+        hits: &ApateCounters,
+        scenarios: &ScenarioStates,
+    ) -> Option<(usize, &DeceitResponse)> {
         for (idx, dr) in self.responses.iter().enumerate() {
-            if dr.matchers.is_empty() {
-                // Empty matchers - always yes
-                return Some(idx);
+            if !dr.enabled {
+                continue;
             }
             let deceit_ref = rref.with_level(idx);
 
-            if matchers_and(&deceit_ref, rhai, ctx, &dr.matchers) {
-                return Some(idx);
+            // Empty matchers list always passes
+            if !matchers_by_mode(dr.match_mode, &deceit_ref, rhai, ctx, &dr.matchers) {
+                continue;
+            }
+
+            if let Some(scenario) = &dr.scenario
+                && !scenario.is_active(scenarios)
+            {
+                continue;
+            }
+
+            if let Some(max_matches) = dr.max_matches {
+                match hits.get_and_increment(&deceit_ref.to_string()) {
+                    Ok(prev) if prev >= max_matches => continue,
+                    Ok(_) => {}
+                    Err(e) => log::error!("Can't count response {deceit_ref} matches: {e}"),
+                }
+            }
+
+            if let Some(scenario) = &dr.scenario {
+                scenario.transition(scenarios);
             }
+            return Some((idx, dr));
         }
 
-        None
+        self.default_response
+            .as_ref()
+            .map(|dr| (self.responses.len(), dr))
     }
 }
 
@@ -93,21 +441,69 @@ pub struct DeceitResponseContext {
     pub req: RequestContext,
     pub response_code: Arc<AtomicU16>,
     pub counters: ApateCounters,
+    /// Headers added by processors, they override ones from specs.
+    pub response_headers: Arc<Mutex<Vec<(String, String)>>>,
+    /// Top level reference of the deceit that handles request.
+    pub deceit_ref: ResourceRef,
+    /// Body that Rhai processor works on, see `ctx.get_body()` & `ctx.set_body(blob)`.
+    pub response_body: Arc<Mutex<Vec<u8>>>,
+}
+
+impl DeceitResponseContext {
+    /// Counter keys with [`LOCAL_COUNTER_PREFIX`] are resolved to keys visible only for current deceit.
+    pub fn counter_key<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        match key.strip_prefix(LOCAL_COUNTER_PREFIX) {
+            Some(local_key) => Cow::Owned(format!("deceit-{}:{local_key}", self.deceit_ref)),
+            None => Cow::Borrowed(key),
+        }
+    }
+
+    pub fn add_response_header(&self, key: &str, value: &str) {
+        self.response_headers
+            .lock()
+            .expect("Response headers lock failed")
+            .push((key.to_string(), value.to_string()));
+    }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DeceitResponse {
-    /// Code for this particular response
+    /// Human readable identifier used in logs instead of response index.
     #[serde(default)]
-    pub code: Option<u16>,
+    pub name: Option<String>,
+
+    /// Disabled responses are skipped, next responses are checked.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Code for this particular response, could be a minijinja template that renders a number
+    #[serde(default)]
+    pub code: Option<ResponseCode>,
 
     /// Same as for [`Deceit`] but it will check next response on a failure
     #[serde(default)]
     pub matchers: Vec<Matcher>,
 
+    /// Same as for [`Deceit`]
     #[serde(default)]
+    pub match_mode: MatchMode,
+
+    /// Response is skipped after it was used this many times, so the next one takes over.
+    #[serde(default)]
+    pub max_matches: Option<u64>,
+
+    /// Stateful behavior: response is used only in a specific scenario state.
+    #[serde(default)]
+    pub scenario: Option<ScenarioSpec>,
+
+    /// Same as for [`Deceit`], list of pairs or a map
+    #[serde(default, deserialize_with = "deserialize_headers")]
     pub headers: Vec<(String, String)>,
 
+    /// Headers added only when their matcher passes, they override regular ones.
+    #[serde(default)]
+    pub conditional_headers: Vec<ConditionalHeader>,
+
     #[serde(default)]
     pub processors: Vec<Processor>,
 
@@ -116,53 +512,309 @@ pub struct DeceitResponse {
 
     #[serde(default)]
     pub output: String,
+
+    /// Copy request headers into response, used with `echo` output type.
+    #[serde(default)]
+    pub echo_headers: bool,
+
+    /// Structured response body serialized to JSON as is.
+    /// Written as native TOML table so no need to quote JSON inside strings.
+    /// Takes precedence over `output` and its type.
+    #[serde(default)]
+    pub json_body: Option<serde_json::Value>,
+
+    /// Simulate network failure instead of sending a response or corrupt the rendered one.
+    #[serde(default)]
+    pub fault: Option<Fault>,
+
+    /// Redirect client to another location with empty response body.
+    #[serde(default)]
+    pub redirect: Option<RedirectSpec>,
+
+    /// HTTP date for `Last-Modified` header like `Wed, 21 Oct 2015 07:28:00 GMT`.
+    /// Requests with `If-Modified-Since` at or after it get 304 with empty body.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+/// Accept headers as a list of `[name, value]` pairs (allows repeated names)
+/// or as a map like `{"Content-Type" = "application/json"}`.
+fn deserialize_headers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, String)>, D::Error> {
+    struct HeadersVisitor;
+
+    impl<'de> Visitor<'de> for HeadersVisitor {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("list of [name, value] pairs or a map of headers")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut headers = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+            while let Some(header) = seq.next_element()? {
+                headers.push(header);
+            }
+            Ok(headers)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut headers = Vec::with_capacity(map.size_hint().unwrap_or_default());
+            while let Some(header) = map.next_entry()? {
+                headers.push(header);
+            }
+            Ok(headers)
+        }
+    }
+
+    deserializer.deserialize_any(HeadersVisitor)
+}
+
+/// Response code as a plain number or as a minijinja template like
+/// `"{{ 202 if ctx.inc_counter('job') < 3 else 200 }}"`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ResponseCode {
+    Code(u16),
+    Template(String),
+}
+
+/// Redirect response configuration.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedirectSpec {
+    /// Value of `Location` header, could be a minijinja template
+    pub location: String,
+
+    /// Redirection status code (301, 302, 303, 307 or 308)
+    #[serde(default = "default_redirect_code")]
+    pub code: u16,
+}
+
+fn default_redirect_code() -> u16 {
+    StatusCode::FOUND.as_u16()
+}
+
+/// Response header that is added only when request passes the matcher.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConditionalHeader {
+    pub matcher: Matcher,
+
+    pub key: String,
+
+    pub value: String,
+}
+
+/// Network failures that could be simulated for resilience testing.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// Drop connection without sending any response.
+    /// Works only for plain TCP connections. Client could observe either
+    /// "connection reset" or "connection closed" error depending on OS and timings.
+    ConnectionReset,
+
+    /// Gracefully close connection without sending any response (empty reply).
+    /// Works only for plain TCP connections.
+    EmptyResponse,
+
+    /// Send chunked response headers followed by a broken chunk and close connection.
+    /// Works only for plain TCP connections.
+    MalformedChunk,
+
+    /// Render response as usual, then flip a random byte of the body with given probability (0.0 - 1.0).
+    /// Streamed bodies are not corrupted.
+    RandomBodyCorruption { probability: f64 },
+}
+
+impl Fault {
+    /// Faults that break connection instead of sending a rendered response.
+    pub fn breaks_connection(&self) -> bool {
+        !matches!(self, Fault::RandomBodyCorruption { .. })
+    }
+}
+
+impl DeceitResponse {
+    /// Response name if present or its index otherwise.
+    pub fn label(&self, idx: usize) -> String {
+        self.name.clone().unwrap_or_else(|| idx.to_string())
+    }
+
+    /// Response fault wins over the deceit level one.
+    pub fn fault<'a>(&'a self, deceit: &'a Deceit) -> Option<&'a Fault> {
+        self.fault.as_ref().or(deceit.fault.as_ref())
+    }
+
+    /// Response `Last-Modified` date wins over the deceit level one.
+    pub fn last_modified<'a>(&'a self, deceit: &'a Deceit) -> Option<&'a str> {
+        self.last_modified
+            .as_deref()
+            .or(deceit.last_modified.as_deref())
+    }
+
+    /// Content type that is added to response unless specs define another one.
+    pub fn default_content_type(&self) -> Option<&'static str> {
+        if self.json_body.is_some() {
+            Some("application/json")
+        } else {
+            self.output_type.default_content_type()
+        }
+    }
 }
 
 pub fn create_response_context(
     ctx: RequestContext,
     cnt: ApateCounters,
+    deceit_ref: &ResourceRef,
 ) -> color_eyre::Result<DeceitResponseContext> {
     Ok(DeceitResponseContext {
         req: ctx.clone(),
         response_code: Arc::new(AtomicU16::new(0)),
         counters: cnt,
+        response_headers: Default::default(),
+        deceit_ref: deceit_ref.clone(),
+        response_body: Default::default(),
     })
 }
 
+impl Default for Deceit {
+    fn default() -> Self {
+        DeceitBuilder::with_uris::<&str>(&[]).build()
+    }
+}
+
 pub struct DeceitBuilder {
+    name: Option<String>,
+
+    disabled: bool,
+
+    priority: i32,
+
     uris: Vec<String>,
 
+    uri_glob: Vec<String>,
+
     headers: Vec<(String, String)>,
 
     matchers: Vec<Matcher>,
 
+    match_mode: MatchMode,
+
     processors: Vec<Processor>,
 
     responses: Vec<DeceitResponse>,
+
+    default_response: Option<DeceitResponse>,
+
+    log_bodies: bool,
+
+    respect_pretty_param: bool,
+
+    cors: Option<CorsSpec>,
+
+    last_modified: Option<String>,
+
+    rate_limit: Option<RateLimit>,
+
+    fault: Option<Fault>,
+
+    path_arg_defaults: HashMap<String, String>,
 }
 
 impl DeceitBuilder {
     pub fn with_uris<T: AsRef<str>>(uris: &[T]) -> Self {
         let uris = uris.iter().map(|u| u.as_ref().to_string()).collect();
         Self {
+            name: None,
+            disabled: false,
+            priority: 0,
             uris,
+            uri_glob: Vec::new(),
             headers: Vec::new(),
             matchers: Vec::new(),
+            match_mode: MatchMode::All,
             responses: Vec::new(),
             processors: Vec::new(),
+            default_response: None,
+            log_bodies: false,
+            respect_pretty_param: false,
+            cors: None,
+            last_modified: None,
+            rate_limit: None,
+            fault: None,
+            path_arg_defaults: HashMap::new(),
         }
     }
 
     pub fn build(self) -> Deceit {
         Deceit {
+            name: self.name,
+            enabled: !self.disabled,
+            priority: self.priority,
             uris: self.uris,
+            uri_glob: self.uri_glob,
             headers: self.headers,
             matchers: self.matchers,
+            match_mode: self.match_mode,
             processors: self.processors,
             responses: self.responses,
+            default_response: self.default_response,
+            log_bodies: self.log_bodies,
+            respect_pretty_param: self.respect_pretty_param,
+            cors: self.cors,
+            last_modified: self.last_modified,
+            rate_limit: self.rate_limit,
+            fault: self.fault,
+            path_arg_defaults: self.path_arg_defaults,
         }
     }
 
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Keep the spec but skip it while matching requests.
+    pub fn disable(mut self) -> Self {
+        self.disabled = true;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// HTTP date for `Last-Modified` header of all responses that do not define their own one.
+    pub fn with_last_modified(mut self, last_modified: &str) -> Self {
+        self.last_modified = Some(last_modified.to_string());
+        self
+    }
+
+    /// Allow only `requests` per `per_secs` seconds, others get 429 response.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        self.fault = Some(fault);
+        self
+    }
+
+    /// Use default value for empty or missing path argument, see [`Deceit::path_arg_defaults`].
+    pub fn with_path_arg_default(mut self, name: &str, value: &str) -> Self {
+        self.path_arg_defaults
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Add shell-like URI glob, see [`Deceit::uri_glob`].
+    pub fn add_uri_glob(mut self, glob: &str) -> Self {
+        self.uri_glob.push(glob.to_string());
+        self
+    }
+
     /// Wraps single [`Deceit`] into a [`ApateConfig`] with default parameters.
     pub fn to_app_config(self) -> ApateConfig {
         ApateConfig {
@@ -206,6 +858,29 @@ impl DeceitBuilder {
         self
     }
 
+    /// Response to use when none of the responses matched
+    pub fn with_default_response(mut self, response: DeceitResponse) -> Self {
+        self.default_response = Some(response);
+        self
+    }
+
+    /// Log request and response bodies for this deceit
+    pub fn with_log_bodies(mut self, log_bodies: bool) -> Self {
+        self.log_bodies = log_bodies;
+        self
+    }
+
+    /// Format JSON responses according to `pretty` query argument
+    pub fn with_respect_pretty_param(mut self, respect_pretty_param: bool) -> Self {
+        self.respect_pretty_param = respect_pretty_param;
+        self
+    }
+
+    pub fn with_cors(mut self, cors: CorsSpec) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
     //
     // Matchers configuration
     //
@@ -231,6 +906,33 @@ impl DeceitBuilder {
         self
     }
 
+    pub fn require_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.matchers.push(Matcher::BasicAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    pub fn require_bearer_token(mut self, token: &str) -> Self {
+        self.matchers.push(Matcher::BearerToken {
+            token: token.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require client IP (connection peer address) to be in CIDR like `10.0.0.0/8`.
+    pub fn require_remote_ip(mut self, cidr: &str) -> Self {
+        self.matchers.push(Matcher::RemoteIp {
+            cidr: cidr.to_string(),
+            forwarded: false,
+            negate: false,
+        });
+        self
+    }
+
     pub fn require_query_arg(mut self, name: &str, value: &str) -> Self {
         self.matchers.push(Matcher::QueryArg {
             name: name.to_string(),
@@ -240,10 +942,57 @@ impl DeceitBuilder {
         self
     }
 
+    pub fn require_form_arg(mut self, name: &str, value: &str) -> Self {
+        self.matchers.push(Matcher::FormArg {
+            name: name.to_string(),
+            value: value.to_string(),
+            negate: false,
+        });
+        self
+    }
+
     pub fn require_path_arg(mut self, name: &str, value: &str) -> Self {
         self.matchers.push(Matcher::PathArg {
             name: name.to_string(),
             value: value.to_string(),
+            regex: false,
+            negate: false,
+        });
+        self
+    }
+
+    /// Require path argument to match provided regular expression.
+    pub fn require_path_arg_regex(mut self, name: &str, regex: &str) -> Self {
+        self.matchers.push(Matcher::PathArg {
+            name: name.to_string(),
+            value: regex.to_string(),
+            regex: true,
+            negate: false,
+        });
+        self
+    }
+
+    pub fn require_content_type(mut self, content_type: &str) -> Self {
+        self.matchers.push(Matcher::ContentType {
+            eq: content_type.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require `Accept` header to allow given media type.
+    pub fn require_accepts(mut self, media_type: &str) -> Self {
+        self.matchers.push(Matcher::Accepts {
+            media_type: media_type.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require whole request path to match regular expression.
+    pub fn require_path(mut self, regex: &str) -> Self {
+        self.matchers.push(Matcher::Path {
+            regex: regex.to_string(),
             negate: false,
         });
         self
@@ -263,36 +1012,109 @@ impl DeceitBuilder {
         self.matchers = matchers;
         self
     }
+
+    /// Pass if any of matchers passes instead of all of them.
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
 }
+impl Default for DeceitResponse {
+    fn default() -> Self {
+        DeceitResponseBuilder::default().build()
+    }
+}
+
 #[derive(Default)]
 pub struct DeceitResponseBuilder {
-    code: Option<u16>,
+    name: Option<String>,
+
+    disabled: bool,
+
+    code: Option<ResponseCode>,
 
     matchers: Vec<Matcher>,
 
+    match_mode: MatchMode,
+
+    max_matches: Option<u64>,
+
+    scenario: Option<ScenarioSpec>,
+
     headers: Vec<(String, String)>,
 
+    conditional_headers: Vec<ConditionalHeader>,
+
     processors: Vec<Processor>,
 
     output_type: OutputType,
 
     output: String,
+
+    echo_headers: bool,
+
+    json_body: Option<serde_json::Value>,
+
+    fault: Option<Fault>,
+
+    redirect: Option<RedirectSpec>,
+
+    last_modified: Option<String>,
 }
 
 impl DeceitResponseBuilder {
     pub fn build(self) -> DeceitResponse {
         DeceitResponse {
+            name: self.name,
+            enabled: !self.disabled,
             code: self.code,
             matchers: self.matchers,
+            match_mode: self.match_mode,
+            max_matches: self.max_matches,
+            scenario: self.scenario,
             headers: self.headers,
+            conditional_headers: self.conditional_headers,
             processors: self.processors,
             output_type: self.output_type,
             output: self.output,
+            echo_headers: self.echo_headers,
+            json_body: self.json_body,
+            fault: self.fault,
+            redirect: self.redirect,
+            last_modified: self.last_modified,
         }
     }
 
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Keep the spec but skip it while matching requests.
+    pub fn disable(mut self) -> Self {
+        self.disabled = true;
+        self
+    }
+
+    /// Use this response only for the first `max_matches` matching requests.
+    pub fn with_max_matches(mut self, max_matches: u64) -> Self {
+        self.max_matches = Some(max_matches);
+        self
+    }
+
+    pub fn with_scenario(mut self, scenario: ScenarioSpec) -> Self {
+        self.scenario = Some(scenario);
+        self
+    }
+
     pub fn code(mut self, code: u16) -> Self {
-        self.code = Some(code);
+        self.code = Some(ResponseCode::Code(code));
+        self
+    }
+
+    /// Response code rendered from minijinja template
+    pub fn with_code_template(mut self, template: &str) -> Self {
+        self.code = Some(ResponseCode::Template(template.to_string()));
         self
     }
 
@@ -302,6 +1124,16 @@ impl DeceitResponseBuilder {
         self
     }
 
+    /// Add header only for requests that pass the matcher.
+    pub fn add_conditional_header(mut self, matcher: Matcher, key: &str, value: &str) -> Self {
+        self.conditional_headers.push(ConditionalHeader {
+            matcher,
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
     pub fn add_processor(mut self, processor: Processor) -> Self {
         self.processors.push(processor);
         self
@@ -317,6 +1149,39 @@ impl DeceitResponseBuilder {
         self
     }
 
+    /// Copy request headers into response, see [`OutputType::Echo`].
+    pub fn with_echo_headers(mut self, echo_headers: bool) -> Self {
+        self.echo_headers = echo_headers;
+        self
+    }
+
+    /// Respond with JSON serialized value instead of `output`
+    pub fn with_json_body(mut self, json_body: serde_json::Value) -> Self {
+        self.json_body = Some(json_body);
+        self
+    }
+
+    /// Simulate network failure instead of response
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        self.fault = Some(fault);
+        self
+    }
+
+    /// Redirect to location (minijinja template) with the given status code
+    pub fn with_redirect(mut self, location: &str, code: u16) -> Self {
+        self.redirect = Some(RedirectSpec {
+            location: location.to_string(),
+            code,
+        });
+        self
+    }
+
+    /// Set `Last-Modified` HTTP date to handle conditional `If-Modified-Since` requests
+    pub fn with_last_modified(mut self, last_modified: &str) -> Self {
+        self.last_modified = Some(last_modified.to_string());
+        self
+    }
+
     //
     // Matchers configuration
     //
@@ -342,6 +1207,33 @@ impl DeceitResponseBuilder {
         self
     }
 
+    pub fn require_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.matchers.push(Matcher::BasicAuth {
+            username: username.to_string(),
+            password: password.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    pub fn require_bearer_token(mut self, token: &str) -> Self {
+        self.matchers.push(Matcher::BearerToken {
+            token: token.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require client IP (connection peer address) to be in CIDR like `10.0.0.0/8`.
+    pub fn require_remote_ip(mut self, cidr: &str) -> Self {
+        self.matchers.push(Matcher::RemoteIp {
+            cidr: cidr.to_string(),
+            forwarded: false,
+            negate: false,
+        });
+        self
+    }
+
     pub fn require_query_arg(mut self, name: &str, value: &str) -> Self {
         self.matchers.push(Matcher::QueryArg {
             name: name.to_string(),
@@ -351,10 +1243,57 @@ impl DeceitResponseBuilder {
         self
     }
 
+    pub fn require_form_arg(mut self, name: &str, value: &str) -> Self {
+        self.matchers.push(Matcher::FormArg {
+            name: name.to_string(),
+            value: value.to_string(),
+            negate: false,
+        });
+        self
+    }
+
     pub fn require_path_arg(mut self, name: &str, value: &str) -> Self {
         self.matchers.push(Matcher::PathArg {
             name: name.to_string(),
             value: value.to_string(),
+            regex: false,
+            negate: false,
+        });
+        self
+    }
+
+    /// Require path argument to match provided regular expression.
+    pub fn require_path_arg_regex(mut self, name: &str, regex: &str) -> Self {
+        self.matchers.push(Matcher::PathArg {
+            name: name.to_string(),
+            value: regex.to_string(),
+            regex: true,
+            negate: false,
+        });
+        self
+    }
+
+    pub fn require_content_type(mut self, content_type: &str) -> Self {
+        self.matchers.push(Matcher::ContentType {
+            eq: content_type.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require `Accept` header to allow given media type.
+    pub fn require_accepts(mut self, media_type: &str) -> Self {
+        self.matchers.push(Matcher::Accepts {
+            media_type: media_type.to_string(),
+            negate: false,
+        });
+        self
+    }
+
+    /// Require whole request path to match regular expression.
+    pub fn require_path(mut self, regex: &str) -> Self {
+        self.matchers.push(Matcher::Path {
+            regex: regex.to_string(),
             negate: false,
         });
         self
@@ -374,4 +1313,59 @@ impl DeceitResponseBuilder {
         self.matchers = matchers;
         self
     }
+
+    /// Pass if any of matchers passes instead of all of them.
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn headers_as_list_of_pairs() {
+        let deceit: Deceit = toml::from_str(
+            r#"
+            uris = ["/"]
+            headers = [["Set-Cookie", "a=1"], ["Set-Cookie", "b=2"]]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            deceit.headers,
+            pairs(&[("Set-Cookie", "a=1"), ("Set-Cookie", "b=2")])
+        );
+    }
+
+    #[test]
+    fn headers_as_map() {
+        let deceit: Deceit = toml::from_str(
+            r#"
+            uris = ["/"]
+
+            [headers]
+            "Content-Type" = "application/json"
+            "X-Trace" = "on"
+
+            [[responses]]
+            headers = { "X-Response" = "1" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            deceit.headers,
+            pairs(&[("Content-Type", "application/json"), ("X-Trace", "on")])
+        );
+        assert_eq!(deceit.responses[0].headers, pairs(&[("X-Response", "1")]));
+    }
 }
@@ -0,0 +1,94 @@
+//! Access log middleware that writes one JSON line per request.
+
+use std::time::Instant;
+
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use serde::Serialize;
+
+/// Log target for access records, so they could be filtered with `RUST_LOG`.
+pub const ACCESS_LOG_TARGET: &str = "apate::access";
+
+/// Deceit that produced a response, stored in response extensions.
+#[derive(Clone, Debug)]
+pub struct MatchedDeceit {
+    pub idx: usize,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AccessLogRecord {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub duration_ms: f64,
+    /// Absent for admin API and requests that no deceit matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deceit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deceit_name: Option<String>,
+}
+
+impl AccessLogRecord {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+pub async fn json_access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+
+    let res = next.call(req).await?;
+
+    let matched = res.response().extensions().get::<MatchedDeceit>().cloned();
+    let record = AccessLogRecord {
+        method,
+        path,
+        status: res.status().as_u16(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        deceit: matched.as_ref().map(|m| m.idx),
+        deceit_name: matched.and_then(|m| m.name),
+    };
+    log::info!(target: ACCESS_LOG_TARGET, "{}", record.to_json());
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_log_record_json() {
+        let record = AccessLogRecord {
+            method: "GET".to_string(),
+            path: "/user/1".to_string(),
+            status: 200,
+            duration_ms: 1.5,
+            deceit: Some(3),
+            deceit_name: Some("user".to_string()),
+        };
+        assert_eq!(
+            record.to_json(),
+            r#"{"method":"GET","path":"/user/1","status":200,"duration_ms":1.5,"deceit":3,"deceit_name":"user"}"#
+        );
+
+        let record = AccessLogRecord {
+            deceit: None,
+            deceit_name: None,
+            status: 404,
+            ..record
+        };
+        assert!(!record.to_json().contains("deceit"));
+        assert!(record.to_json().contains(r#""status":404"#));
+    }
+}
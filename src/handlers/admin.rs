@@ -1,5 +1,10 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::atomic::Ordering,
+};
+
 use actix_web::{
-    HttpRequest, HttpResponse, get,
+    HttpRequest, HttpResponse, delete, get,
     http::header::CONTENT_TYPE,
     post, routes,
     web::{self, Bytes, Data, ServiceConfig},
@@ -7,7 +12,16 @@ use actix_web::{
 use include_dir::{Dir, include_dir};
 use serde::Serialize;
 
-use crate::{ApateSpecs, ApateState};
+use super::curl::specs_to_curl_script;
+use crate::{
+    ApateSpecs, ApateState,
+    deceit::{Deceit, DeceitResponse, ResponseCode},
+    journal::RequestsCriteria,
+    matchers::{BinaryEncoding, Matcher},
+    output::OutputType,
+    processors::Processor,
+    rhai::RhaiState,
+};
 
 pub const ADMIN_API: &str = "/apate";
 
@@ -21,13 +35,31 @@ struct ApateInfo<'a> {
     version: &'a str,
 }
 
+/// Probes go first, admin scope itself is registered before mock requests handler.
 pub fn admin_service_config(cfg: &mut ServiceConfig) {
-    cfg.service(apate_ui)
+    cfg.service(apate_health)
+        .service(apate_ready)
+        .service(apate_ui)
         .service(apate_info)
         .service(specification_get)
         .service(specification_replace)
         .service(specification_append)
         .service(specification_prepend)
+        .service(specification_validate)
+        .service(specification_curl)
+        .service(deceit_get)
+        .service(deceit_add)
+        .service(deceit_delete)
+        .service(deceit_toggle)
+        .service(requests_get)
+        .service(requests_har)
+        .service(requests_clear)
+        .service(requests_count)
+        .service(scenarios_get)
+        .service(scenarios_reset)
+        .service(scenario_reset)
+        .service(remote_cache_purge)
+        .service(apate_drain)
         .service(admin_assets);
 }
 
@@ -62,6 +94,36 @@ async fn apate_info() -> HttpResponse {
     }
 }
 
+#[get("/health")]
+async fn apate_health() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "application/json"))
+        .body(r#"{"status":"ok"}"#)
+}
+
+/// Specs are loaded before server starts, so it is ready unless draining.
+#[get("/ready")]
+async fn apate_ready(state: Data<ApateState>) -> HttpResponse {
+    if state.draining.load(Ordering::Relaxed) {
+        return HttpResponse::ServiceUnavailable()
+            .insert_header(("Content-Type", "application/json"))
+            .body(r#"{"status":"draining"}"#);
+    }
+
+    let deceits = state.specs.read().await.deceit.len();
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "application/json"))
+        .body(format!(r#"{{"status":"ready","deceits":{deceits}}}"#))
+}
+
+/// Stop serving mock requests before teardown. Admin API stays available.
+#[post("/drain")]
+async fn apate_drain(state: Data<ApateState>) -> HttpResponse {
+    state.draining.store(true, Ordering::Relaxed);
+    log::info!("Server is draining, mock requests will be rejected");
+    HttpResponse::Ok().body("Server is draining".to_string())
+}
+
 #[get("/specs")]
 async fn specification_get(state: Data<ApateState>) -> HttpResponse {
     let specs = state.specs.read().await;
@@ -76,22 +138,36 @@ async fn specification_get(state: Data<ApateState>) -> HttpResponse {
     }
 }
 
+/// Shell script with example `curl` command for every deceit URI and method.
+#[get("/specs/curl")]
+async fn specification_curl(req: HttpRequest, state: Data<ApateState>) -> HttpResponse {
+    let base_url = {
+        let info = req.connection_info();
+        format!("{}://{}", info.scheme(), info.host())
+    };
+    let specs = state.specs.read().await;
+
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/plain"))
+        .body(specs_to_curl_script(&specs, &base_url))
+}
+
 #[post("/specs/replace")]
 async fn specification_replace(
     _req: HttpRequest,
     body: Bytes,
     state: Data<ApateState>,
 ) -> HttpResponse {
-    let new_specs = match parse_input_toml(&body) {
+    let new_specs = match parse_checked_specs(&body) {
         Ok(specs) => specs,
-        Err(err_response) => return err_response,
+        Err(err_response) => return *err_response,
     };
 
     let mut specs = state.specs.write().await;
     *specs = new_specs;
+    specs.sort_by_priority();
 
-    state.clear_cache();
-    state.rhai.clear_and_update(specs.rhai.clone());
+    state.reload_caches(&specs);
 
     HttpResponse::Ok().body("Specification replaced".to_string())
 }
@@ -102,17 +178,17 @@ async fn specification_prepend(
     body: Bytes,
     state: Data<ApateState>,
 ) -> HttpResponse {
-    let new_specs = match parse_input_toml(&body) {
+    let new_specs = match parse_checked_specs(&body) {
         Ok(specs) => specs,
-        Err(err_response) => return err_response,
+        Err(err_response) => return *err_response,
     };
 
     let mut specs = state.specs.write().await;
 
     specs.prepend(new_specs);
+    specs.sort_by_priority();
 
-    state.clear_cache();
-    state.rhai.clear_and_update(specs.rhai.clone());
+    state.reload_caches(&specs);
 
     HttpResponse::Ok().body("New specification prepended to the existing one".to_string())
 }
@@ -123,29 +199,445 @@ async fn specification_append(
     body: Bytes,
     state: Data<ApateState>,
 ) -> HttpResponse {
-    let new_specs = match parse_input_toml(&body) {
+    let new_specs = match parse_checked_specs(&body) {
         Ok(specs) => specs,
-        Err(err_response) => return err_response,
+        Err(err_response) => return *err_response,
     };
 
     let mut specs = state.specs.write().await;
 
     specs.append(new_specs);
+    specs.sort_by_priority();
 
-    state.clear_cache();
-    state.rhai.clear_and_update(specs.rhai.clone());
+    state.reload_caches(&specs);
 
     HttpResponse::Ok().body("New specification appended to the existing one".to_string())
 }
 
-fn parse_input_toml(body: &Bytes) -> Result<ApateSpecs, HttpResponse> {
+/// Dry run for specs: compile all Rhai scripts and Jinja templates without applying them.
+#[post("/specs/validate")]
+async fn specification_validate(body: Bytes, state: Data<ApateState>) -> HttpResponse {
+    let specs = match parse_input_toml(&body) {
+        Ok(specs) => specs,
+        Err(err_response) => return *err_response,
+    };
+
+    let report = SpecsValidator::new(&specs, &state.rhai).validate(&specs);
+
+    match serde_json::to_string(&report) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(("Content-Type", "application/json"))
+            .body(json),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Serialize? Not able to! {err}"))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SpecsValidationReport {
+    valid: bool,
+    errors: Vec<SpecsValidationError>,
+}
+
+#[derive(Serialize)]
+struct SpecsValidationError {
+    /// Location of the script or template in specs like `deceit[0].responses[1].output`
+    id: String,
+    error: String,
+}
+
+struct SpecsValidator<'a> {
+    rhai: &'a RhaiState,
+    script_ids: HashSet<&'a str>,
+    errors: Vec<SpecsValidationError>,
+}
+
+impl<'a> SpecsValidator<'a> {
+    fn new(specs: &'a ApateSpecs, rhai: &'a RhaiState) -> Self {
+        Self {
+            rhai,
+            script_ids: specs.rhai.iter().map(|s| s.id.as_str()).collect(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn validate(mut self, specs: &ApateSpecs) -> SpecsValidationReport {
+        for script in &specs.rhai {
+            self.check_rhai(format!("rhai.{}", script.id), &script.script);
+        }
+        for (name, fragment) in &specs.fragments {
+            self.check_jinja(format!("fragments.{name}"), fragment);
+        }
+
+        for (idx, d) in specs.deceit.iter().enumerate() {
+            let id = format!("deceit[{idx}]");
+            if let Err(e) = d.compile_uri_glob() {
+                self.errors.push(SpecsValidationError {
+                    id: format!("{id}.uri_glob"),
+                    error: e.to_string(),
+                });
+            }
+            if let Err(e) = d.check_path_arg_defaults() {
+                self.errors.push(SpecsValidationError {
+                    id: format!("{id}.path_arg_defaults"),
+                    error: e.to_string(),
+                });
+            }
+            self.check_matchers(&id, &d.matchers);
+            self.check_processors(&id, &d.processors);
+            for (ridx, dresp) in d.responses.iter().enumerate() {
+                self.check_response(&format!("{id}.responses[{ridx}]"), dresp);
+            }
+            if let Some(dresp) = &d.default_response {
+                self.check_response(&format!("{id}.default_response"), dresp);
+            }
+        }
+
+        if let Some(dresp) = &specs.default_deceit {
+            self.check_response("default_deceit", dresp);
+        }
+
+        SpecsValidationReport {
+            valid: self.errors.is_empty(),
+            errors: self.errors,
+        }
+    }
+
+    fn check_response(&mut self, id: &str, dresp: &DeceitResponse) {
+        self.check_matchers(id, &dresp.matchers);
+        self.check_processors(id, &dresp.processors);
+
+        let output_id = format!("{id}.output");
+        match &dresp.output_type {
+            OutputType::Jinja
+            | OutputType::JinjaHex
+            | OutputType::JinjaBase64
+            | OutputType::Csv => self.check_jinja(output_id, &dresp.output),
+            OutputType::Rhai | OutputType::RhaiStream => self.check_rhai(output_id, &dresp.output),
+            OutputType::RhaiRef { id, .. } => self.check_rhai_ref(output_id, id),
+            _ => {}
+        }
+
+        if let Some(redirect) = &dresp.redirect {
+            self.check_jinja(format!("{id}.redirect.location"), &redirect.location);
+        }
+        if let Some(ResponseCode::Template(tpl)) = &dresp.code {
+            self.check_jinja(format!("{id}.code"), tpl);
+        }
+    }
+
+    fn check_matchers(&mut self, id: &str, matchers: &[Matcher]) {
+        for (idx, matcher) in matchers.iter().enumerate() {
+            let matcher_id = format!("{id}.matchers[{idx}]");
+            match matcher {
+                Matcher::Rhai { script, .. } => self.check_rhai(matcher_id, script),
+                Matcher::RhaiRef { id, .. } => self.check_rhai_ref(matcher_id, id),
+                Matcher::Xpath { path, .. } => self.check_xpath(matcher_id, path),
+                Matcher::Path { regex, .. } => self.check_regex(matcher_id, regex),
+                Matcher::RawPath {
+                    eq, regex: true, ..
+                } => self.check_regex(matcher_id, eq),
+                Matcher::PathArg {
+                    value, regex: true, ..
+                } => self.check_regex(matcher_id, value),
+                Matcher::BodyBytes {
+                    encoding, value, ..
+                } => self.check_body_bytes(matcher_id, *encoding, value),
+                Matcher::And { matchers } | Matcher::Or { matchers } => {
+                    self.check_matchers(&matcher_id, matchers)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_processors(&mut self, id: &str, processors: &[Processor]) {
+        for (idx, processor) in processors.iter().enumerate() {
+            let processor_id = format!("{id}.processors[{idx}]");
+            match processor {
+                Processor::Rhai { script } => self.check_rhai(processor_id, script),
+                Processor::RhaiRef { id, .. } => self.check_rhai_ref(processor_id, id),
+                Processor::JsonMergePatch { patch } => self.check_jinja(processor_id, patch),
+                Processor::Replace {
+                    pattern,
+                    regex: true,
+                    ..
+                } => self.check_regex(processor_id, pattern),
+                #[cfg(feature = "jsonschema")]
+                Processor::ValidateResponse { schema } => self.check_schema(processor_id, schema),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_rhai(&mut self, id: String, script: &str) {
+        if let Err(e) = self.rhai.compile(script) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Rhai script compilation failed: {e}"),
+            });
+        }
+    }
+
+    fn check_rhai_ref(&mut self, id: String, script_id: &str) {
+        if !self.script_ids.contains(script_id) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("No Rhai script with id: {script_id}"),
+            });
+        }
+    }
+
+    fn check_regex(&mut self, id: String, pattern: &str) {
+        if let Err(e) = regex::Regex::new(pattern) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Invalid regex: {e}"),
+            });
+        }
+    }
+
+    #[cfg(feature = "jsonschema")]
+    fn check_schema(&mut self, id: String, schema: &serde_json::Value) {
+        if let Err(e) = jsonschema::validator_for(schema) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Invalid JSON Schema: {e}"),
+            });
+        }
+    }
+
+    fn check_body_bytes(&mut self, id: String, encoding: BinaryEncoding, value: &str) {
+        if let Err(e) = encoding.decode(value) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Invalid {encoding:?} body bytes: {e}"),
+            });
+        }
+    }
+
+    fn check_xpath(&mut self, id: String, path: &str) {
+        if let Err(e) = sxd_xpath::Factory::new().build(path) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Invalid XPath expression: {e}"),
+            });
+        }
+    }
+
+    fn check_jinja(&mut self, id: String, template: &str) {
+        if let Err(e) = minijinja::Environment::new().template_from_str(template) {
+            self.errors.push(SpecsValidationError {
+                id,
+                error: format!("Jinja template compilation failed: {e}"),
+            });
+        }
+    }
+}
+
+#[get("/specs/deceit/{index}")]
+async fn deceit_get(path: web::Path<usize>, state: Data<ApateState>) -> HttpResponse {
+    let index = path.into_inner();
+    let specs = state.specs.read().await;
+
+    let Some(deceit) = specs.deceit.get(index) else {
+        return deceit_not_found(index);
+    };
+
+    match serde_json::to_string(deceit) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(("Content-Type", "application/json"))
+            .body(json),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Serialize? Not able to! {err}"))
+        }
+    }
+}
+
+/// Append single deceit from JSON body, responds with its index.
+#[post("/specs/deceit")]
+async fn deceit_add(body: Bytes, state: Data<ApateState>) -> HttpResponse {
+    let deceit = match serde_json::from_slice::<Deceit>(&body) {
+        Ok(d) => d,
+        Err(e) => {
+            return HttpResponse::BadRequest().body(format!(
+                "Failed to parse JSON deceit from request body: {e}"
+            ));
+        }
+    };
+
+    if let Err(e) = deceit.check_uris() {
+        return HttpResponse::BadRequest().body(format!("Invalid deceit: {e}"));
+    }
+
+    let priority = deceit.priority;
+    let mut specs = state.specs.write().await;
+    specs.deceit.push(deceit);
+    specs.sort_by_priority();
+    // Sort is stable, so the new deceit is the last one with its priority
+    let index = specs
+        .deceit
+        .iter()
+        .filter(|d| d.priority >= priority)
+        .count()
+        - 1;
+
+    state.reload_caches(&specs);
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "application/json"))
+        .body(format!(r#"{{"index":{index}}}"#))
+}
+
+#[delete("/specs/deceit/{index}")]
+async fn deceit_delete(path: web::Path<usize>, state: Data<ApateState>) -> HttpResponse {
+    let index = path.into_inner();
+    let mut specs = state.specs.write().await;
+
+    if index >= specs.deceit.len() {
+        return deceit_not_found(index);
+    }
+
+    specs.deceit.remove(index);
+
+    // Cached scripts & templates are bound to deceit indexes
+    state.reload_caches(&specs);
+
+    HttpResponse::Ok().body(format!("Deceit {index} removed"))
+}
+
+/// Flip deceit `enabled` flag, responds with the new value.
+#[post("/specs/deceit/{index}/toggle")]
+async fn deceit_toggle(path: web::Path<usize>, state: Data<ApateState>) -> HttpResponse {
+    let index = path.into_inner();
+    let mut specs = state.specs.write().await;
+
+    let Some(deceit) = specs.deceit.get_mut(index) else {
+        return deceit_not_found(index);
+    };
+    deceit.enabled = !deceit.enabled;
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "application/json"))
+        .body(format!(r#"{{"enabled":{}}}"#, deceit.enabled))
+}
+
+fn deceit_not_found(index: usize) -> HttpResponse {
+    HttpResponse::NotFound().body(format!("There is no deceit with index {index}"))
+}
+
+#[get("/requests")]
+async fn requests_get(state: Data<ApateState>) -> HttpResponse {
+    match serde_json::to_string(&state.journal.list()) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(("Content-Type", "application/json"))
+            .body(json),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Serialize? Not able to! {err}"))
+        }
+    }
+}
+
+/// Export recorded requests & responses as HAR (HTTP Archive) 1.2 document.
+#[get("/requests/har")]
+async fn requests_har(state: Data<ApateState>) -> HttpResponse {
+    match serde_json::to_string(&state.journal.to_har()) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(("Content-Type", "application/json"))
+            .body(json),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Serialize? Not able to! {err}"))
+        }
+    }
+}
+
+#[delete("/requests")]
+async fn requests_clear(state: Data<ApateState>) -> HttpResponse {
+    state.journal.clear();
+    HttpResponse::Ok().body("Requests journal cleared".to_string())
+}
+
+#[post("/requests/count")]
+async fn requests_count(body: Bytes, state: Data<ApateState>) -> HttpResponse {
+    let criteria = match serde_json::from_slice::<RequestsCriteria>(&body) {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::BadRequest().body(format!(
+                "Failed to parse JSON criteria from request body: {e}"
+            ));
+        }
+    };
+
+    let count = state.journal.count(&criteria, &state.rhai);
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "application/json"))
+        .body(format!(r#"{{"count":{count}}}"#))
+}
+
+/// Current state of every scenario mentioned in specs.
+#[get("/scenarios")]
+async fn scenarios_get(state: Data<ApateState>) -> HttpResponse {
+    let specs = state.specs.read().await;
+    let scenarios: BTreeMap<&str, String> = specs
+        .deceit
+        .iter()
+        .flat_map(|d| d.responses.iter().chain(d.default_response.as_ref()))
+        .filter_map(|dr| dr.scenario.as_ref())
+        .map(|scenario| (scenario.name.as_str(), state.scenarios.get(&scenario.name)))
+        .collect();
+
+    match serde_json::to_string(&scenarios) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(("Content-Type", "application/json"))
+            .body(json),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Serialize? Not able to! {err}"))
+        }
+    }
+}
+
+#[delete("/scenarios")]
+async fn scenarios_reset(state: Data<ApateState>) -> HttpResponse {
+    state.scenarios.reset_all();
+    HttpResponse::Ok().body("All scenarios reset".to_string())
+}
+
+#[delete("/scenarios/{name}")]
+async fn scenario_reset(name: web::Path<String>, state: Data<ApateState>) -> HttpResponse {
+    state.scenarios.reset(&name);
+    HttpResponse::Ok().body(format!("Scenario {name} reset"))
+}
+
+/// Remote cached outputs are fetched again on next use.
+#[delete("/remote-cache")]
+async fn remote_cache_purge(state: Data<ApateState>) -> HttpResponse {
+    state.remote_cache.clear();
+    HttpResponse::Ok().body("Remote cache purged".to_string())
+}
+
+fn parse_input_toml(body: &Bytes) -> Result<ApateSpecs, Box<HttpResponse>> {
     let body_str = String::from_utf8_lossy(body);
 
     toml::from_str::<ApateSpecs>(&body_str).map_err(|e| {
-        HttpResponse::BadRequest().body(format!("Failed to parse TOML from request body: {e:?}"))
+        Box::new(
+            HttpResponse::BadRequest()
+                .body(format!("Failed to parse TOML from request body: {e:?}")),
+        )
     })
 }
 
+/// Refuse specs that would fail on server start, like invalid URI globs.
+/// Existing deceits were checked already, so checking new ones is enough before merging them.
+fn parse_checked_specs(body: &Bytes) -> Result<ApateSpecs, Box<HttpResponse>> {
+    let specs = parse_input_toml(body)?;
+    specs
+        .check_uris()
+        .map_err(|e| Box::new(HttpResponse::BadRequest().body(format!("Invalid specs: {e}"))))?;
+    Ok(specs)
+}
+
 #[get("/assets/{filename:.*}")]
 async fn admin_assets(path: web::Path<String>) -> HttpResponse {
     let filename = path.into_inner();
@@ -0,0 +1,178 @@
+//! Generate shell script with example `curl` calls for all deceits.
+//! Requirements for requests are inferred from deceit level matchers.
+
+use std::fmt::Write as _;
+
+use crate::{
+    ApateSpecs,
+    matchers::{MatchMode, Matcher},
+};
+
+/// Methods are looked up in method matcher value, which could be like `GET|POST`.
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+/// Request requirements collected from deceit matchers.
+#[derive(Default)]
+struct CurlRequest {
+    methods: Vec<&'static str>,
+    headers: Vec<(String, String)>,
+    query_args: Vec<(String, String)>,
+    form_args: Vec<(String, String)>,
+    basic_auth: Option<String>,
+    path_args: Vec<(String, String)>,
+}
+
+impl CurlRequest {
+    fn from_matchers(matchers: &[Matcher]) -> Self {
+        let mut request = Self::default();
+        request.collect(matchers);
+        request
+    }
+
+    /// Negated and OR matchers could not be turned into a single request, so they are skipped.
+    fn collect(&mut self, matchers: &[Matcher]) {
+        for matcher in matchers {
+            match matcher {
+                Matcher::And { matchers } => self.collect(matchers),
+                Matcher::Method { eq, negate: false } => {
+                    let eq = eq.to_uppercase();
+                    self.methods
+                        .extend(METHODS.iter().copied().filter(|m| eq.contains(m)));
+                }
+                Matcher::Header {
+                    key,
+                    value,
+                    negate: false,
+                } => self.headers.push((key.clone(), value.clone())),
+                Matcher::BasicAuth {
+                    username,
+                    password,
+                    negate: false,
+                } => self.basic_auth = Some(format!("{username}:{password}")),
+                Matcher::BearerToken {
+                    token,
+                    negate: false,
+                } => self
+                    .headers
+                    .push(("Authorization".to_string(), format!("Bearer {token}"))),
+                Matcher::ContentType { eq, negate: false } if !eq.contains('*') => {
+                    self.headers.push(("Content-Type".to_string(), eq.clone()))
+                }
+                Matcher::Accepts {
+                    media_type,
+                    negate: false,
+                } if !media_type.contains('*') => self
+                    .headers
+                    .push(("Accept".to_string(), media_type.clone())),
+                Matcher::QueryArg {
+                    name,
+                    value,
+                    negate: false,
+                } => self.query_args.push((name.clone(), value.clone())),
+                Matcher::FormArg {
+                    name,
+                    value,
+                    negate: false,
+                } => self.form_args.push((name.clone(), value.clone())),
+                Matcher::PathArg {
+                    name,
+                    value,
+                    regex: false,
+                    negate: false,
+                } => self.path_args.push((name.clone(), value.clone())),
+                _ => {}
+            }
+        }
+    }
+
+    /// Replace `{arg}` and `{arg:regex}` placeholders with required path args values.
+    /// Unknown args are left as `{arg}`.
+    fn uri(&self, pattern: &str) -> String {
+        let mut uri = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            uri.push_str(&rest[..start]);
+
+            let placeholder = &rest[start + 1..start + len];
+            let name = placeholder.split(':').next().unwrap_or_default();
+            match self.path_args.iter().find(|(n, _)| n == name) {
+                Some((_, value)) => uri.push_str(value),
+                None => uri.push_str(&format!("{{{name}}}")),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        uri.push_str(rest);
+
+        if !self.query_args.is_empty()
+            && let Ok(query) = serde_urlencoded::to_string(&self.query_args)
+        {
+            uri.push('?');
+            uri.push_str(&query);
+        }
+        uri
+    }
+}
+
+/// Deceits without method matcher get a GET request, requests are sent to `$BASE_URL`.
+pub fn specs_to_curl_script(specs: &ApateSpecs, base_url: &str) -> String {
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str("# Example requests for apate deceits\n\n");
+    let _ = writeln!(script, "BASE_URL=\"${{BASE_URL:-{base_url}}}\"");
+
+    for (idx, deceit) in specs.deceit.iter().enumerate() {
+        // Any of matchers is enough, so none of them is required
+        let request = match deceit.match_mode {
+            MatchMode::All => CurlRequest::from_matchers(&deceit.matchers),
+            MatchMode::Any => CurlRequest::default(),
+        };
+        let methods = if request.methods.is_empty() {
+            vec!["GET"]
+        } else {
+            request.methods.clone()
+        };
+
+        let _ = writeln!(script, "\n# deceit[{idx}]");
+        for uri in &deceit.uris {
+            let uri = request.uri(uri);
+            for method in &methods {
+                script.push_str(&curl_command(method, &uri, &request));
+                script.push('\n');
+            }
+        }
+    }
+
+    script
+}
+
+fn curl_command(method: &str, uri: &str, request: &CurlRequest) -> String {
+    let mut cmd = format!("curl -X {method}");
+    // Unresolved path args look like URL globbing for curl
+    if uri.contains('{') {
+        cmd.push_str(" -g");
+    }
+    if let Some(credentials) = &request.basic_auth {
+        let _ = write!(cmd, " -u {}", shell_quote(credentials));
+    }
+    for (key, value) in &request.headers {
+        let _ = write!(cmd, " -H {}", shell_quote(&format!("{key}: {value}")));
+    }
+    // curl sends urlencoded form content type by default
+    for (name, value) in &request.form_args {
+        let _ = write!(
+            cmd,
+            " --data-urlencode {}",
+            shell_quote(&format!("{name}={value}"))
+        );
+    }
+    let _ = write!(cmd, " \"$BASE_URL\"{}", shell_quote(uri));
+    cmd
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
@@ -0,0 +1,113 @@
+//! Simulation of network failures for resilience testing.
+
+use std::{any::Any, io::Write as _, net::Shutdown, sync::Arc, time::Duration};
+
+use actix_web::{
+    HttpResponse,
+    body::{BoxBody, MessageBody as _},
+    dev::Extensions,
+    rt::net::TcpStream,
+    web::BytesMut,
+};
+use rand::Rng as _;
+use socket2::{SockRef, Socket};
+
+use crate::{ApateState, deceit::Fault};
+
+/// Duplicated handle of the client connection socket.
+/// Allows handlers to break the connection that actix owns.
+#[derive(Clone)]
+pub(crate) struct ConnectionSocket(Arc<Socket>);
+
+/// Keep connection socket handle in connection data for faults simulation.
+/// Socket is duplicated only while specs have connection breaking faults,
+/// so connections opened before such faults were added can't be broken.
+/// Only plain TCP connections are supported.
+pub fn on_connect(state: &ApateState, conn: &dyn Any, data: &mut Extensions) {
+    // Specs locked for update may get faults, so the socket is kept then
+    if let Some(specs) = state.specs.try_read()
+        && !specs.has_connection_faults()
+    {
+        return;
+    }
+
+    let Some(stream) = conn.downcast_ref::<TcpStream>() else {
+        return;
+    };
+
+    match SockRef::from(stream).try_clone() {
+        Ok(socket) => {
+            data.insert(ConnectionSocket(Arc::new(socket)));
+        }
+        Err(e) => log::warn!("Can't keep connection socket, faults will not work: {e}"),
+    }
+}
+
+/// Response head followed by a chunk which size line is not a HEX number.
+const MALFORMED_CHUNK: &[u8] =
+    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nZZ\r\nmalformed chunk\r\n";
+
+/// Break client connection according to the fault.
+/// Returned response is never delivered if the fault was applied.
+pub(crate) fn apply_fault(fault: &Fault, socket: Option<&ConnectionSocket>) -> HttpResponse {
+    let Some(ConnectionSocket(socket)) = socket else {
+        log::error!(
+            "Can't simulate {fault:?} fault, no connection socket available (connection may predate the fault)"
+        );
+        return HttpResponse::InternalServerError().body(format!("Can't simulate {fault:?}\n"));
+    };
+
+    match fault {
+        Fault::ConnectionReset => {
+            // Zero linger makes OS send RST instead of FIN when socket is finally closed
+            let result = socket
+                .set_linger(Some(Duration::ZERO))
+                .and_then(|_| socket.shutdown(Shutdown::Both));
+            if let Err(e) = result {
+                log::error!("Can't reset connection: {e}");
+            }
+        }
+        Fault::EmptyResponse => {
+            if let Err(e) = socket.shutdown(Shutdown::Both) {
+                log::error!("Can't close connection: {e}");
+            }
+        }
+        Fault::MalformedChunk => {
+            let result = (&**socket)
+                .write_all(MALFORMED_CHUNK)
+                .and_then(|_| socket.shutdown(Shutdown::Both));
+            if let Err(e) = result {
+                log::error!("Can't send malformed chunk: {e}");
+            }
+        }
+        Fault::RandomBodyCorruption { .. } => {
+            log::error!("{fault:?} does not break connection, response must be rendered");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    HttpResponse::InternalServerError().finish()
+}
+
+/// Flip one random byte of the response body with given probability.
+/// Content length is kept, so clients get corrupted content instead of a protocol error.
+pub(crate) fn corrupt_body(response: HttpResponse, probability: f64) -> HttpResponse {
+    let mut rng = rand::rng();
+    if !rng.random_bool(probability.clamp(0.0, 1.0)) {
+        return response;
+    }
+
+    let (response, body) = response.into_parts();
+    let body = match body.try_into_bytes() {
+        Ok(bytes) if !bytes.is_empty() => {
+            let mut corrupted = BytesMut::from(bytes);
+            let idx = rng.random_range(0..corrupted.len());
+            corrupted[idx] ^= rng.random_range(1..=u8::MAX);
+            log::debug!("Response body corrupted at byte {idx}");
+            BoxBody::new(corrupted.freeze())
+        }
+        Ok(bytes) => BoxBody::new(bytes),
+        Err(body) => body,
+    };
+    response.set_body(body)
+}
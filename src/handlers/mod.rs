@@ -1,127 +1,493 @@
 //! This module has server logic to handle all URI requests.
 
+mod access_log;
 #[cfg(feature = "server")]
 mod admin;
+#[cfg(feature = "server")]
+mod curl;
+mod fault;
+mod websocket;
 
-use std::sync::atomic::Ordering;
+use std::{
+    fmt::Write as _,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
+pub use access_log::{ACCESS_LOG_TARGET, MatchedDeceit, json_access_log};
 #[cfg(feature = "server")]
 pub use admin::{ADMIN_API, admin_service_config};
+pub use fault::on_connect;
 
 use actix_web::{
-    HttpRequest, HttpResponse, HttpResponseBuilder,
-    http::StatusCode,
-    web::{Bytes, Data},
+    FromRequest as _, HttpRequest, HttpResponse, HttpResponseBuilder,
+    body::{BoxBody, MessageBody as _},
+    http::{
+        StatusCode,
+        header::{
+            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, CONTENT_TYPE, HeaderName,
+            HeaderValue, LAST_MODIFIED, LOCATION, RETRY_AFTER,
+        },
+    },
+    web::{self, Bytes, Data},
 };
+use futures::StreamExt as _;
+
+use fault::{ConnectionSocket, apply_fault, corrupt_body};
+use websocket::{WsUpgrade, is_websocket_upgrade, websocket_response};
 
 use crate::{
     ApateState, RequestContext, ResourceRef,
-    deceit::{DEFAULT_RESPONSE_CODE, create_response_context},
-    processors::apply_processors,
+    deceit::{
+        CorsSpec, DEFAULT_RESPONSE_CODE, Deceit, DeceitResponse, DeceitResponseContext, Fault,
+        RateLimit, RedirectSpec, ResponseCode, create_response_context,
+    },
+    journal::{RecordedRequest, RecordedResponse},
+    matchers::{is_matcher_approves, media_type_matches},
+    output::{OutputType, output_redirect_location, output_response_code, output_response_stream},
+    processors::{apply_pre_processors, apply_processors},
 };
 
+/// Value of `Retry-After` header for requests rejected while server is draining
+const DRAIN_RETRY_AFTER_SECS: u64 = 5;
+
+/// Request header that asks for match diagnostics when match trace is enabled.
+const TRACE_HEADER: &str = "x-apate-trace";
+const MATCHED_DECEIT_HEADER: HeaderName = HeaderName::from_static("x-apate-matched-deceit");
+const MATCHED_RESPONSE_HEADER: HeaderName = HeaderName::from_static("x-apate-matched-response");
+
 /// Handle all apate server requests
 pub async fn apate_server_handler(
     req: HttpRequest,
-    body: Bytes,
+    payload: web::Payload,
     state: Data<ApateState>,
 ) -> HttpResponse {
-    deceit_handler(req, body, state).await
+    if is_websocket_upgrade(&req) {
+        let upgrade = WsUpgrade {
+            req: req.clone(),
+            payload,
+        };
+        return deceit_handler(req, Bytes::new(), state, Some(upgrade)).await;
+    }
+
+    // Regular extractor keeps body size limit and decompression
+    match Bytes::from_request(&req, &mut payload.into_inner()).await {
+        Ok(body) => deceit_handler(req, body, state, None).await,
+        Err(e) => e.error_response(),
+    }
 }
 
-async fn deceit_handler(req: HttpRequest, body: Bytes, state: Data<ApateState>) -> HttpResponse {
-    let deceit = &state.specs.read().await.deceit;
+async fn deceit_handler(
+    req: HttpRequest,
+    body: Bytes,
+    state: Data<ApateState>,
+    upgrade: Option<WsUpgrade>,
+) -> HttpResponse {
+    if state.draining.load(Ordering::Relaxed) {
+        return HttpResponse::ServiceUnavailable()
+            .insert_header((RETRY_AFTER, DRAIN_RETRY_AFTER_SECS.to_string()))
+            .body("Server is draining, no new requests accepted\n");
+    }
 
+    let socket = req.conn_data::<ConnectionSocket>().cloned();
     let mut ctx = RequestContext::new(req, body);
 
-    for (deceit_idx, d) in deceit.iter().enumerate() {
+    if let Some(prefix) = &state.path_prefix
+        && !ctx.strip_path_prefix(prefix)
+    {
+        state.unmatched_requests.fetch_add(1, Ordering::Relaxed);
+        return HttpResponse::NotFound().body(format!(
+            "Request path {} is not under prefix {prefix}\n",
+            ctx.request_path
+        ));
+    }
+
+    match apply_pre_processors(&state.pre_processors, &mut ctx) {
+        Ok(Some(response)) => return response,
+        Ok(None) => {}
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Can't apply pre processors! {e}\n"));
+        }
+    }
+
+    if state.record_requests {
+        log::info!(
+            "Recorded request: {} {} headers:{:?} body:{}",
+            ctx.method,
+            ctx.request_path,
+            ctx.headers,
+            String::from_utf8_lossy(&ctx.body)
+        );
+    }
+
+    if !state.journal.is_enabled() {
+        return match_deceit(&state, ctx, socket, upgrade).await;
+    }
+
+    let started = Instant::now();
+    let mut record = RecordedRequest::from(&ctx);
+    let response = match_deceit(&state, ctx, socket, upgrade).await;
+    let (response, recorded_response) = record_response(response, started.elapsed());
+    record.response = Some(recorded_response);
+    state.journal.record(record);
+
+    response
+}
+
+/// Find deceit for the request and build response.
+async fn match_deceit(
+    state: &ApateState,
+    mut ctx: RequestContext,
+    socket: Option<ConnectionSocket>,
+    mut upgrade: Option<WsUpgrade>,
+) -> HttpResponse {
+    let specs = state.specs.read().await;
+
+    // Diagnostics for deceits that were checked, only collected on explicit request
+    let mut trace = (state.match_trace && ctx.headers.get(TRACE_HEADER).is_some_and(|v| v == "1"))
+        .then(Vec::new);
+
+    for (deceit_idx, d) in specs.deceit.iter().enumerate() {
+        if !d.enabled {
+            continue;
+        }
         let Some(path) = d.match_againtst_uris(&ctx.request_path) else {
             continue;
         };
 
-        let args_path = path
-            .iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect();
-
-        ctx.update_paths(path.as_str().to_string(), args_path);
+        ctx.update_paths(path.as_str().to_string(), d.path_args(&path));
 
         log::trace!("Request context is: {ctx:?}");
 
+        if let Some(cors) = &d.cors
+            && is_cors_preflight(&ctx)
+        {
+            log::debug!("Deceit {} answers CORS preflight", d.label(deceit_idx));
+            return cors_preflight_response(cors);
+        }
+
         let deceit_ref = ResourceRef::new(deceit_idx);
-        let Some(idx) = d.match_response(&deceit_ref, &ctx, &state.rhai) else {
-            continue;
-        };
+        let matched = d.match_matchers(&deceit_ref, &ctx, &state.rhai);
 
-        log::debug!("Deceit {deceit_ref} matched (^_^). Processing response: {idx}");
+        if matched
+            && let Some(limit) = &d.rate_limit
+            && let Err(retry_after) = state
+                .rate_limiter
+                .acquire(&rate_limit_key(&deceit_ref, limit, &ctx), limit)
+        {
+            log::debug!("Deceit {} rate limit exceeded", d.label(deceit_idx));
+            let mut response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.to_string()))
+                .body("Rate limit exceeded\n");
+            response.extensions_mut().insert(MatchedDeceit {
+                idx: deceit_idx,
+                name: d.name.clone(),
+            });
+            return response;
+        }
 
-        let Some(dresp) = d.responses.get(idx) else {
-            log::error!("Wow we definitely must have response for this index {idx}");
+        let picked = matched
+            .then(|| {
+                d.pick_response(
+                    &deceit_ref,
+                    &ctx,
+                    &state.rhai,
+                    &state.response_matches,
+                    &state.scenarios,
+                )
+            })
+            .flatten();
+        let Some((idx, dresp)) = picked else {
+            if let Some(trace) = trace.as_mut() {
+                trace.push(format!(
+                    "deceit {} {:?}: URI matched, matchers did not",
+                    d.label(deceit_idx),
+                    d.uris
+                ));
+            }
             continue;
         };
 
+        // Do not block specs updates while remote output is fetched
+        let unlocked;
+        let (d, dresp) = if is_uncached_remote_output(state, dresp) {
+            unlocked = (d.clone(), dresp.clone());
+            drop(specs);
+            (&unlocked.0, &unlocked.1)
+        } else {
+            (d, dresp)
+        };
+
+        let deceit_label = d.label(&deceit_ref);
+        log::debug!(
+            "Deceit {deceit_label} matched (^_^). Processing response: {}",
+            dresp.label(idx)
+        );
+
+        if d.log_bodies {
+            log::debug!(
+                "Deceit {deceit_label} request body: {}",
+                String::from_utf8_lossy(&ctx.body)
+            );
+        }
+
         // Here all matchers checks passed
         // Now we are processing response
         // At this point we can't skip to the next deceit anymore
-        let drctx = match create_response_context(ctx.clone(), state.counters.clone()) {
-            Ok(ctx) => ctx,
-            Err(e) => {
-                return HttpResponse::InternalServerError()
-                    .body(format!("Cant create deceit context! {e}"));
-            }
+        let fault = dresp.fault(d);
+        let mut response = if let Some(fault) = fault.filter(|f| f.breaks_connection()) {
+            log::debug!("Deceit {deceit_label} simulates {fault:?}");
+            apply_fault(fault, socket.as_ref())
+        } else if let OutputType::WebSocket = dresp.output_type {
+            log::debug!("Deceit {deceit_label} answers with WebSocket");
+            websocket_response(upgrade.take(), &dresp.output)
+        } else {
+            deceit_response(
+                state,
+                d,
+                &deceit_ref,
+                idx,
+                dresp,
+                &ctx,
+                DEFAULT_RESPONSE_CODE,
+            )
+            .await
         };
+        if let Some(Fault::RandomBodyCorruption { probability }) = fault {
+            response = corrupt_body(response, *probability);
+        }
+        if trace.is_some() {
+            let headers = response.headers_mut();
+            if let Ok(value) = HeaderValue::from_str(&deceit_label) {
+                headers.insert(MATCHED_DECEIT_HEADER, value);
+            }
+            if let Ok(value) = HeaderValue::from_str(&dresp.label(idx)) {
+                headers.insert(MATCHED_RESPONSE_HEADER, value);
+            }
+        }
+        response.extensions_mut().insert(MatchedDeceit {
+            idx: deceit_idx,
+            name: d.name.clone(),
+        });
+        return response;
+    }
 
-        let output_body = crate::output::output_response_body(
+    state.unmatched_requests.fetch_add(1, Ordering::Relaxed);
+
+    if let Some(dresp) = &specs.default_deceit {
+        log::debug!("No deceit matched, processing default deceit");
+        if let Some(fault) = dresp.fault.as_ref().filter(|f| f.breaks_connection()) {
+            return apply_fault(fault, socket.as_ref());
+        }
+        // Default deceit goes after all others so its resources do not clash with them
+        let deceit_ref = ResourceRef::new(specs.deceit.len());
+        let unlocked;
+        let dresp = if is_uncached_remote_output(state, dresp) {
+            unlocked = dresp.clone();
+            drop(specs);
+            &unlocked
+        } else {
+            dresp
+        };
+        let d = Deceit::default();
+        ctx.update_paths(ctx.request_path.to_string(), Default::default());
+        let response = deceit_response(
+            state,
+            &d,
             &deceit_ref,
+            0,
+            dresp,
+            &ctx,
+            StatusCode::NOT_FOUND,
+        )
+        .await;
+        return match &dresp.fault {
+            Some(Fault::RandomBodyCorruption { probability }) => {
+                corrupt_body(response, *probability)
+            }
+            _ => response,
+        };
+    }
+
+    let mut body = format!(
+        "Nothing can handle your requiest with path: {}\n",
+        ctx.request_path
+    );
+    if let Some(trace) = trace {
+        let _ = writeln!(body, "Evaluated deceits: {}", trace.len());
+        for line in trace {
+            let _ = writeln!(body, "  {line}");
+        }
+    }
+    HttpResponse::NotFound().body(body)
+}
+
+/// Take a copy of response for the journal. Streamed bodies are not recorded.
+fn record_response(response: HttpResponse, duration: Duration) -> (HttpResponse, RecordedResponse) {
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+        .collect();
+    let status = response.status().as_u16();
+
+    let (response, body) = response.into_parts();
+    let (body, recorded_body) = match body.try_into_bytes() {
+        Ok(bytes) => {
+            let recorded = String::from_utf8_lossy(&bytes).to_string();
+            (BoxBody::new(bytes), recorded)
+        }
+        Err(body) => (body, String::new()),
+    };
+
+    let recorded = RecordedResponse {
+        status,
+        duration_ms: duration.as_millis() as u64,
+        headers,
+        body: recorded_body,
+    };
+    (response.set_body(body), recorded)
+}
+
+/// Render response through output pipeline and apply processors.
+/// `default_code` is used when neither specs nor scripts define a response code.
+async fn deceit_response(
+    state: &ApateState,
+    d: &Deceit,
+    deceit_ref: &ResourceRef,
+    idx: usize,
+    dresp: &DeceitResponse,
+    ctx: &RequestContext,
+    default_code: StatusCode,
+) -> HttpResponse {
+    let drctx = match create_response_context(ctx.clone(), state.counters.clone(), deceit_ref) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Cant create deceit context! {e}"));
+        }
+    };
+
+    // Each response (including default one) must have its own cached template or script
+    let response_ref = deceit_ref.with_level(idx);
+
+    if let Some(last_modified) = dresp.last_modified(d)
+        && is_not_modified(last_modified, ctx)
+    {
+        return HttpResponse::NotModified()
+            .insert_header((LAST_MODIFIED, last_modified))
+            .finish();
+    }
+
+    if let Some(redirect) = &dresp.redirect {
+        return redirect_response(state, d, &response_ref, dresp, redirect, &drctx);
+    }
+
+    if let OutputType::RhaiStream = dresp.output_type
+        && dresp.json_body.is_none()
+    {
+        let status = match response_status(state, &response_ref, dresp, &drctx, default_code) {
+            Ok(status) => status,
+            Err(e) => return code_error_response(e),
+        };
+        return match output_response_stream(&response_ref, &dresp.output, &drctx, &state.rhai) {
+            Ok(stream) => {
+                if !d.processors.is_empty() || !dresp.processors.is_empty() {
+                    log::warn!("Deceit {deceit_ref} processors are ignored for streaming output");
+                }
+                let mut hrb = HttpResponseBuilder::new(status);
+                insert_response_headers(&mut hrb, state, &response_ref, d, dresp, ctx);
+                hrb.streaming(stream.map(Ok::<_, actix_web::Error>))
+            }
+            Err(e) => HttpResponse::InternalServerError().body(format!("It happened! {e}\n")),
+        };
+    }
+
+    let output_body = match dresp.output_type {
+        OutputType::RemoteCached if dresp.json_body.is_none() => {
+            remote_cached_output(state, &dresp.output).await
+        }
+        _ => crate::output::output_response_body(
+            &response_ref,
             &dresp.output_type,
             &dresp.output,
+            dresp.json_body.as_ref(),
             &drctx,
             &state.minijinja,
             &state.rhai,
-        );
+        ),
+    };
 
-        return match output_body {
-            Ok(body) => {
-                let mut prcs = Vec::with_capacity(d.processors.len() + dresp.processors.len());
-                prcs.extend(d.processors.iter());
-                prcs.extend(dresp.processors.iter());
+    match output_body {
+        Ok(body) => {
+            let mut prcs = Vec::with_capacity(d.processors.len() + dresp.processors.len());
+            prcs.extend(d.processors.iter());
+            prcs.extend(dresp.processors.iter());
 
-                match apply_processors(
-                    &deceit_ref,
-                    &state.processors,
-                    &prcs,
-                    &drctx,
-                    &body,
-                    &state.rhai,
-                ) {
-                    Ok(new_body) => {
-                        let mut hrb = HttpResponseBuilder::new(DEFAULT_RESPONSE_CODE);
-                        insert_response_headers(&mut hrb, &d.headers, &dresp.headers);
-                        if let Ok(code) =
-                            StatusCode::from_u16(drctx.response_code.load(Ordering::Relaxed))
-                        {
-                            // This is where we are applying new status code
-                            hrb.status(code);
-                        }
-
-                        if let Some(bts) = new_body {
-                            hrb.body(bts)
-                        } else {
-                            hrb.body(body)
-                        }
+            // Response ref keeps cached processor scripts & templates apart between responses
+            match apply_processors(&response_ref, state, &prcs, &drctx, &body) {
+                Ok(new_body) => {
+                    let mut body = new_body.unwrap_or(body);
+                    if d.respect_pretty_param
+                        && let Some(formatted) = format_json_by_pretty_param(d, dresp, ctx, &body)
+                    {
+                        body = formatted;
+                    }
+                    if d.log_bodies {
+                        log::debug!(
+                            "Deceit {deceit_ref} response body: {}",
+                            String::from_utf8_lossy(&body)
+                        );
+                    }
+
+                    let status =
+                        match response_status(state, &response_ref, dresp, &drctx, default_code) {
+                            Ok(status) => status,
+                            Err(e) => return code_error_response(e),
+                        };
+                    let mut hrb = HttpResponseBuilder::new(status);
+                    if dresp.echo_headers {
+                        insert_echo_headers(&mut hrb, ctx);
                     }
-                    Err(e) => HttpResponse::InternalServerError()
-                        .body(format!("Can't apply post processors! {e}\n")),
+                    insert_response_headers(&mut hrb, state, &response_ref, d, dresp, ctx);
+                    let processor_headers = drctx.response_headers.lock().expect("Lock failed");
+                    for (k, v) in processor_headers.iter() {
+                        hrb.insert_header((k.as_str(), v.as_str()));
+                    }
+                    hrb.body(body)
                 }
+                Err(e) => HttpResponse::InternalServerError()
+                    .body(format!("Can't apply post processors! {e}\n")),
             }
-            Err(e) => HttpResponse::InternalServerError().body(format!("It happened! {e}\n")),
-        };
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("It happened! {e}\n")),
+    }
+}
+
+fn is_uncached_remote_output(state: &ApateState, dresp: &DeceitResponse) -> bool {
+    matches!(dresp.output_type, OutputType::RemoteCached)
+        && dresp.json_body.is_none()
+        && !state.remote_cache.contains(&dresp.output)
+}
+
+/// Remote body is fetched on first use and served from memory afterwards.
+/// Concurrent first requests could fetch it several times.
+async fn remote_cached_output(state: &ApateState, url: &str) -> color_eyre::Result<Vec<u8>> {
+    if let Some(body) = state.remote_cache.get(url) {
+        return Ok(body);
     }
 
-    HttpResponse::NotFound().body(format!(
-        "Nothing can handle your requiest with path: {}\n",
-        ctx.request_path
-    ))
+    #[cfg(feature = "remote-specs")]
+    {
+        log::debug!("Fetching remote output from {url}");
+        let body = crate::remote::fetch_output(url).await?;
+        state.remote_cache.insert(url, body.clone());
+        Ok(body)
+    }
+    #[cfg(not(feature = "remote-specs"))]
+    color_eyre::eyre::bail!("Remote cached output requires \"remote-specs\" feature")
 }
 
 /* impl DeceitResponse {
@@ -141,15 +507,213 @@ async fn deceit_handler(req: HttpRequest, body: Bytes, state: Data<ApateState>)
     }
 } */
 
+/// Pretty print or minify JSON body according to `pretty` query argument.
+/// Returns `None` if there is nothing to change.
+fn format_json_by_pretty_param(
+    d: &Deceit,
+    dresp: &DeceitResponse,
+    ctx: &RequestContext,
+    body: &[u8],
+) -> Option<Vec<u8>> {
+    let pretty = match ctx.query_args.get("pretty")?.as_str() {
+        "true" | "1" => true,
+        "false" | "0" => false,
+        _ => return None,
+    };
+
+    let content_type = response_content_type(d, dresp)?;
+    if !media_type_matches("application/json", content_type) {
+        return None;
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let formatted = if pretty {
+        serde_json::to_vec_pretty(&json)
+    } else {
+        serde_json::to_vec(&json)
+    };
+    formatted.ok()
+}
+
+/// Content type in the same order of priority as in [`insert_response_headers`].
+fn response_content_type<'a>(d: &'a Deceit, dresp: &'a DeceitResponse) -> Option<&'a str> {
+    dresp
+        .headers
+        .iter()
+        .rev()
+        .chain(d.headers.iter().rev())
+        .find(|(k, _)| k.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+        .map(|(_, v)| v.as_str())
+        .or_else(|| dresp.default_content_type())
+}
+
+/// Response code forced from scripts or templates has priority over the one from specs.
+fn response_status(
+    state: &ApateState,
+    response_ref: &ResourceRef,
+    dresp: &DeceitResponse,
+    drctx: &DeceitResponseContext,
+    default_code: StatusCode,
+) -> color_eyre::Result<StatusCode> {
+    let code = match &dresp.code {
+        Some(ResponseCode::Code(code)) => *code,
+        Some(ResponseCode::Template(tpl)) => {
+            output_response_code(response_ref, tpl, drctx, &state.minijinja)?
+        }
+        None => 0,
+    };
+
+    let forced = drctx.response_code.load(Ordering::Relaxed);
+    Ok([forced, code]
+        .into_iter()
+        .find_map(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(default_code))
+}
+
+fn code_error_response(e: color_eyre::Report) -> HttpResponse {
+    HttpResponse::InternalServerError().body(format!("Can't render response code! {e}\n"))
+}
+
+/// Conditional GET check, `If-Modified-Since` is ignored when `If-None-Match` is present.
+fn is_not_modified(last_modified: &str, ctx: &RequestContext) -> bool {
+    if !matches!(ctx.method.as_str(), "GET" | "HEAD") || ctx.headers.contains_key("if-none-match") {
+        return false;
+    }
+    let Some(if_modified_since) = ctx.headers.get("if-modified-since") else {
+        return false;
+    };
+
+    let last_modified = match httpdate::parse_http_date(last_modified) {
+        Ok(date) => date,
+        Err(e) => {
+            log::warn!("Invalid last_modified HTTP date \"{last_modified}\": {e}");
+            return false;
+        }
+    };
+
+    httpdate::parse_http_date(if_modified_since).is_ok_and(|since| since >= last_modified)
+}
+
+fn redirect_response(
+    state: &ApateState,
+    d: &Deceit,
+    response_ref: &ResourceRef,
+    dresp: &DeceitResponse,
+    redirect: &RedirectSpec,
+    drctx: &DeceitResponseContext,
+) -> HttpResponse {
+    let location =
+        match output_redirect_location(response_ref, &redirect.location, drctx, &state.minijinja) {
+            Ok(location) => location,
+            Err(e) => {
+                return HttpResponse::InternalServerError()
+                    .body(format!("Can't render redirect location! {e}\n"));
+            }
+        };
+
+    let status = StatusCode::from_u16(redirect.code)
+        .ok()
+        .filter(StatusCode::is_redirection)
+        .unwrap_or_else(|| {
+            log::warn!("Invalid redirect code {}, using 302", redirect.code);
+            StatusCode::FOUND
+        });
+
+    let mut hrb = HttpResponseBuilder::new(status);
+    insert_response_headers(&mut hrb, state, response_ref, d, dresp, &drctx.req);
+    hrb.insert_header((LOCATION, location));
+    hrb.finish()
+}
+
 fn insert_response_headers(
     rbuilder: &mut HttpResponseBuilder,
-    parent_headers: &[(String, String)],
-    headers: &[(String, String)],
+    state: &ApateState,
+    response_ref: &ResourceRef,
+    d: &Deceit,
+    dresp: &DeceitResponse,
+    ctx: &RequestContext,
 ) {
-    for (k, v) in parent_headers {
-        rbuilder.insert_header((k.as_str(), v.as_str()));
+    if let Some(content_type) = dresp.default_content_type() {
+        rbuilder.insert_header((CONTENT_TYPE, content_type));
+    }
+    if let Some(last_modified) = dresp.last_modified(d) {
+        rbuilder.insert_header((LAST_MODIFIED, last_modified));
+    }
+    if let Some(cors) = &d.cors {
+        rbuilder.insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, cors.allow_origin.as_str()));
+    }
+    insert_header_list(rbuilder, &d.headers);
+    insert_header_list(rbuilder, &dresp.headers);
+    // Levels after response matchers ones, so cached Rhai matchers do not clash
+    for (hid, header) in dresp.conditional_headers.iter().enumerate() {
+        let header_ref = response_ref.with_level(dresp.matchers.len() + hid);
+        if is_matcher_approves(&header_ref, &state.rhai, ctx, &header.matcher) {
+            rbuilder.insert_header((header.key.as_str(), header.value.as_str()));
+        }
+    }
+}
+
+/// Header repeated in the list is sent several times (like `Set-Cookie`),
+/// its first occurrence replaces the one inserted before.
+fn insert_header_list(rbuilder: &mut HttpResponseBuilder, headers: &[(String, String)]) {
+    for (idx, (k, v)) in headers.iter().enumerate() {
+        if headers[..idx]
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(k))
+        {
+            rbuilder.append_header((k.as_str(), v.as_str()));
+        } else {
+            rbuilder.insert_header((k.as_str(), v.as_str()));
+        }
+    }
+}
+
+/// Request headers that describe request message itself and must not be copied into response.
+const NOT_ECHOED_HEADERS: &[&str] = &[
+    "content-length",
+    "content-encoding",
+    "transfer-encoding",
+    "connection",
+    "keep-alive",
+];
+
+/// Echoed request headers go first so headers from specs override them.
+fn insert_echo_headers(rbuilder: &mut HttpResponseBuilder, ctx: &RequestContext) {
+    for (k, v) in ctx.headers.iter() {
+        if !NOT_ECHOED_HEADERS.contains(&k.as_str()) {
+            rbuilder.insert_header((k.as_str(), v.as_str()));
+        }
+    }
+}
+
+fn rate_limit_key(deceit_ref: &ResourceRef, limit: &RateLimit, ctx: &RequestContext) -> String {
+    let forwarded_ip = if limit.forwarded {
+        ctx.forwarded_ip
+    } else {
+        None
+    };
+    let client_ip = forwarded_ip.or(ctx.peer_ip).filter(|_| limit.per_client);
+    match client_ip {
+        Some(ip) => format!("{deceit_ref}:{ip}"),
+        None => deceit_ref.to_string(),
+    }
+}
+
+fn is_cors_preflight(ctx: &RequestContext) -> bool {
+    ctx.method == "OPTIONS" && ctx.headers.contains_key("access-control-request-method")
+}
+
+fn cors_preflight_response(cors: &CorsSpec) -> HttpResponse {
+    let mut hrb = HttpResponse::NoContent();
+    hrb.insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, cors.allow_origin.as_str()));
+    if !cors.allow_methods.is_empty() {
+        hrb.insert_header((ACCESS_CONTROL_ALLOW_METHODS, cors.allow_methods.join(", ")));
+    }
+    if !cors.allow_headers.is_empty() {
+        hrb.insert_header((ACCESS_CONTROL_ALLOW_HEADERS, cors.allow_headers.join(", ")));
     }
-    for (k, v) in headers {
-        rbuilder.insert_header((k.as_str(), v.as_str()));
+    if let Some(max_age) = cors.max_age {
+        hrb.insert_header((ACCESS_CONTROL_MAX_AGE, max_age.to_string()));
     }
+    hrb.finish()
 }
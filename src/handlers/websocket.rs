@@ -0,0 +1,111 @@
+//! WebSocket mock responses: echo incoming messages or replay scripted ones.
+
+use actix_web::{HttpRequest, HttpResponse, http::header::UPGRADE, web};
+
+/// WebSocket handshake request with payload that becomes connection stream after upgrade.
+#[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+pub(crate) struct WsUpgrade {
+    pub req: HttpRequest,
+    pub payload: web::Payload,
+}
+
+/// Body of upgrade request must not be read, it is a connection stream.
+pub(crate) fn is_websocket_upgrade(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// Empty output echoes messages back, otherwise it is a JSON array of messages
+/// sent right after upgrade, connection is closed after the last one.
+pub(crate) fn websocket_response(upgrade: Option<WsUpgrade>, output: &str) -> HttpResponse {
+    let Some(upgrade) = upgrade else {
+        return HttpResponse::build(actix_web::http::StatusCode::UPGRADE_REQUIRED)
+            .insert_header((UPGRADE, "websocket"))
+            .body("WebSocket upgrade is expected\n");
+    };
+
+    let script = match parse_script(output) {
+        Ok(script) => script,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Invalid WebSocket output: {e}\n"));
+        }
+    };
+
+    #[cfg(feature = "websocket")]
+    return ws::upgrade(upgrade, script);
+
+    #[cfg(not(feature = "websocket"))]
+    {
+        let _ = (upgrade, script);
+        HttpResponse::InternalServerError()
+            .body("WebSocket output requires \"websocket\" feature\n")
+    }
+}
+
+/// Messages are sent as text frames, non string JSON values are serialized.
+fn parse_script(output: &str) -> color_eyre::Result<Vec<String>> {
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let messages: Vec<serde_json::Value> = serde_json::from_str(output)?;
+    Ok(messages
+        .into_iter()
+        .map(|m| match m {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        })
+        .collect())
+}
+
+#[cfg(feature = "websocket")]
+mod ws {
+    use actix_web::HttpResponse;
+    use actix_ws::{Message, MessageStream, Session};
+
+    use super::WsUpgrade;
+
+    pub(super) fn upgrade(upgrade: WsUpgrade, script: Vec<String>) -> HttpResponse {
+        let (response, session, stream) = match actix_ws::handle(&upgrade.req, upgrade.payload) {
+            Ok(handshake) => handshake,
+            Err(e) => return e.error_response(),
+        };
+
+        if script.is_empty() {
+            actix_web::rt::spawn(echo(session, stream));
+        } else {
+            actix_web::rt::spawn(replay(session, script));
+        }
+        response
+    }
+
+    async fn echo(mut session: Session, mut stream: MessageStream) {
+        while let Some(Ok(msg)) = stream.recv().await {
+            let sent = match msg {
+                Message::Text(text) => session.text(text).await,
+                Message::Binary(bin) => session.binary(bin).await,
+                Message::Ping(bytes) => session.pong(&bytes).await,
+                Message::Close(reason) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                _ => Ok(()),
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn replay(mut session: Session, script: Vec<String>) {
+        for message in script {
+            if session.text(message).await.is_err() {
+                return;
+            }
+        }
+        let _ = session.close(None).await;
+    }
+}
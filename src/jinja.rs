@@ -1,13 +1,15 @@
 use std::{
+    collections::HashMap,
     fmt::Debug,
     sync::{Arc, atomic::Ordering},
 };
 
 use minijinja::{
     Environment, State, Value, context,
-    value::{Object, ObjectRepr},
+    value::{Kwargs, Object, ObjectRepr},
 };
 use rand::{Rng as _, RngCore as _};
+use serde::Serialize as _;
 use uuid::Uuid;
 
 use crate::deceit::DeceitResponseContext;
@@ -17,13 +19,24 @@ use crate::deceit::DeceitResponseContext;
 /// Expose next API:
 ///  - ctx.method -> returns request method
 ///  - ctx.path -> returns request path
+///  - ctx.remote_ip -> returns connection peer IP address (could be a proxy)
+///  - ctx.forwarded_ip -> returns first `X-Forwarded-For` address (undefined without the header)
 ///  - ctx.response_code -> get set custom response code if any (default 0 if not set)
 ///  - ctx.load_headers() -> build request headers map (lowercase keys)
 ///  - ctx.load_query_args() -> build map with URL query arguments
+///  - ctx.load_query_args_multi() -> build map with lists of all values for every query argument
 ///  - ctx.load_path_args() -> build arguments map from specs URIs like /mypath/{user_id}/{item_id}
 ///  - ctx.load_body_string() -> load request body as string
 ///  - ctx.load_body_json() -> load request body as json
+///  - ctx.load_form() -> build map with fields of urlencoded form body
+///  - ctx.load_multipart() -> build map with text fields of multipart form body
 ///  - ctx.inc_counter("key") -> increment counter by key and returns previous value
+///  - ctx.dec_counter("key") -> decrement counter by key (not below zero) and returns previous value
+///  - ctx.set_counter("key", value) -> set counter value and returns previous value
+///  - ctx.reset_counter("key") -> set counter to zero and returns previous value
+///  - ctx.get_counter("key") -> returns current counter value
+///
+/// Counter keys prefixed with `@local:` are visible only for current deceit.
 pub struct MiniJinjaResponseContext {
     ctx: DeceitResponseContext,
 }
@@ -50,6 +63,12 @@ impl Object for MiniJinjaResponseContext {
         match field.as_str()? {
             "method" => Some(Value::from(self.ctx.req.method.as_str())),
             "path" => Some(Value::from(self.ctx.req.path.as_str())),
+            "remote_ip" => self.ctx.req.peer_ip.map(|ip| Value::from(ip.to_string())),
+            "forwarded_ip" => self
+                .ctx
+                .req
+                .forwarded_ip
+                .map(|ip| Value::from(ip.to_string())),
             _ => None,
         }
     }
@@ -63,7 +82,12 @@ impl Object for MiniJinjaResponseContext {
         match method {
             "load_headers" => Ok(Value::from(self.ctx.req.headers.as_ref().clone())),
             "load_query_args" => Ok(Value::from(self.ctx.req.query_args.as_ref().clone())),
+            "load_query_args_multi" => {
+                Ok(Value::from(self.ctx.req.query_args_multi.as_ref().clone()))
+            }
             "load_path_args" => Ok(Value::from(self.ctx.req.path_args.as_ref().clone())),
+            "load_form" => Ok(Value::from(self.ctx.req.load_form())),
+            "load_multipart" => Ok(Value::from(self.ctx.req.load_multipart())),
             "load_body_string" => {
                 if self.ctx.req.body.trim_ascii().is_empty() {
                     Ok(Value::default())
@@ -79,7 +103,7 @@ impl Object for MiniJinjaResponseContext {
                     Err(minijinja::Error::from(minijinja::ErrorKind::CannotUnpack))
                 }
             },
-            "inc_counter" => {
+            "inc_counter" | "dec_counter" | "reset_counter" | "get_counter" => {
                 if args.len() != 1 {
                     return Err(minijinja::Error::from(
                         minijinja::ErrorKind::MissingArgument,
@@ -88,16 +112,32 @@ impl Object for MiniJinjaResponseContext {
                 let Some(key) = args[0].as_str() else {
                     return Err(minijinja::Error::from(minijinja::ErrorKind::NonKey));
                 };
-                self.ctx
-                    .counters
-                    .get_and_increment(key)
-                    .map(Value::from)
-                    .map_err(|e| {
-                        minijinja::Error::new(
-                            minijinja::ErrorKind::UndefinedError,
-                            format!("Can't get counter value for key \"{key}\". {e:?}"),
-                        )
-                    })
+                let counters = &self.ctx.counters;
+                let counter_key = self.ctx.counter_key(key);
+                let result = match method {
+                    "inc_counter" => counters.get_and_increment(&counter_key),
+                    "dec_counter" => counters.get_and_decrement(&counter_key),
+                    "reset_counter" => counters.reset(&counter_key),
+                    _ => counters.get_or_default(&counter_key),
+                };
+                counter_value(key, result)
+            }
+            "set_counter" => {
+                if args.len() != 2 {
+                    return Err(minijinja::Error::from(
+                        minijinja::ErrorKind::MissingArgument,
+                    ));
+                }
+                let Some(key) = args[0].as_str() else {
+                    return Err(minijinja::Error::from(minijinja::ErrorKind::NonKey));
+                };
+                let Some(value) = args[1].as_i64().and_then(|v| u64::try_from(v).ok()) else {
+                    return Err(minijinja::Error::from(minijinja::ErrorKind::NonPrimitive));
+                };
+                counter_value(
+                    key,
+                    self.ctx.counters.set(&self.ctx.counter_key(key), value),
+                )
             }
             "set_response_code" => {
                 if args.len() != 1 {
@@ -116,6 +156,15 @@ impl Object for MiniJinjaResponseContext {
     }
 }
 
+fn counter_value(key: &str, result: color_eyre::Result<u64>) -> Result<Value, minijinja::Error> {
+    result.map(Value::from).map_err(|e| {
+        minijinja::Error::new(
+            minijinja::ErrorKind::UndefinedError,
+            format!("Can't get counter value for key \"{key}\". {e:?}"),
+        )
+    })
+}
+
 pub fn build_tpl_context(ctx: DeceitResponseContext) -> minijinja::Value {
     let mjctx = MiniJinjaResponseContext::new(ctx);
     context! {
@@ -133,6 +182,7 @@ pub fn build_tpl_context(ctx: DeceitResponseContext) -> minijinja::Value {
 #[derive(Default, Clone)]
 pub struct MiniJinjaState {
     env: Arc<std::sync::RwLock<Option<Environment<'static>>>>,
+    fragments: Arc<std::sync::RwLock<HashMap<String, String>>>,
 }
 
 impl MiniJinjaState {
@@ -162,7 +212,10 @@ impl MiniJinjaState {
             drop(read_guard);
             let mut write_guard = self.env.write().expect("RwLock failed");
             if write_guard.is_none() {
-                *write_guard = Some(init_minijinja());
+                let mut env = init_minijinja();
+                let fragments = self.fragments.read().expect("RwLock failed");
+                add_fragments(&mut env, &fragments);
+                *write_guard = Some(env);
             }
         }
     }
@@ -171,6 +224,33 @@ impl MiniJinjaState {
         let mut write_guard = self.env.write().expect("Write RwLock failed");
         *write_guard = None;
     }
+
+    /// Drop cached environment and replace fragments available via `fragment(name)`.
+    pub fn clear_and_update(&self, fragments: HashMap<String, String>) {
+        *self.fragments.write().expect("Write RwLock failed") = fragments;
+        self.clear();
+    }
+}
+
+/// Fragments are registered as templates, so they could be also used with `{% include %}`.
+fn add_fragments(env: &mut Environment<'static>, fragments: &HashMap<String, String>) {
+    for (name, source) in fragments {
+        if let Err(e) = env.add_template_owned(fragment_id(name), source.clone()) {
+            log::error!("Can't add minijinja fragment \"{name}\": {e}");
+        }
+    }
+    env.add_function("fragment", ctx_fragment);
+}
+
+fn fragment_id(name: &str) -> String {
+    format!("fragment:{name}")
+}
+
+/// Render named fragment with the same `ctx` as the calling template.
+fn ctx_fragment(state: &State, name: &str) -> Result<Value, minijinja::Error> {
+    let tpl = state.get_template(&fragment_id(name))?;
+    let rendered = tpl.render(context! { ctx => state.lookup("ctx") })?;
+    Ok(Value::from_safe_string(rendered))
 }
 
 pub(crate) fn init_minijinja() -> minijinja::Environment<'static> {
@@ -179,14 +259,52 @@ pub(crate) fn init_minijinja() -> minijinja::Environment<'static> {
     // env.set_lstrip_blocks(true);
     // env.set_keep_trailing_newline(false);
     add_clean_functions(&mut env);
+    env.add_filter("tojson", filter_tojson);
     env
 }
 
+/// Replaces builtin `tojson` that escapes HTML sensitive chars like `'` or `<` as `\u0027`.
+/// Serialize value with `serde_json` as is, `tojson(indent=2)` or `tojson(2)` pretty prints it.
+fn filter_tojson(
+    value: &Value,
+    indent: Option<usize>,
+    kwargs: Kwargs,
+) -> Result<Value, minijinja::Error> {
+    let indent = match kwargs.get::<Option<usize>>("indent")? {
+        Some(i) => Some(i),
+        None => indent,
+    };
+    kwargs.assert_all_used()?;
+
+    let to_error = |e: serde_json::Error| {
+        minijinja::Error::new(
+            minijinja::ErrorKind::InvalidOperation,
+            "Can't serialize value to JSON",
+        )
+        .with_source(e)
+    };
+
+    let json = match indent {
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let mut buf = Vec::new();
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+            value.serialize(&mut ser).map_err(to_error)?;
+            String::from_utf8(buf).expect("serde_json writes valid UTF-8")
+        }
+        None => serde_json::to_string(value).map_err(to_error)?,
+    };
+
+    Ok(Value::from_safe_string(json))
+}
+
 /// Add clean functions (without side effects) to minijinja environment.
 pub fn add_clean_functions(env: &mut minijinja::Environment) {
     env.add_function("random_num", ctx_random_num);
     env.add_function("random_hex", ctx_random_hex);
     env.add_function("uuid_v4", ctx_uuid_v4);
+    env.add_function("uuid_v5", ctx_uuid_v5);
 }
 
 fn ctx_random_num(a: Option<u128>, b: Option<u128>) -> String {
@@ -213,3 +331,9 @@ fn ctx_random_hex(length: Option<u64>) -> String {
 fn ctx_uuid_v4() -> String {
     Uuid::new_v4().to_string()
 }
+
+fn ctx_uuid_v5(namespace: &str, name: &str) -> Result<String, minijinja::Error> {
+    crate::uuid_v5(namespace, name)
+        .map(|uuid| uuid.to_string())
+        .map_err(|e| minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e))
+}
@@ -0,0 +1,308 @@
+//! In memory journal of requests received by apate server.
+//! Helps to check which requests were actually sent to the mock.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_router::{Path, ResourceDef};
+use actix_web::web::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    RequestContext, ResourceRef,
+    matchers::{Matcher, matchers_and},
+    rhai::RhaiState,
+};
+
+/// Journal capacity used when recording is enabled without explicit capacity.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1000;
+
+/// Single request stored in the journal.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedRequest {
+    /// Milliseconds since UNIX epoch when request was received
+    pub time_ms: u64,
+    pub method: String,
+    pub path: String,
+    pub query_args: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    /// Request body, invalid UTF-8 sequences are replaced
+    pub body: String,
+    /// Response produced by apate for this request
+    #[serde(default)]
+    pub response: Option<RecordedResponse>,
+}
+
+/// Response stored in the journal along with request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// Milliseconds spent to prepare response
+    pub duration_ms: u64,
+    pub headers: HashMap<String, String>,
+    /// Response body, invalid UTF-8 sequences are replaced. Empty for streamed responses.
+    pub body: String,
+}
+
+impl From<&RequestContext> for RecordedRequest {
+    fn from(ctx: &RequestContext) -> Self {
+        let time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            time_ms,
+            method: ctx.method.clone(),
+            path: ctx.request_path.as_ref().clone(),
+            query_args: ctx.query_args.as_ref().clone(),
+            headers: ctx.headers.as_ref().clone(),
+            body: String::from_utf8_lossy(&ctx.body).to_string(),
+            response: None,
+        }
+    }
+}
+
+impl From<&RecordedRequest> for RequestContext {
+    fn from(rec: &RecordedRequest) -> Self {
+        let path = Arc::new(rec.path.clone());
+        Self {
+            method: rec.method.clone(),
+            headers: Arc::new(rec.headers.clone()),
+            path: path.clone(),
+            request_path: path.clone(),
+            raw_path: path,
+            query_args: Arc::new(rec.query_args.clone()),
+            query_args_multi: Arc::new(
+                rec.query_args
+                    .iter()
+                    .map(|(k, v)| (k.clone(), vec![v.clone()]))
+                    .collect(),
+            ),
+            peer_ip: None,
+            forwarded_ip: None,
+            path_args: Default::default(),
+            body: Arc::new(Bytes::from(rec.body.clone())),
+            body_json: Default::default(),
+        }
+    }
+}
+
+/// Criteria to select recorded requests.
+/// All defined criteria must pass for request to be selected.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RequestsCriteria {
+    /// HTTP method (case insensitive)
+    #[serde(default)]
+    pub method: Option<String>,
+
+    /// Path or a pattern with arguments like `/user/{user_id}` same as deceit URIs.
+    /// Path arguments are available for `path_arg` matchers.
+    #[serde(default)]
+    pub path: Option<String>,
+
+    #[serde(default)]
+    pub matchers: Vec<Matcher>,
+}
+
+impl RequestsCriteria {
+    pub fn is_match(&self, rref: &ResourceRef, rhai: &RhaiState, rec: &RecordedRequest) -> bool {
+        if let Some(method) = &self.method
+            && !method.eq_ignore_ascii_case(&rec.method)
+        {
+            return false;
+        }
+
+        let mut ctx: RequestContext = rec.into();
+
+        if let Some(pattern) = &self.path {
+            let mut path = Path::new(rec.path.clone());
+            if !ResourceDef::new(pattern.as_str()).capture_match_info(&mut path) {
+                return false;
+            }
+
+            let args_path = path
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            ctx.update_paths(path.as_str().to_string(), args_path);
+        }
+
+        matchers_and(rref, rhai, &ctx, &self.matchers)
+    }
+}
+
+/// Ring buffer with recorded requests.
+/// The oldest records are dropped when capacity is reached.
+/// Zero capacity means that recording is disabled.
+#[derive(Clone, Default)]
+pub struct RequestJournal {
+    capacity: usize,
+    records: Arc<RwLock<VecDeque<RecordedRequest>>>,
+}
+
+impl RequestJournal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn record(&self, rec: RecordedRequest) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut wguard = self.records.write().expect("Journal RwLock write failed");
+        if wguard.len() >= self.capacity {
+            wguard.pop_front();
+        }
+        wguard.push_back(rec);
+    }
+
+    /// Returns all recorded requests from the oldest to the newest one.
+    pub fn list(&self) -> Vec<RecordedRequest> {
+        let rguard = self.records.read().expect("Journal RwLock read failed");
+        rguard.iter().cloned().collect()
+    }
+
+    /// Count recorded requests that satisfy criteria.
+    pub fn count(&self, criteria: &RequestsCriteria, rhai: &RhaiState) -> usize {
+        // Ad-hoc inline scripts must not interfere with the ones cached for specs
+        let rhai = rhai.with_isolated_cache();
+        let rref = ResourceRef::new(0);
+
+        let rguard = self.records.read().expect("Journal RwLock read failed");
+        rguard
+            .iter()
+            .filter(|rec| criteria.is_match(&rref, &rhai, rec))
+            .count()
+    }
+
+    /// Export recorded requests with their responses as HAR 1.2 document.
+    pub fn to_har(&self) -> serde_json::Value {
+        let entries: Vec<serde_json::Value> = self.list().iter().map(har_entry).collect();
+
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "apate", "version": env!("CARGO_PKG_VERSION")},
+                "entries": entries,
+            }
+        })
+    }
+
+    pub fn clear(&self) {
+        let mut wguard = self.records.write().expect("Journal RwLock write failed");
+        wguard.clear();
+    }
+}
+
+fn har_entry(rec: &RecordedRequest) -> serde_json::Value {
+    let host = rec.headers.get("host").map_or("localhost", String::as_str);
+    let query = serde_urlencoded::to_string(&rec.query_args).unwrap_or_default();
+    let url = if query.is_empty() {
+        format!("http://{host}{}", rec.path)
+    } else {
+        format!("http://{host}{}?{query}", rec.path)
+    };
+
+    let mut request = json!({
+        "method": rec.method,
+        "url": url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": har_pairs(&rec.headers),
+        "queryString": har_pairs(&rec.query_args),
+        "headersSize": -1,
+        "bodySize": rec.body.len(),
+    });
+    if !rec.body.is_empty() {
+        request["postData"] = json!({
+            "mimeType": rec.headers.get("content-type").map_or("", String::as_str),
+            "text": rec.body,
+        });
+    }
+
+    let time = rec.response.as_ref().map_or(0, |r| r.duration_ms);
+
+    json!({
+        "startedDateTime": iso8601_utc(rec.time_ms),
+        "time": time,
+        "request": request,
+        "response": har_response(rec.response.as_ref()),
+        "cache": {},
+        "timings": {"send": 0, "wait": time, "receive": 0},
+    })
+}
+
+fn har_response(response: Option<&RecordedResponse>) -> serde_json::Value {
+    let empty_headers = HashMap::new();
+    let (status, headers, body) = response.map_or((0, &empty_headers, ""), |r| {
+        (r.status, &r.headers, r.body.as_str())
+    });
+
+    let status_text = actix_web::http::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+
+    json!({
+        "status": status,
+        "statusText": status_text,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": har_pairs(headers),
+        "content": {
+            "size": body.len(),
+            "mimeType": headers.get("content-type").map_or("", String::as_str),
+            "text": body,
+        },
+        "redirectURL": headers.get("location").map_or("", String::as_str),
+        "headersSize": -1,
+        "bodySize": body.len(),
+    })
+}
+
+fn har_pairs(map: &HashMap<String, String>) -> Vec<serde_json::Value> {
+    let mut pairs: Vec<_> = map.iter().collect();
+    pairs.sort();
+    pairs
+        .into_iter()
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect()
+}
+
+/// Format milliseconds since UNIX epoch like `2024-01-31T12:00:00.000Z`.
+fn iso8601_utc(time_ms: u64) -> String {
+    let secs = time_ms / 1000;
+    let (days, day_secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        day_secs / 3_600,
+        day_secs % 3_600 / 60,
+        day_secs % 60,
+        time_ms % 1000
+    )
+}
@@ -1,51 +1,141 @@
 pub mod deceit;
 mod handlers;
 pub mod jinja;
+pub mod journal;
 pub mod matchers;
+pub mod openapi;
 pub mod output;
+pub mod postman;
 pub mod processors;
+#[cfg(feature = "remote-specs")]
+mod remote;
 pub mod rhai;
 pub mod test;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "watch")]
+mod watch;
 
-use deceit::Deceit;
+use deceit::{Deceit, DeceitResponse, Fault};
 
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::io::Read as _;
-use std::net::Ipv4Addr;
-use std::sync::atomic::AtomicU64;
-use std::sync::{Arc, Mutex};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use actix_web::App;
 use actix_web::dev::Server;
-use actix_web::middleware::Logger;
+use actix_web::middleware::{Condition, Logger, from_fn};
 use actix_web::{
     HttpRequest, HttpServer,
-    web::{self, Bytes, Data},
+    http::KeepAlive,
+    web::{self, Bytes, Data, PayloadConfig},
 };
 use async_lock::RwLock;
 use serde::{Deserialize, Serialize};
 
 use crate::jinja::MiniJinjaState;
-use crate::processors::ApateProcessor;
+use crate::journal::RequestJournal;
+use crate::processors::{ApatePreProcessor, ApateProcessor, SchemaValidators};
 use crate::rhai::{RhaiScript, RhaiState};
 
 pub const DEFAULT_PORT: u16 = 8228;
 pub const DEFAULT_RUST_LOG: &str = "info,apate=debug";
+/// How long to wait for in-flight requests on shutdown (same as actix default).
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
 
 #[derive(Debug)]
 pub struct ApateConfig {
     pub port: u16,
+    /// Address to bind server to (all interfaces by default).
+    pub bind: IpAddr,
+    /// Additional addresses to bind server to, e.g. IPv6 one in dual-stack environments.
+    pub extra_binds: Vec<SocketAddr>,
     pub processors: HashMap<String, ApateProcessor>,
+    /// Run on every mock request before deceits matching, see [`processors::PreProcessor`].
+    pub pre_processors: Vec<ApatePreProcessor>,
     pub specs: ApateSpecs,
+    /// Log every incoming request (method, path, headers and body) at info level.
+    pub record_requests: bool,
+    /// How many requests to keep in the requests journal, 0 disables journal.
+    pub journal_capacity: usize,
+    /// Paths of files specs were read from (including ones from ENV).
+    pub specs_files: Vec<String>,
+    /// API descriptions converted into deceits after `specs_files` ones, see [`ApateConfig::add_specs_import`].
+    pub specs_imports: Vec<SpecsImport>,
+    /// Reload specs from `specs_files` and `specs_imports` when they change on disk.
+    pub watch: bool,
+    /// Seconds to wait for in-flight requests to finish after shutdown signal.
+    pub shutdown_timeout_secs: u64,
+    /// Prefix (like `/mock` from reverse proxy) stripped from request path before matching.
+    /// Requests that are not under the prefix get 404.
+    pub path_prefix: Option<String>,
+    /// PEM certificate chain to serve HTTPS (and HTTP/2) with, requires "tls" feature.
+    pub cert_path: Option<String>,
+    /// PEM private key for `cert_path`.
+    pub key_path: Option<String>,
+    /// Number of worker threads, `None` keeps actix default (number of physical CPUs).
+    pub workers: Option<usize>,
+    /// Max concurrent connections per worker, `None` keeps actix default (25k).
+    pub max_connections: Option<usize>,
+    /// Access log format, JSON records are written under `apate::access` log target.
+    pub log_format: LogFormat,
+    /// Allow `X-Apate-Trace: 1` request header to get match diagnostics in responses.
+    /// Disabled by default, so mock responses are not polluted.
+    pub match_trace: bool,
+    /// Requests with bigger bodies get 413, `None` keeps actix default (256 KiB).
+    pub max_body_bytes: Option<usize>,
+    /// Seconds to keep idle client connections open, `None` or 0 disables keep-alive (default).
+    pub keep_alive: Option<u64>,
+}
+
+/// Format of access log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// actix [`Logger`] default format.
+    #[default]
+    Plain,
+    /// One JSON object per request with method, path, status, duration and matched deceit.
+    Json,
+}
+
+impl LogFormat {
+    /// Read `APATHE_LOG_FORMAT`, anything except `json` means plain.
+    pub fn from_env() -> Self {
+        match std::env::var("APATHE_LOG_FORMAT") {
+            Ok(v) if v.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Plain,
+        }
+    }
 }
 
 impl Default for ApateConfig {
     fn default() -> Self {
         Self {
             port: DEFAULT_PORT,
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            extra_binds: Default::default(),
             specs: Default::default(),
             processors: Default::default(),
+            pre_processors: Default::default(),
+            record_requests: false,
+            journal_capacity: 0,
+            specs_files: Default::default(),
+            specs_imports: Default::default(),
+            watch: false,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            path_prefix: None,
+            cert_path: None,
+            key_path: None,
+            workers: None,
+            max_connections: None,
+            log_format: LogFormat::Plain,
+            match_trace: false,
+            max_body_bytes: None,
+            keep_alive: None,
         }
     }
 }
@@ -64,31 +154,91 @@ impl ApateConfig {
                 .unwrap_or(DEFAULT_PORT)
         };
 
-        let specs = Self::read_specs(specs_files)?;
+        let watch = std::env::var("APATHE_WATCH").is_ok_and(|v| !v.is_empty() && v != "0");
+        let match_trace =
+            std::env::var("APATHE_MATCH_TRACE").is_ok_and(|v| !v.is_empty() && v != "0");
+
+        let shutdown_timeout_secs = std::env::var("APATHE_SHUTDOWN_TIMEOUT")
+            .map(|t| t.parse::<u64>().unwrap())
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+
+        let path_prefix = std::env::var("APATHE_PATH_PREFIX").ok();
+        let cert_path = std::env::var("APATHE_TLS_CERT").ok();
+        let key_path = std::env::var("APATHE_TLS_KEY").ok();
+        let workers = std::env::var("APATHE_WORKERS")
+            .ok()
+            .map(|w| w.parse::<usize>().unwrap());
+        let max_connections = std::env::var("APATHE_MAX_CONNECTIONS")
+            .ok()
+            .map(|c| c.parse::<usize>().unwrap());
+        let max_body_bytes = std::env::var("APATHE_MAX_BODY_BYTES")
+            .ok()
+            .map(|b| b.parse::<usize>().unwrap());
+        let keep_alive = std::env::var("APATHE_KEEP_ALIVE")
+            .ok()
+            .map(|k| k.parse::<u64>().unwrap());
+
+        let mut specs_files = specs_files;
+        specs_files.extend(Self::read_paths_from_env());
+
+        let specs = Self::read_specs(&specs_files, &[])?;
 
         Ok(ApateConfig {
             port,
             specs,
-            processors: Default::default(),
+            specs_files,
+            watch,
+            shutdown_timeout_secs,
+            path_prefix,
+            cert_path,
+            key_path,
+            workers,
+            max_connections,
+            log_format: LogFormat::from_env(),
+            match_trace,
+            max_body_bytes,
+            keep_alive,
+            ..Default::default()
         })
     }
 
-    fn read_specs(specs_files: Vec<String>) -> color_eyre::Result<ApateSpecs> {
+    pub(crate) fn read_specs(
+        specs_files: &[String],
+        specs_imports: &[SpecsImport],
+    ) -> color_eyre::Result<ApateSpecs> {
         let mut specs = ApateSpecs::default();
 
         for path in specs_files {
-            let parsed = Self::parse_specs_from(&path)?;
+            let parsed = Self::parse_specs_from(path)?;
             specs.append(parsed);
         }
-
-        for path in Self::read_paths_from_env() {
-            let parsed = Self::parse_specs_from(&path)?;
-            specs.append(parsed);
+        for import in specs_imports {
+            specs.append(import.load()?);
         }
         Ok(specs)
     }
 
+    /// Append deceits generated from API description and keep it to be imported again on specs reload.
+    pub fn add_specs_import(&mut self, import: SpecsImport) -> color_eyre::Result<()> {
+        self.specs.append(import.load()?);
+        self.specs_imports.push(import);
+        Ok(())
+    }
+
     fn parse_specs_from(path: &str) -> color_eyre::Result<ApateSpecs> {
+        if is_remote_specs(path) {
+            log::debug!("Fetching specs from: {}", path);
+            #[cfg(feature = "remote-specs")]
+            return remote::fetch_specs(path);
+            #[cfg(not(feature = "remote-specs"))]
+            color_eyre::eyre::bail!("Loading specs from {path} requires \"remote-specs\" feature");
+        }
+
+        if path == STDIN_SPECS {
+            log::debug!("Parsing config from stdin");
+            return parse_stdin_specs(read_stdin_specs()?);
+        }
+
         log::debug!("Parsing TOML config from: {}", path);
 
         let mut file = std::fs::File::open(path)
@@ -97,8 +247,7 @@ impl ApateConfig {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
 
-        let specs: ApateSpecs = toml::from_slice(&buf)?;
-        Ok(specs)
+        parse_toml_specs(path, &buf)
     }
 
     fn read_paths_from_env() -> Vec<String> {
@@ -113,16 +262,110 @@ impl ApateConfig {
             .collect()
     }
 
-    fn into_state(self) -> ApateState {
+    fn into_state(mut self) -> color_eyre::Result<ApateState> {
+        self.specs.sort_by_priority();
+        self.specs.check_uris()?;
         let rhai = RhaiState::default();
         rhai.clear_and_update(self.specs.rhai.clone());
-        ApateState {
+        rhai.compile_all()?;
+        let minijinja = MiniJinjaState::default();
+        minijinja.clear_and_update(self.specs.fragments.clone());
+        Ok(ApateState {
             specs: RwLock::new(self.specs),
             processors: self.processors,
+            pre_processors: self.pre_processors,
+            minijinja,
             rhai,
+            record_requests: self.record_requests,
+            journal: RequestJournal::new(self.journal_capacity),
+            path_prefix: self.path_prefix.as_deref().and_then(normalize_path_prefix),
+            match_trace: self.match_trace,
             ..Default::default()
+        })
+    }
+}
+
+/// Prefix always starts with `/` and has no trailing one, `/` alone means no prefix.
+fn normalize_path_prefix(prefix: &str) -> Option<String> {
+    let prefix = prefix.trim().trim_matches('/');
+    (!prefix.is_empty()).then(|| format!("/{prefix}"))
+}
+
+/// Env variables are expanded in specs text before TOML parsing.
+fn parse_toml_specs(path: &str, buf: &[u8]) -> color_eyre::Result<ApateSpecs> {
+    let content = expanded_specs_text(path, buf)?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// Stdin has no file extension to tell the format, so YAML is tried when TOML parsing fails.
+fn parse_stdin_specs(buf: &[u8]) -> color_eyre::Result<ApateSpecs> {
+    let content = expanded_specs_text(STDIN_SPECS, buf)?;
+    toml::from_str(&content).or_else(|toml_err| {
+        serde_yaml::from_str(&content).map_err(|yaml_err| {
+            color_eyre::eyre::eyre!(
+                "Can't parse specs from stdin as TOML ({toml_err}) or YAML ({yaml_err})"
+            )
+        })
+    })
+}
+
+pub(crate) fn expanded_specs_text(path: &str, buf: &[u8]) -> color_eyre::Result<String> {
+    let content = std::str::from_utf8(buf)
+        .map_err(|e| color_eyre::eyre::eyre!("Specs {path} are not valid UTF-8. {e}"))?;
+    expand_env_vars(content).map_err(|e| color_eyre::eyre::eyre!("Can't parse {path}. {e}"))
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` with process env values, `$${VAR}` stays as `${VAR}`.
+/// Only upper case names are expanded, so Rhai `${var}` string interpolation is left untouched.
+fn expand_env_vars(content: &str) -> color_eyre::Result<String> {
+    static ENV_VAR: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"\$(\$)?\{([A-Z_][A-Z0-9_]*)(?::-([^}]*))?\}").expect("Env var regex")
+    });
+
+    let mut expanded = String::with_capacity(content.len());
+    let mut last = 0;
+    for caps in ENV_VAR.captures_iter(content) {
+        let whole = caps.get(0).expect("Whole match");
+        expanded.push_str(&content[last..whole.start()]);
+        last = whole.end();
+
+        if caps.get(1).is_some() {
+            expanded.push_str(&whole.as_str()[1..]);
+            continue;
         }
+
+        let name = &caps[2];
+        match (std::env::var(name), caps.get(3)) {
+            (Ok(value), _) => expanded.push_str(&value),
+            (Err(_), Some(default)) => expanded.push_str(default.as_str()),
+            (Err(_), None) => color_eyre::eyre::bail!("Env variable {name} is not defined"),
+        }
+    }
+    expanded.push_str(&content[last..]);
+
+    Ok(expanded)
+}
+
+/// Specs "file" name to read specs from standard input.
+pub(crate) const STDIN_SPECS: &str = "-";
+
+/// Stdin could be read only once, so its content is kept for specs reloads.
+fn read_stdin_specs() -> color_eyre::Result<&'static [u8]> {
+    static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+    if let Some(buf) = STDIN.get() {
+        return Ok(buf);
     }
+
+    let mut buf = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut buf)
+        .map_err(|e| color_eyre::eyre::eyre!("Can't read specs from stdin. {e}"))?;
+    Ok(STDIN.get_or_init(|| buf))
+}
+
+/// Specs "file" could be an HTTP(S) URL to fetch specs from.
+pub(crate) fn is_remote_specs(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -131,20 +374,65 @@ pub struct ApateSpecs {
     pub rhai: Vec<RhaiScript>,
     #[serde(default)]
     pub deceit: Vec<Deceit>,
+    /// Named minijinja snippets that templates could render with `fragment(name)`.
+    #[serde(default)]
+    pub fragments: HashMap<String, String>,
+    /// Response for requests that no deceit could handle (404 unless code is defined).
+    /// Without it plain text 404 response is returned.
+    #[serde(default)]
+    pub default_deceit: Option<DeceitResponse>,
 }
 
 impl ApateSpecs {
+    /// Deceits with higher priority go first, specs order is kept for equal ones.
+    pub fn sort_by_priority(&mut self) {
+        self.deceit.sort_by_key(|d| std::cmp::Reverse(d.priority));
+    }
+
+    /// Fails on the first deceit with invalid URI globs or path arg defaults that are never used.
+    pub fn check_uris(&self) -> color_eyre::Result<()> {
+        for (idx, d) in self.deceit.iter().enumerate() {
+            d.check_uris()
+                .map_err(|e| color_eyre::eyre::eyre!("Deceit {}: {e}", d.label(idx)))?;
+        }
+        Ok(())
+    }
+
+    /// Some deceit or response breaks client connections, see [`Fault::breaks_connection`].
+    pub fn has_connection_faults(&self) -> bool {
+        let breaks = |fault: &Option<Fault>| fault.as_ref().is_some_and(Fault::breaks_connection);
+        self.default_deceit
+            .as_ref()
+            .is_some_and(|d| breaks(&d.fault))
+            || self
+                .deceit
+                .iter()
+                .any(|d| breaks(&d.fault) || d.responses.iter().any(|r| breaks(&r.fault)))
+    }
+
+    /// Default deceit and fragments from appended specs win if defined.
     pub fn append(&mut self, specs: ApateSpecs) {
         self.deceit.extend(specs.deceit);
         self.rhai.extend(specs.rhai);
+        self.fragments.extend(specs.fragments);
+        if specs.default_deceit.is_some() {
+            self.default_deceit = specs.default_deceit;
+        }
     }
 
+    /// Current default deceit and fragments win if defined.
     pub fn prepend(&mut self, mut specs: ApateSpecs) {
         specs.deceit.extend(self.deceit.clone());
         specs.rhai.extend(self.rhai.clone());
+        for (name, fragment) in specs.fragments {
+            self.fragments.entry(name).or_insert(fragment);
+        }
 
         self.deceit = specs.deceit;
         self.rhai = specs.rhai;
+        if self.default_deceit.is_none() {
+            self.default_deceit = specs.default_deceit;
+        }
     }
 }
 
@@ -154,14 +442,46 @@ pub struct ApateState {
     pub specs: RwLock<ApateSpecs>,
     pub counters: ApateCounters,
     pub processors: HashMap<String, ApateProcessor>,
+    pub pre_processors: Vec<ApatePreProcessor>,
     pub minijinja: MiniJinjaState,
     pub rhai: RhaiState,
+    pub record_requests: bool,
+    pub journal: RequestJournal,
+    /// When set, mock requests are answered with 503 while admin API keeps working.
+    pub draining: AtomicBool,
+    /// Number of requests that no deceit could handle (answered with 404).
+    pub unmatched_requests: AtomicU64,
+    /// Normalized prefix stripped from request paths, see [`ApateConfig::path_prefix`].
+    pub path_prefix: Option<String>,
+    /// See [`ApateConfig::match_trace`].
+    pub match_trace: bool,
+    /// How many times responses with `max_matches` were used, keyed by response resource ref.
+    pub response_matches: ApateCounters,
+    /// Current states of response scenarios.
+    pub scenarios: ScenarioStates,
+    /// Bodies of remote cached outputs by URL.
+    pub remote_cache: RemoteCache,
+    /// Token buckets of deceits with `rate_limit`.
+    pub rate_limiter: RateLimiter,
+    /// Compiled schemas of response validation processors.
+    pub schemas: SchemaValidators,
 }
 
 impl ApateState {
     pub fn clear_cache(&self) {
         self.minijinja.clear();
     }
+
+    /// Drop all cached templates & compiled scripts and load Rhai scripts & fragments from new specs.
+    /// Must be called after any specs mutation.
+    pub fn reload_caches(&self, specs: &ApateSpecs) {
+        self.minijinja.clear_and_update(specs.fragments.clone());
+        self.rhai.clear_and_update(specs.rhai.clone());
+        // Responses could be moved to other indexes
+        self.response_matches.clear();
+        self.rate_limiter.clear();
+        self.schemas.clear();
+    }
 }
 
 #[derive(Clone, Default)]
@@ -190,6 +510,183 @@ impl ApateCounters {
         let prev_value = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         Ok(prev_value)
     }
+
+    /// Decrement counter by key and return previous value. Counter never goes below zero.
+    pub fn get_and_decrement(&self, key: &str) -> color_eyre::Result<u64> {
+        let mut counters = self
+            .counters
+            .write()
+            .map_err(|e| color_eyre::eyre::eyre!("{e}"))?;
+
+        let counter = counters.entry(key.to_string()).or_default();
+        let prev_value = counter
+            .fetch_update(
+                std::sync::atomic::Ordering::SeqCst,
+                std::sync::atomic::Ordering::SeqCst,
+                |v| Some(v.saturating_sub(1)),
+            )
+            .unwrap_or_default();
+        Ok(prev_value)
+    }
+
+    /// Set counter value by key and return previous value.
+    pub fn set(&self, key: &str, value: u64) -> color_eyre::Result<u64> {
+        let mut counters = self
+            .counters
+            .write()
+            .map_err(|e| color_eyre::eyre::eyre!("{e}"))?;
+
+        let counter = counters.entry(key.to_string()).or_default();
+        Ok(counter.swap(value, std::sync::atomic::Ordering::SeqCst))
+    }
+
+    /// Reset counter by key to zero and return previous value.
+    pub fn reset(&self, key: &str) -> color_eyre::Result<u64> {
+        self.set(key, 0)
+    }
+
+    /// Drop all counters.
+    pub fn clear(&self) {
+        match self.counters.write() {
+            Ok(mut counters) => counters.clear(),
+            Err(e) => log::error!("Can't clear counters: {e}"),
+        }
+    }
+}
+
+/// Current state of every scenario by its name.
+/// Scenarios that were never moved are in [`deceit::SCENARIO_STARTED`] state.
+#[derive(Clone, Default)]
+pub struct ScenarioStates {
+    states: Arc<std::sync::RwLock<HashMap<String, String>>>,
+}
+
+impl ScenarioStates {
+    pub fn get(&self, name: &str) -> String {
+        match self.states.read() {
+            Ok(states) => states
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| deceit::SCENARIO_STARTED.to_string()),
+            Err(e) => {
+                log::error!("Can't read scenario {name} state: {e}");
+                deceit::SCENARIO_STARTED.to_string()
+            }
+        }
+    }
+
+    pub fn set(&self, name: &str, state: &str) {
+        match self.states.write() {
+            Ok(mut states) => {
+                states.insert(name.to_string(), state.to_string());
+            }
+            Err(e) => log::error!("Can't set scenario {name} state: {e}"),
+        }
+    }
+
+    /// Move scenario back to the initial state.
+    pub fn reset(&self, name: &str) {
+        match self.states.write() {
+            Ok(mut states) => {
+                states.remove(name);
+            }
+            Err(e) => log::error!("Can't reset scenario {name}: {e}"),
+        }
+    }
+
+    /// Move all scenarios back to the initial state.
+    pub fn reset_all(&self) {
+        match self.states.write() {
+            Ok(mut states) => states.clear(),
+            Err(e) => log::error!("Can't reset scenarios: {e}"),
+        }
+    }
+}
+
+/// Token buckets for [`deceit::RateLimit`] by key like deceit ref with optional client IP.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<String, (f64, Instant)>>>,
+}
+
+impl RateLimiter {
+    /// Take a token from the bucket or return number of seconds to wait for the next one.
+    pub fn acquire(&self, key: &str, limit: &deceit::RateLimit) -> Result<(), u64> {
+        let capacity = limit.requests as f64;
+        let rate = capacity / limit.per_secs.max(1) as f64;
+        if rate <= 0.0 {
+            return Err(limit.per_secs);
+        }
+
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(e) => {
+                log::error!("Can't check rate limit {key}: {e}");
+                return Ok(());
+            }
+        };
+
+        let now = Instant::now();
+        let (tokens, updated) = buckets.entry(key.to_string()).or_insert((capacity, now));
+        *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * rate).min(capacity);
+        *updated = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - *tokens) / rate).ceil() as u64)
+        }
+    }
+
+    /// Refill all buckets.
+    pub fn clear(&self) {
+        match self.buckets.lock() {
+            Ok(mut buckets) => buckets.clear(),
+            Err(e) => log::error!("Can't clear rate limits: {e}"),
+        }
+    }
+}
+
+/// Response bodies fetched for remote cached outputs, see [`output::OutputType::RemoteCached`].
+#[derive(Clone, Default)]
+pub struct RemoteCache {
+    bodies: Arc<std::sync::RwLock<HashMap<String, Vec<u8>>>>,
+}
+
+impl RemoteCache {
+    pub fn get(&self, url: &str) -> Option<Vec<u8>> {
+        match self.bodies.read() {
+            Ok(bodies) => bodies.get(url).cloned(),
+            Err(e) => {
+                log::error!("Can't read remote cache: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.bodies
+            .read()
+            .is_ok_and(|bodies| bodies.contains_key(url))
+    }
+
+    pub fn insert(&self, url: &str, body: Vec<u8>) {
+        match self.bodies.write() {
+            Ok(mut bodies) => {
+                bodies.insert(url.to_string(), body);
+            }
+            Err(e) => log::error!("Can't update remote cache: {e}"),
+        }
+    }
+
+    /// Drop all cached bodies, so they are fetched again on next use.
+    pub fn clear(&self) {
+        match self.bodies.write() {
+            Ok(mut bodies) => bodies.clear(),
+            Err(e) => log::error!("Can't clear remote cache: {e}"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -198,7 +695,17 @@ pub struct RequestContext {
     pub headers: Arc<HashMap<String, String>>,
     pub path: Arc<String>,
     pub request_path: Arc<String>,
+    /// Request path exactly as it was received (no URL decoding).
+    /// It must not be affected by any path rewriting.
+    pub raw_path: Arc<String>,
+    /// First value for every query argument.
     pub query_args: Arc<HashMap<String, String>>,
+    /// All values for every query argument like `?tag=a&tag=b` in order of appearance.
+    pub query_args_multi: Arc<HashMap<String, Vec<String>>>,
+    /// IP address of the connected client (could be a proxy), unknown for replayed requests.
+    pub peer_ip: Option<IpAddr>,
+    /// First (client) address from `X-Forwarded-For` header if any, it could be forged.
+    pub forwarded_ip: Option<IpAddr>,
     pub path_args: Arc<HashMap<String, String>>,
     pub body: Arc<Bytes>,
     #[allow(clippy::type_complexity)]
@@ -221,31 +728,124 @@ impl RequestContext {
             .collect();
 
         let mut args_query: HashMap<String, String> = Default::default();
+        let mut args_query_multi: HashMap<String, Vec<String>> = Default::default();
         let qstring = req.uri().query().unwrap_or_default();
-        if let Ok(qargs) = serde_urlencoded::from_str::<HashMap<String, String>>(qstring) {
-            args_query = qargs;
+        if let Ok(qargs) = serde_urlencoded::from_str::<Vec<(String, String)>>(qstring) {
+            for (name, value) in qargs {
+                args_query
+                    .entry(name.clone())
+                    .or_insert_with(|| value.clone());
+                args_query_multi.entry(name).or_default().push(value);
+            }
         } else {
             log::error!("Can't decode query string from URL");
         }
+        let peer_ip = req.peer_addr().map(|addr| addr.ip().to_canonical());
+        let forwarded_ip = req
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|xff| xff.to_str().ok()?.split(',').next()?.trim().parse().ok());
         let request_path = Arc::new(req.path().to_string());
+        let raw_path = Arc::new(req.uri().path().to_string());
 
         Self {
             body: Arc::new(body),
             method,
             request_path,
+            raw_path,
             headers: Arc::new(headers),
             query_args: Arc::new(args_query),
+            query_args_multi: Arc::new(args_query_multi),
+            peer_ip,
+            forwarded_ip,
             path: Arc::new("/".to_string()),
             path_args: Arc::new(Default::default()),
             body_json: Default::default(),
         }
     }
 
+    /// Strip prefix from the request path, returns false if path is not under the prefix.
+    /// Raw path stays untouched.
+    pub fn strip_path_prefix(&mut self, prefix: &str) -> bool {
+        let stripped = match self.request_path.strip_prefix(prefix) {
+            Some("") => "/",
+            Some(rest) if rest.starts_with('/') => rest,
+            _ => return false,
+        };
+        self.request_path = Arc::new(stripped.to_string());
+        true
+    }
+
     pub fn update_paths(&mut self, path: String, args_path: HashMap<String, String>) {
         self.path = Arc::new(path);
         self.path_args = Arc::new(args_path);
     }
 
+    /// Parse `application/x-www-form-urlencoded` body, first value wins for repeated fields.
+    /// Returns empty map for other content types.
+    pub fn load_form(&self) -> HashMap<String, String> {
+        if !self.has_content_type("application/x-www-form-urlencoded") {
+            return Default::default();
+        }
+
+        match serde_urlencoded::from_bytes::<Vec<(String, String)>>(&self.body) {
+            // Reversed, so the first value is inserted last
+            Ok(fields) => fields.into_iter().rev().collect(),
+            Err(e) => {
+                log::warn!("Can't decode form body: {e}");
+                Default::default()
+            }
+        }
+    }
+
+    /// Parse text fields of `multipart/form-data` body, file parts are skipped.
+    /// Returns empty map for other content types.
+    pub fn load_multipart(&self) -> HashMap<String, String> {
+        if !self.has_content_type("multipart/form-data") {
+            return Default::default();
+        }
+
+        let content_type = self.headers.get("content-type").map(String::as_str);
+        let boundary = match multer::parse_boundary(content_type.unwrap_or_default()) {
+            Ok(b) => b,
+            Err(e) => {
+                log::warn!("Can't parse multipart boundary: {e}");
+                return Default::default();
+            }
+        };
+
+        // Whole body is already here, so stream is ready & nothing really blocks
+        let body = self.body.as_ref().clone();
+        let stream = futures::stream::once(async move { Ok::<_, std::io::Error>(body) });
+        let mut multipart = multer::Multipart::new(stream, boundary);
+
+        let parsed = futures::executor::block_on(async {
+            let mut fields = HashMap::new();
+            while let Some(field) = multipart.next_field().await? {
+                let Some(name) = field.name().map(str::to_string) else {
+                    continue;
+                };
+                if field.file_name().is_some() {
+                    continue;
+                }
+                let value = field.text().await?;
+                fields.entry(name).or_insert(value);
+            }
+            Ok::<_, multer::Error>(fields)
+        });
+
+        parsed.unwrap_or_else(|e| {
+            log::warn!("Can't decode multipart body: {e}");
+            Default::default()
+        })
+    }
+
+    fn has_content_type(&self, media_type: &str) -> bool {
+        self.headers
+            .get("content-type")
+            .is_some_and(|ct| matchers::media_type_matches(media_type, ct))
+    }
+
     pub fn load_body_as_json(&self) -> Result<Arc<serde_json::Value>, String> {
         let mut guard = self
             .body_json
@@ -271,8 +871,46 @@ impl RequestContext {
 }
 
 /// Create and run apate server based on input config.
+/// On SIGTERM or Ctrl-C server stops accepting connections
+/// and waits for in-flight requests up to `shutdown_timeout_secs`.
 pub async fn apate_server_run(config: ApateConfig) -> std::io::Result<()> {
-    init_actix_web_server(config)?.await
+    let shutdown_timeout_secs = config.shutdown_timeout_secs;
+    let (server, _) = init_actix_web_server(config)?;
+    let server_handle = server.handle();
+
+    actix_web::rt::spawn(async move {
+        let signal = wait_for_shutdown_signal().await;
+        log::info!(
+            "Received {signal}, stopping server gracefully (timeout {shutdown_timeout_secs}s)"
+        );
+        server_handle.stop(true).await;
+    });
+
+    server.await?;
+    log::info!("Apate server stopped");
+    Ok(())
+}
+
+/// Resolves with signal name once process is asked to terminate.
+async fn wait_for_shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = sigterm.recv() => return "SIGTERM",
+                _ = tokio::signal::ctrl_c() => return "SIGINT",
+            },
+            Err(e) => log::error!("Can't listen for SIGTERM: {e}"),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        log::error!("Can't listen for Ctrl-C: {e}");
+        futures::future::pending::<()>().await;
+    }
+    "SIGINT"
 }
 
 /// Initialize server configuration with overrides.
@@ -284,21 +922,127 @@ pub fn apate_init_server_config(
 ) -> color_eyre::Result<ApateConfig> {
     let rust_log = log.unwrap_or(DEFAULT_RUST_LOG.to_string());
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(rust_log)).init();
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(rust_log));
+    if LogFormat::from_env() == LogFormat::Json {
+        // Access records must stay plain JSON lines for log aggregators
+        logger.format(|buf, record| {
+            use std::io::Write as _;
+            if record.target() == handlers::ACCESS_LOG_TARGET {
+                writeln!(buf, "{}", record.args())
+            } else {
+                writeln!(
+                    buf,
+                    "[{} {} {}] {}",
+                    buf.timestamp(),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                )
+            }
+        });
+    }
+    logger.init();
 
     ApateConfig::try_new(port, files)
 }
 
-fn init_actix_web_server(config: ApateConfig) -> std::io::Result<Server> {
+/// API description that is converted into specs, see [`ApateConfig::add_specs_import`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpecsImport {
+    /// OpenAPI 3 document (JSON or YAML)
+    OpenApi(String),
+    /// Postman v2.1 collection JSON
+    Postman(String),
+}
+
+impl SpecsImport {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::OpenApi(path) | Self::Postman(path) => path,
+        }
+    }
+
+    pub fn load(&self) -> color_eyre::Result<ApateSpecs> {
+        match self {
+            Self::OpenApi(path) => openapi::from_openapi(path),
+            Self::Postman(path) => postman::from_postman_collection(path),
+        }
+    }
+}
+
+/// Parse specs from config and return short summary if they are fine.
+/// Used to validate specs files without running a server.
+pub fn apate_specs_check(config: &ApateConfig) -> color_eyre::Result<String> {
+    let specs = &config.specs;
+    for (idx, d) in specs.deceit.iter().enumerate() {
+        if d.uris.is_empty() && d.uri_glob.is_empty() {
+            color_eyre::eyre::bail!("Deceit {idx} has no URIs");
+        }
+        if d.responses.is_empty() {
+            log::warn!("Deceit {idx} with URIs {:?} has no responses", d.uris);
+        }
+    }
+
+    specs.check_uris()?;
+
+    let rhai = RhaiState::default();
+    rhai.clear_and_update(specs.rhai.clone());
+    rhai.compile_all()?;
+
+    Ok(format!(
+        "Specs are OK: {} deceit(s), {} rhai script(s)",
+        specs.deceit.len(),
+        specs.rhai.len()
+    ))
+}
+
+/// Serialize specs from config into TOML string.
+pub fn apate_specs_dump(config: &ApateConfig) -> color_eyre::Result<String> {
+    Ok(toml::to_string(&config.specs)?)
+}
+
+fn init_actix_web_server(config: ApateConfig) -> std::io::Result<(Server, Data<ApateState>)> {
     if config.specs.deceit.is_empty() {
         log::warn!("Starting server without deceits in specs");
     }
     let port = config.port;
+    let bind = config.bind;
+    let extra_binds = config.extra_binds.clone();
+    let tls = load_tls_config(&config)?;
+    let workers = config.workers;
+    let max_connections = config.max_connections;
+    let keep_alive = match config.keep_alive {
+        Some(secs) if secs > 0 => KeepAlive::Timeout(Duration::from_secs(secs)),
+        _ => KeepAlive::Disabled,
+    };
+    let payload_config = config
+        .max_body_bytes
+        .map_or_else(PayloadConfig::default, PayloadConfig::new);
+    let json_log = config.log_format == LogFormat::Json;
+    let shutdown_timeout_secs = config.shutdown_timeout_secs;
+    let watch_sources = config
+        .watch
+        .then(|| (config.specs_files.clone(), config.specs_imports.clone()));
+
+    let data: Data<ApateState> = Data::new(config.into_state().map_err(std::io::Error::other)?);
+
+    if let Some((files, imports)) = watch_sources {
+        #[cfg(feature = "watch")]
+        watch::spawn_specs_watcher(files, imports, data.clone().into_inner())
+            .map_err(std::io::Error::other)?;
+        #[cfg(not(feature = "watch"))]
+        log::warn!("Specs watching requires \"watch\" feature, ignoring {files:?} {imports:?}");
+    }
 
-    let data: Data<ApateState> = Data::new(config.into_state());
-
-    let server = HttpServer::new(move || {
-        let mut app = App::new().app_data(data.clone()).wrap(Logger::default());
+    let app_data = data.clone();
+    let conn_data = data.clone();
+    let mut server = HttpServer::new(move || {
+        let mut app = App::new()
+            .app_data(app_data.clone())
+            .app_data(payload_config.clone())
+            .wrap(Condition::new(!json_log, Logger::default()))
+            .wrap(Condition::new(json_log, from_fn(handlers::json_access_log)));
         #[cfg(feature = "server")]
         {
             app = app
@@ -306,27 +1050,100 @@ fn init_actix_web_server(config: ApateConfig) -> std::io::Result<Server> {
         }
         app.default_service(web::to(handlers::apate_server_handler))
     })
-    .bind((Ipv4Addr::UNSPECIFIED, port))?
-    .keep_alive(actix_web::http::KeepAlive::Disabled)
-    .run();
+    .on_connect(move |conn, ext| handlers::on_connect(&conn_data, conn, ext));
 
-    Ok(server)
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
+    if let Some(max_connections) = max_connections {
+        server = server.max_connections(max_connections);
+    }
+
+    for addr in std::iter::once(SocketAddr::new(bind, port)).chain(extra_binds) {
+        server = match &tls {
+            #[cfg(feature = "tls")]
+            Some(tls) => server.bind_rustls_0_23(addr, tls.clone())?,
+            _ => server.bind(addr)?,
+        };
+    }
+
+    let server = server
+        .keep_alive(keep_alive)
+        .shutdown_timeout(shutdown_timeout_secs)
+        // signals are handled by apate_server_run, test servers are stopped by handle
+        .disable_signals()
+        .run();
+
+    Ok((server, data))
+}
+
+#[cfg(feature = "tls")]
+fn load_tls_config(config: &ApateConfig) -> std::io::Result<Option<rustls::ServerConfig>> {
+    match (&config.cert_path, &config.key_path) {
+        (Some(cert), Some(key)) => tls::load_server_config(cert, key).map(Some),
+        (None, None) => Ok(None),
+        _ => Err(std::io::Error::other(
+            "Both TLS certificate and key paths must be set",
+        )),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls_config(config: &ApateConfig) -> std::io::Result<Option<std::convert::Infallible>> {
+    if config.cert_path.is_some() || config.key_path.is_some() {
+        return Err(std::io::Error::other(
+            "HTTPS listener requires \"tls\" feature",
+        ));
+    }
+    Ok(None)
 }
 
 pub struct ApateConfigBuilder {
     port: u16,
+    bind: IpAddr,
+    extra_binds: Vec<SocketAddr>,
     deceit: Vec<Deceit>,
+    default_deceit: Option<DeceitResponse>,
     pub processors: HashMap<String, ApateProcessor>,
+    pre_processors: Vec<ApatePreProcessor>,
     scripts: HashMap<String, String>,
+    fragments: HashMap<String, String>,
+    journal_capacity: usize,
+    shutdown_timeout_secs: u64,
+    path_prefix: Option<String>,
+    cert_path: Option<String>,
+    key_path: Option<String>,
+    workers: Option<usize>,
+    max_connections: Option<usize>,
+    log_format: LogFormat,
+    match_trace: bool,
+    max_body_bytes: Option<usize>,
+    keep_alive: Option<u64>,
 }
 
 impl Default for ApateConfigBuilder {
     fn default() -> Self {
         Self {
             port: DEFAULT_PORT,
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            extra_binds: Default::default(),
             deceit: Default::default(),
+            default_deceit: None,
             processors: Default::default(),
+            pre_processors: Default::default(),
             scripts: Default::default(),
+            fragments: Default::default(),
+            journal_capacity: 0,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            path_prefix: None,
+            cert_path: None,
+            key_path: None,
+            workers: None,
+            max_connections: None,
+            log_format: LogFormat::Plain,
+            match_trace: false,
+            max_body_bytes: None,
+            keep_alive: None,
         }
     }
 }
@@ -337,26 +1154,120 @@ impl ApateConfigBuilder {
         self
     }
 
+    pub fn with_bind(mut self, bind: IpAddr) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    /// Bind server to one more address besides the main one.
+    pub fn add_bind(mut self, addr: SocketAddr) -> Self {
+        self.extra_binds.push(addr);
+        self
+    }
+
+    /// Keep up to `capacity` last requests in the requests journal.
+    pub fn with_journal_capacity(mut self, capacity: usize) -> Self {
+        self.journal_capacity = capacity;
+        self
+    }
+
+    /// Seconds to wait for in-flight requests to finish when server is stopping.
+    pub fn with_shutdown_timeout_secs(mut self, secs: u64) -> Self {
+        self.shutdown_timeout_secs = secs;
+        self
+    }
+
+    /// Strip prefix like `/mock` from request paths before matching deceits.
+    pub fn with_path_prefix(mut self, prefix: &str) -> Self {
+        self.path_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Number of worker threads instead of actix default (number of physical CPUs).
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Max concurrent connections per worker instead of actix default.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Answer 413 to requests with bodies over the limit instead of actix default 256 KiB.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = Some(max_body_bytes);
+        self
+    }
+
+    /// Keep idle client connections open for given seconds, 0 disables keep-alive.
+    pub fn with_keep_alive(mut self, secs: u64) -> Self {
+        self.keep_alive = Some(secs);
+        self
+    }
+
+    pub fn with_log_format(mut self, log_format: LogFormat) -> Self {
+        self.log_format = log_format;
+        self
+    }
+
+    /// Answer `X-Apate-Trace: 1` requests with matched deceit headers or 404 diagnostics.
+    pub fn with_match_trace(mut self, enabled: bool) -> Self {
+        self.match_trace = enabled;
+        self
+    }
+
+    /// Serve HTTPS with PEM certificate & key files, HTTP/2 is enabled as well.
+    pub fn with_tls(mut self, cert_path: &str, key_path: &str) -> Self {
+        self.cert_path = Some(cert_path.to_string());
+        self.key_path = Some(key_path.to_string());
+        self
+    }
+
     pub fn add_script(mut self, id: &str, script: &str) -> Self {
         self.scripts.insert(id.to_string(), script.to_string());
         self
     }
 
+    /// Add minijinja snippet that templates could render with `fragment(name)`.
+    pub fn add_fragment(mut self, name: &str, fragment: &str) -> Self {
+        self.fragments
+            .insert(name.to_string(), fragment.to_string());
+        self
+    }
+
     pub fn add_deceit(mut self, deceit: Deceit) -> Self {
         self.deceit.push(deceit);
         self
     }
 
+    /// Response for requests that no deceit could handle.
+    pub fn with_default_deceit(mut self, response: DeceitResponse) -> Self {
+        self.default_deceit = Some(response);
+        self
+    }
+
     pub fn register_processor(mut self, processor: ApateProcessor) -> Self {
         self.processors.insert(processor.id.clone(), processor);
         self
     }
 
+    /// Pre processors run in registration order before deceits matching.
+    pub fn register_pre_processor(mut self, processor: ApatePreProcessor) -> Self {
+        self.pre_processors.push(processor);
+        self
+    }
+
     pub fn build(self) -> ApateConfig {
         ApateConfig {
             port: self.port,
+            bind: self.bind,
+            extra_binds: self.extra_binds,
             specs: ApateSpecs {
                 deceit: self.deceit,
+                fragments: self.fragments,
+                default_deceit: self.default_deceit,
                 rhai: self
                     .scripts
                     .into_iter()
@@ -364,6 +1275,19 @@ impl ApateConfigBuilder {
                     .collect(),
             },
             processors: self.processors,
+            pre_processors: self.pre_processors,
+            journal_capacity: self.journal_capacity,
+            shutdown_timeout_secs: self.shutdown_timeout_secs,
+            path_prefix: self.path_prefix,
+            cert_path: self.cert_path,
+            key_path: self.key_path,
+            workers: self.workers,
+            max_connections: self.max_connections,
+            log_format: self.log_format,
+            match_trace: self.match_trace,
+            max_body_bytes: self.max_body_bytes,
+            keep_alive: self.keep_alive,
+            ..Default::default()
         }
     }
 }
@@ -407,6 +1331,20 @@ impl Display for ResourceRef {
     }
 }
 
+/// Deterministic UUID v5 generated from namespace and name.
+/// Namespace is either a UUID string or one of predefined: `dns`, `url`, `oid`, `x500`.
+pub(crate) fn uuid_v5(namespace: &str, name: &str) -> Result<uuid::Uuid, String> {
+    let namespace = match namespace.to_lowercase().as_str() {
+        "dns" => uuid::Uuid::NAMESPACE_DNS,
+        "url" => uuid::Uuid::NAMESPACE_URL,
+        "oid" => uuid::Uuid::NAMESPACE_OID,
+        "x500" => uuid::Uuid::NAMESPACE_X500,
+        other => uuid::Uuid::parse_str(other)
+            .map_err(|e| format!("Invalid UUID v5 namespace \"{namespace}\": {e}"))?,
+    };
+    Ok(uuid::Uuid::new_v5(&namespace, name.as_bytes()))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1,24 +1,113 @@
-use std::io;
+use std::{io, net::IpAddr};
 
-use apate::{apate_init_server_config, apate_server_run};
+use apate::{
+    SpecsImport, apate_init_server_config, apate_server_run, apate_specs_check, apate_specs_dump,
+    journal::DEFAULT_JOURNAL_CAPACITY,
+};
+
+/// Apate CLI subcommands.
+/// Bare `apate <files>` invocation without subcommand means `serve`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// Run API mocking server
+    Serve,
+    /// Parse specs and exit with error if something is wrong
+    Check,
+    /// Print resulting specs as TOML
+    Dump,
+    /// Run API mocking server that logs every incoming request and keeps it in the journal
+    Record,
+}
+
+impl Command {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "serve" => Some(Self::Serve),
+            "check" => Some(Self::Check),
+            "dump" => Some(Self::Dump),
+            "record" => Some(Self::Record),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CliArgs {
+    command: Command,
+    port: Option<u16>,
+    log: Option<String>,
+    bind: Option<IpAddr>,
+    watch: bool,
+    /// OpenAPI document to generate deceits from (appended after specs files).
+    import_openapi: Option<String>,
+    /// Postman v2.1 collection to generate deceits from (appended after OpenAPI ones).
+    import_postman: Option<String>,
+    files: Vec<String>,
+}
+
+/// Long options supported by CLI and their short equivalents.
+const LONG_OPTIONS: &[(&str, &str)] = &[
+    ("--bind", "-b"),
+    ("--watch", "-w"),
+    ("--import-openapi", "-O"),
+    ("--import-postman", "-P"),
+];
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
-    let (port, log, spec_files) = parse_args()?;
+    let args = parse_args()?;
 
-    let config = apate_init_server_config(port, log, spec_files).map_err(io::Error::other)?;
+    let mut config =
+        apate_init_server_config(args.port, args.log, args.files).map_err(io::Error::other)?;
+
+    if let Some(bind) = args.bind {
+        config.bind = bind;
+    }
+    if args.watch {
+        config.watch = true;
+    }
+    if let Some(path) = args.import_openapi {
+        config
+            .add_specs_import(SpecsImport::OpenApi(path))
+            .map_err(io::Error::other)?;
+    }
+    if let Some(path) = args.import_postman {
+        config
+            .add_specs_import(SpecsImport::Postman(path))
+            .map_err(io::Error::other)?;
+    }
 
     log::debug!("Configuration initialized: {:?}", config);
 
-    apate_server_run(config).await
+    match args.command {
+        Command::Serve => apate_server_run(config).await,
+        Command::Record => {
+            config.record_requests = true;
+            if config.journal_capacity == 0 {
+                config.journal_capacity = DEFAULT_JOURNAL_CAPACITY;
+            }
+            apate_server_run(config).await
+        }
+        Command::Check => {
+            let summary = apate_specs_check(&config).map_err(io::Error::other)?;
+            println!("{summary}");
+            Ok(())
+        }
+        Command::Dump => {
+            let toml = apate_specs_dump(&config).map_err(io::Error::other)?;
+            println!("{toml}");
+            Ok(())
+        }
+    }
 }
 
-fn parse_args() -> io::Result<(Option<u16>, Option<String>, Vec<String>)> {
+fn parse_args() -> io::Result<CliArgs> {
     let mut port = None;
     let mut log = None;
-    let mut files = Vec::new();
+    let mut bind = None;
 
-    let cli = getopt3::new(getopt3::hideBin(std::env::args()), "p:l:");
+    let args = translate_long_options(getopt3::hideBin(std::env::args()));
+    let cli = getopt3::new(args, "p:l:b:wO:P:");
     match cli {
         Ok(g) => {
             if let Some(port_str) = g.options.get(&'p') {
@@ -32,12 +121,66 @@ fn parse_args() -> io::Result<(Option<u16>, Option<String>, Vec<String>)> {
                 log = Some(log_str.clone())
             }
 
-            for path in g.arguments {
-                files.push(path);
+            if let Some(bind_str) = g.options.get(&'b') {
+                let addr = bind_str.parse::<IpAddr>().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid bind address '{bind_str}': {e}"),
+                    )
+                })?;
+                bind = Some(addr)
             }
 
-            Ok((port, log, files))
+            let mut arguments = g.arguments.into_iter().peekable();
+            let command = match arguments.peek().and_then(|a| Command::parse(a)) {
+                Some(cmd) => {
+                    arguments.next();
+                    cmd
+                }
+                None => Command::Serve,
+            };
+
+            Ok(CliArgs {
+                command,
+                port,
+                log,
+                bind,
+                watch: g.options.contains_key(&'w'),
+                import_openapi: g.options.get(&'O').cloned(),
+                import_postman: g.options.get(&'P').cloned(),
+                files: arguments.collect(),
+            })
         }
         Err(e) => Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
     }
 }
+
+/// getopt3 knows nothing about long options, so converting them into short ones.
+/// Supports both `--bind 0.0.0.0` and `--bind=0.0.0.0` forms.
+fn translate_long_options(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            result.push(arg);
+            result.extend(args.by_ref());
+            break;
+        }
+
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+
+        match LONG_OPTIONS.iter().find(|(long, _)| *long == name) {
+            Some((_, short)) => {
+                result.push(short.to_string());
+                result.extend(value.map(str::to_string));
+            }
+            None => result.push(arg),
+        }
+    }
+
+    result
+}
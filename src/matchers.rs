@@ -6,14 +6,21 @@
 //!  - if matchers failed on deceit level, than next deceit will be handled
 //!  - if matchers failed on response level then next response will be handled
 //!  - if all matchers responses failed, than next deceit will be handled
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock, RwLock};
 
+use base64::Engine as _;
+use ipnet::IpNet;
 use jsonpath_rust::JsonPath as _;
+use regex::Regex;
 use rhai::{AST, Array, Engine, Scope};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     RequestContext, ResourceRef,
+    output::{decode_base64, decode_hex},
     rhai::{RhaiRequestContext, RhaiState},
 };
 
@@ -42,6 +49,31 @@ pub enum Matcher {
         #[serde(default)]
         negate: bool,
     },
+    /// Matches credentials from `Authorization: Basic <base64(username:password)>` header.
+    /// Missing or malformed header never matches.
+    BasicAuth {
+        username: String,
+        password: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches token from `Authorization: Bearer <token>` header.
+    /// Missing or malformed header never matches.
+    BearerToken {
+        token: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches client IP address against CIDR like `10.0.0.0/8` or a single IP.
+    /// Connection peer address is used by default (it could be a proxy one).
+    /// With `forwarded = true` the first `X-Forwarded-For` address wins when header is present.
+    RemoteIp {
+        cidr: String,
+        #[serde(default)]
+        forwarded: bool,
+        #[serde(default)]
+        negate: bool,
+    },
     /// Matches query string arguments
     QueryArg {
         name: String,
@@ -49,11 +81,56 @@ pub enum Matcher {
         #[serde(default)]
         negate: bool,
     },
+    /// Matches field of `application/x-www-form-urlencoded` request body.
+    /// Never matches bodies with other content types.
+    FormArg {
+        name: String,
+        value: String,
+        #[serde(default)]
+        negate: bool,
+    },
     /// Matching URI path arguments extracted using paths patterns like `/user/:user_id` etc.
+    ///
+    ///  - `value` value to compare with or a regular expression if `regex` is true
     PathArg {
         name: String,
         value: String,
         #[serde(default)]
+        regex: bool,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches whole request path (without path prefix) with a regular expression,
+    /// unlike [`Matcher::PathArg`] it does not depend on deceit URI patterns.
+    Path {
+        regex: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches request path exactly as it was received (before any URL decoding).
+    /// Allows to distinguish `/a%2Fb` from `/a/b`.
+    ///
+    ///  - `eq` value to compare with or a regular expression if `regex` is true
+    RawPath {
+        eq: String,
+        #[serde(default)]
+        regex: bool,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches request `Content-Type` header ignoring parameters like `; charset=utf-8`.
+    /// Supports wildcards like `application/*` or `*/*`.
+    ContentType {
+        eq: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches when request `Accept` header allows given media type.
+    /// Wildcards like `*/*` or `application/*` are supported, q-values are ignored.
+    /// Request without `Accept` header accepts anything.
+    Accepts {
+        media_type: String,
+        #[serde(default)]
         negate: bool,
     },
     /// Run match logic against request payload as JSON.
@@ -67,17 +144,116 @@ pub enum Matcher {
         #[serde(default)]
         negate: bool,
     },
+    /// Parse request payload as XML and evaluate XPath expression.
+    /// Body that is not a valid XML never matches.
+    ///
+    ///  - `path` XPath expression, e.g. `/Envelope/Body/GetUser/id`
+    ///  - `eq` value to compare with string value of expression result (first node for node sets)
+    Xpath {
+        path: String,
+        eq: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Same as [`Matcher::Json`] but using JMESPath expression (requires "jmespath" feature).
+    ///
+    ///  - `expr` JMESPath expression to evaluate against request JSON
+    ///  - `eq` string to compare with string result, other results are compared as JSON
+    Jmespath {
+        expr: String,
+        eq: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Compare raw request body with exact bytes given as HEX or Base64 string.
+    /// Value that can't be decoded never matches.
+    BodyBytes {
+        encoding: BinaryEncoding,
+        value: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Compare nesting depth of request JSON body, e.g. to reject deeply nested payloads.
+    /// Depth is computed while scanning the body, so it works beyond `serde_json` recursion limit.
+    /// Scalar body has depth 0, `{}` or `[]` has depth 1. Invalid JSON (including empty body) never matches.
+    JsonDepth {
+        op: CompareOp,
+        depth: usize,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Inline Rhai script, `args` are available in script scope same as for [`Matcher::RhaiRef`]
     Rhai {
         script: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        negate: bool,
     },
 
     RhaiRef {
         id: String,
         #[serde(default)]
         args: Vec<String>,
+        #[serde(default)]
+        negate: bool,
     },
 }
 
+/// Encoding of binary values in specs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryEncoding {
+    /// HEX string, could be split with spaces and newlines, lines could have `0x` prefix.
+    Hex,
+    Base64,
+}
+
+impl BinaryEncoding {
+    pub fn decode(self, value: &str) -> color_eyre::Result<Vec<u8>> {
+        match self {
+            BinaryEncoding::Hex => decode_hex(value),
+            BinaryEncoding::Base64 => decode_base64(value),
+        }
+    }
+}
+
+/// How a flat list of matchers is combined.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Every matcher must pass (AND).
+    #[default]
+    All,
+    /// At least one matcher must pass (OR).
+    Any,
+}
+
+/// Comparison operator for numeric matchers.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CompareOp {
+    pub fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            CompareOp::Eq => left == right,
+            CompareOp::Ne => left != right,
+            CompareOp::Gt => left > right,
+            CompareOp::Ge => left >= right,
+            CompareOp::Lt => left < right,
+            CompareOp::Le => left <= right,
+        }
+    }
+}
+
 impl Display for Matcher {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let value = match self {
@@ -85,9 +261,21 @@ impl Display for Matcher {
             Self::Or { .. } => "OR",
             Self::Method { .. } => "METHOD",
             Self::Header { .. } => "HEADER",
+            Self::ContentType { .. } => "CONTENT_TYPE",
+            Self::Accepts { .. } => "ACCEPTS",
+            Self::Path { .. } => "PATH",
+            Self::RawPath { .. } => "RAW_PATH",
             Self::PathArg { .. } => "PATH_ARG",
+            Self::BasicAuth { .. } => "BASIC_AUTH",
+            Self::BearerToken { .. } => "BEARER_TOKEN",
+            Self::RemoteIp { .. } => "REMOTE_IP",
             Self::QueryArg { .. } => "QUERY_ARG",
+            Self::FormArg { .. } => "FORM_ARG",
             Self::Json { .. } => "JSON",
+            Self::Xpath { .. } => "XPATH",
+            Self::Jmespath { .. } => "JMESPATH",
+            Self::BodyBytes { .. } => "BODY_BYTES",
+            Self::JsonDepth { .. } => "JSON_DEPTH",
             Self::Rhai { .. } => "RHAI",
             Self::RhaiRef { .. } => "RHAI_REF",
         };
@@ -111,6 +299,21 @@ pub fn matchers_and(
     true
 }
 
+/// Empty list always passes regardless of the mode.
+pub fn matchers_by_mode(
+    mode: MatchMode,
+    rref: &ResourceRef,
+    rhai: &RhaiState,
+    ctx: &RequestContext,
+    matchers: &[Matcher],
+) -> bool {
+    match mode {
+        _ if matchers.is_empty() => true,
+        MatchMode::All => matchers_and(rref, rhai, ctx, matchers),
+        MatchMode::Any => matchers_or(rref, rhai, ctx, matchers),
+    }
+}
+
 pub fn matchers_or(
     rref: &ResourceRef,
     rhai: &RhaiState,
@@ -140,20 +343,76 @@ pub fn is_matcher_approves(
             value,
             negate,
         } => flip_boolean(match_query_arg(name.as_str(), value.as_str(), ctx), *negate),
+        Matcher::FormArg {
+            name,
+            value,
+            negate,
+        } => flip_boolean(match_form_arg(name.as_str(), value.as_str(), ctx), *negate),
         Matcher::PathArg {
             name,
             value,
+            regex,
             negate,
-        } => flip_boolean(match_path_arg(name.as_str(), value.as_str(), ctx), *negate),
+        } => flip_boolean(
+            match_path_arg(name.as_str(), value.as_str(), *regex, ctx),
+            *negate,
+        ),
         Matcher::Method { eq, negate } => flip_boolean(match_method(eq.as_str(), ctx), *negate),
         Matcher::Header { key, value, negate } => {
             flip_boolean(match_header(key.as_str(), value.as_str(), ctx), *negate)
         }
+        Matcher::BasicAuth {
+            username,
+            password,
+            negate,
+        } => flip_boolean(
+            match_basic_auth(username.as_str(), password.as_str(), ctx),
+            *negate,
+        ),
+        Matcher::BearerToken { token, negate } => {
+            flip_boolean(match_bearer_token(token.as_str(), ctx), *negate)
+        }
+        Matcher::RemoteIp {
+            cidr,
+            forwarded,
+            negate,
+        } => flip_boolean(match_remote_ip(cidr.as_str(), *forwarded, ctx), *negate),
+        Matcher::Path { regex, negate } => flip_boolean(match_path(regex.as_str(), ctx), *negate),
+        Matcher::RawPath { eq, regex, negate } => {
+            flip_boolean(match_raw_path(eq.as_str(), *regex, ctx), *negate)
+        }
+        Matcher::ContentType { eq, negate } => {
+            flip_boolean(match_content_type(eq.as_str(), ctx), *negate)
+        }
+        Matcher::Accepts { media_type, negate } => {
+            flip_boolean(match_accepts(media_type.as_str(), ctx), *negate)
+        }
         Matcher::Json { path, eq, negate } => {
             flip_boolean(match_json(path.as_str(), eq.as_str(), ctx), *negate)
         }
-        Matcher::Rhai { script } => match_rhai(rhai, rref, script, ctx),
-        Matcher::RhaiRef { id, args } => match_rhai_ref(rhai, rref, id.as_str(), ctx, args.clone()),
+        Matcher::Xpath { path, eq, negate } => {
+            flip_boolean(match_xpath(path.as_str(), eq.as_str(), ctx), *negate)
+        }
+        Matcher::Jmespath { expr, eq, negate } => {
+            flip_boolean(match_jmespath(expr.as_str(), eq.as_str(), ctx), *negate)
+        }
+        Matcher::BodyBytes {
+            encoding,
+            value,
+            negate,
+        } => flip_boolean(match_body_bytes(*encoding, value.as_str(), ctx), *negate),
+        Matcher::JsonDepth { op, depth, negate } => {
+            flip_boolean(match_json_depth(*op, *depth, ctx), *negate)
+        }
+        Matcher::Rhai {
+            script,
+            args,
+            negate,
+        } => flip_boolean(match_rhai(rhai, rref, script, ctx, args.clone()), *negate),
+        Matcher::RhaiRef { id, args, negate } => flip_boolean(
+            match_rhai_ref(rhai, rref, id.as_str(), ctx, args.clone()),
+            *negate,
+        ),
         Matcher::And { matchers } => matchers_and(rref, rhai, ctx, matchers),
         Matcher::Or { matchers } => matchers_or(rref, rhai, ctx, matchers),
     };
@@ -167,11 +426,37 @@ fn flip_boolean(value: bool, negate: bool) -> bool {
     if negate { !value } else { value }
 }
 
-pub fn match_path_arg(name: &str, value: &str, ctx: &RequestContext) -> bool {
+pub fn match_path_arg(name: &str, value: &str, regex: bool, ctx: &RequestContext) -> bool {
     let Some(qvalue) = ctx.path_args.get(name) else {
         return false;
     };
-    value == *qvalue
+    if !regex {
+        return value == *qvalue;
+    }
+
+    match cached_regex(value) {
+        Ok(re) => re.is_match(qvalue),
+        Err(e) => {
+            log::error!("Invalid path arg \"{name}\" regex \"{value}\": {e}");
+            false
+        }
+    }
+}
+
+/// Regular expressions are cached by their source, so there is no need to invalidate them on specs change.
+pub(crate) fn cached_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    static REGEXES: LazyLock<RwLock<HashMap<String, Regex>>> = LazyLock::new(Default::default);
+
+    if let Some(re) = REGEXES.read().expect("RwLock failed").get(pattern) {
+        return Ok(re.clone());
+    }
+
+    let re = Regex::new(pattern)?;
+    REGEXES
+        .write()
+        .expect("RwLock failed")
+        .insert(pattern.to_string(), re.clone());
+    Ok(re)
 }
 
 pub fn match_query_arg(name: &str, value: &str, ctx: &RequestContext) -> bool {
@@ -181,6 +466,10 @@ pub fn match_query_arg(name: &str, value: &str, ctx: &RequestContext) -> bool {
     value == qvalue.as_str()
 }
 
+pub fn match_form_arg(name: &str, value: &str, ctx: &RequestContext) -> bool {
+    ctx.load_form().get(name).is_some_and(|v| v == value)
+}
+
 pub fn match_method(method: &str, ctx: &RequestContext) -> bool {
     method.to_uppercase().contains(&ctx.method)
 }
@@ -192,6 +481,120 @@ pub fn match_header(key: &str, value: &str, ctx: &RequestContext) -> bool {
     header_value.as_str() == value
 }
 
+pub fn match_basic_auth(username: &str, password: &str, ctx: &RequestContext) -> bool {
+    let Some(encoded) = authorization_credentials("basic", ctx) else {
+        return false;
+    };
+    let Ok(decoded) = base64::prelude::BASE64_STANDARD.decode(encoded) else {
+        return false;
+    };
+    let Ok(decoded) = String::from_utf8(decoded) else {
+        return false;
+    };
+
+    decoded.split_once(':') == Some((username, password))
+}
+
+pub fn match_bearer_token(token: &str, ctx: &RequestContext) -> bool {
+    authorization_credentials("bearer", ctx) == Some(token)
+}
+
+/// Credentials part of `Authorization` header if it uses expected scheme (case insensitive).
+fn authorization_credentials<'a>(scheme: &str, ctx: &'a RequestContext) -> Option<&'a str> {
+    let header = ctx.headers.get("authorization")?;
+    let (header_scheme, credentials) = header.trim().split_once(' ')?;
+    header_scheme
+        .eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
+}
+
+pub fn match_remote_ip(cidr: &str, forwarded: bool, ctx: &RequestContext) -> bool {
+    let net = match cidr.parse::<IpNet>() {
+        Ok(net) => net,
+        Err(_) => match cidr.parse::<IpAddr>() {
+            Ok(ip) => IpNet::from(ip),
+            Err(e) => {
+                log::error!("Invalid remote IP CIDR \"{cidr}\": {e}");
+                return false;
+            }
+        },
+    };
+
+    let forwarded_ip = if forwarded { ctx.forwarded_ip } else { None };
+
+    forwarded_ip
+        .or(ctx.peer_ip)
+        .is_some_and(|ip| net.contains(&ip.to_canonical()))
+}
+
+pub fn match_path(regex: &str, ctx: &RequestContext) -> bool {
+    match cached_regex(regex) {
+        Ok(re) => re.is_match(&ctx.request_path),
+        Err(e) => {
+            log::error!("Invalid path regex \"{regex}\": {e}");
+            false
+        }
+    }
+}
+
+pub fn match_raw_path(eq: &str, regex: bool, ctx: &RequestContext) -> bool {
+    if !regex {
+        return eq == ctx.raw_path.as_str();
+    }
+
+    match cached_regex(eq) {
+        Ok(re) => re.is_match(&ctx.raw_path),
+        Err(e) => {
+            log::error!("Invalid raw path regex \"{eq}\": {e}");
+            false
+        }
+    }
+}
+
+pub fn match_content_type(eq: &str, ctx: &RequestContext) -> bool {
+    let Some(content_type) = ctx.headers.get("content-type") else {
+        return false;
+    };
+    media_type_matches(eq, content_type)
+}
+
+pub fn match_accepts(media_type: &str, ctx: &RequestContext) -> bool {
+    let Some(accept) = ctx.headers.get("accept") else {
+        return true;
+    };
+    accepts_media_type(accept, media_type)
+}
+
+/// Check `Accept` header value like `text/html, application/*;q=0.8` against media type.
+/// Wildcards are allowed on both sides, so `application/*` is acceptable for `application/json`.
+pub fn accepts_media_type(accept: &str, media_type: &str) -> bool {
+    accept.split(',').any(|accepted| {
+        media_type_matches(accepted, media_type) || media_type_matches(media_type, accepted)
+    })
+}
+
+/// Check if media type matches pattern that could contain wildcards like `application/*`.
+/// Parameters like `; charset=utf-8` are ignored for both arguments.
+pub fn media_type_matches(pattern: &str, media_type: &str) -> bool {
+    let (Some((ptype, psubtype)), Some((mtype, msubtype))) =
+        (parse_media_type(pattern), parse_media_type(media_type))
+    else {
+        return false;
+    };
+
+    (ptype == "*" || ptype == mtype) && (psubtype == "*" || psubtype == msubtype)
+}
+
+fn parse_media_type(value: &str) -> Option<(String, String)> {
+    let essence = value.split(';').next()?.trim().to_ascii_lowercase();
+    let (tp, subtype) = essence.split_once('/')?;
+    let (tp, subtype) = (tp.trim(), subtype.trim());
+    if tp.is_empty() || subtype.is_empty() {
+        return None;
+    }
+    Some((tp.to_string(), subtype.to_string()))
+}
+
 pub fn match_json(path: &str, value: &str, ctx: &RequestContext) -> bool {
     let json = match ctx.load_body_as_json() {
         Ok(json) => json,
@@ -210,6 +613,240 @@ pub fn match_json(path: &str, value: &str, ctx: &RequestContext) -> bool {
     })
 }
 
+pub fn match_xpath(path: &str, value: &str, ctx: &RequestContext) -> bool {
+    let Ok(body) = std::str::from_utf8(&ctx.body) else {
+        log::error!("Can't parse request as XML, body is not a valid UTF-8");
+        return false;
+    };
+
+    let package = match sxd_document::parser::parse(body) {
+        Ok(package) => package,
+        Err(e) => {
+            log::error!("Can't parse request as XML {e:?}");
+            return false;
+        }
+    };
+
+    match sxd_xpath::evaluate_xpath(&package.as_document(), path) {
+        Ok(result) => result.string() == value,
+        Err(e) => {
+            log::error!("Can't evaluate XPath expression {path}: {e}");
+            false
+        }
+    }
+}
+
+pub fn match_body_bytes(encoding: BinaryEncoding, value: &str, ctx: &RequestContext) -> bool {
+    match cached_body_bytes(encoding, value) {
+        Ok(expected) => ctx.body.as_ref() == expected.as_slice(),
+        Err(e) => {
+            log::error!("Can't decode {encoding:?} body bytes matcher value: {e}");
+            false
+        }
+    }
+}
+
+/// Decoded values are cached by their source like [`cached_regex`] ones.
+fn cached_body_bytes(encoding: BinaryEncoding, value: &str) -> color_eyre::Result<Arc<Vec<u8>>> {
+    type BodyBytesCache = HashMap<(BinaryEncoding, String), Arc<Vec<u8>>>;
+    static BODY_BYTES: LazyLock<RwLock<BodyBytesCache>> = LazyLock::new(Default::default);
+
+    let key = (encoding, value.to_string());
+    if let Some(bytes) = BODY_BYTES.read().expect("RwLock failed").get(&key) {
+        return Ok(bytes.clone());
+    }
+
+    let bytes = Arc::new(encoding.decode(value)?);
+    BODY_BYTES
+        .write()
+        .expect("RwLock failed")
+        .insert(key, bytes.clone());
+    Ok(bytes)
+}
+
+pub fn match_json_depth(op: CompareOp, depth: usize, ctx: &RequestContext) -> bool {
+    match json_depth(&ctx.body) {
+        Some(body_depth) => op.compare(body_depth, depth),
+        None => {
+            log::error!("Can't compute JSON depth, request body is not a valid JSON");
+            false
+        }
+    }
+}
+
+/// What [`json_depth`] scanner expects as the next token.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JsonExpect {
+    /// Any value (document start, after `:` or after `,` in array)
+    Value,
+    /// Any value or `]` right after `[`
+    ValueOrClose,
+    /// Object key after `,`
+    Key,
+    /// Object key or `}` right after `{`
+    KeyOrClose,
+    /// `:` after object key
+    Colon,
+    /// `,` or closing bracket after a value, end of input at top level
+    Next,
+}
+
+/// Scan and validate JSON without building a value, so nesting depth is not limited by recursion.
+/// Strings and scalars are validated by `serde_json` one token at a time.
+/// Returns `None` if body is not a valid JSON.
+fn json_depth(body: &[u8]) -> Option<usize> {
+    use JsonExpect::*;
+
+    let mut stack = Vec::new();
+    let mut max_depth = 0;
+    let mut expect = Value;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let b = body[pos];
+        match b {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                pos += 1;
+                continue;
+            }
+            b'{' | b'[' => {
+                if !matches!(expect, Value | ValueOrClose) {
+                    return None;
+                }
+                stack.push(b);
+                max_depth = max_depth.max(stack.len());
+                expect = if b == b'{' { KeyOrClose } else { ValueOrClose };
+                pos += 1;
+            }
+            b'}' | b']' => {
+                let open = if b == b'}' { b'{' } else { b'[' };
+                let allowed = expect == Next
+                    || (b == b'}' && expect == KeyOrClose)
+                    || (b == b']' && expect == ValueOrClose);
+                if !allowed || stack.pop() != Some(open) {
+                    return None;
+                }
+                expect = Next;
+                pos += 1;
+            }
+            b':' => {
+                if expect != Colon {
+                    return None;
+                }
+                expect = Value;
+                pos += 1;
+            }
+            b',' => {
+                expect = match (expect, stack.last()) {
+                    (Next, Some(b'{')) => Key,
+                    (Next, Some(_)) => Value,
+                    _ => return None,
+                };
+                pos += 1;
+            }
+            b'"' => {
+                let end = json_string_end(body, pos)?;
+                serde_json::from_slice::<String>(&body[pos..end]).ok()?;
+                expect = match expect {
+                    Key | KeyOrClose => Colon,
+                    Value | ValueOrClose => Next,
+                    _ => return None,
+                };
+                pos = end;
+            }
+            _ => {
+                let end = body[pos..]
+                    .iter()
+                    .position(|c| b" \t\n\r,:]}[{\"".contains(c))
+                    .map_or(body.len(), |p| pos + p);
+                serde_json::from_slice::<serde_json::Value>(&body[pos..end]).ok()?;
+                if !matches!(expect, Value | ValueOrClose) {
+                    return None;
+                }
+                expect = Next;
+                pos = end;
+            }
+        }
+    }
+
+    (stack.is_empty() && expect == Next).then_some(max_depth)
+}
+
+/// Position right after the closing quote of a string starting at `start`.
+fn json_string_end(body: &[u8], start: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (i, &b) in body.iter().enumerate().skip(start + 1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(feature = "jmespath")]
+pub fn match_jmespath(expr: &str, eq: &str, ctx: &RequestContext) -> bool {
+    let expression = match jmespath_expression(expr) {
+        Ok(expression) => expression,
+        Err(e) => {
+            log::error!("Invalid JMESPath expression \"{expr}\": {e}");
+            return false;
+        }
+    };
+
+    let json = match ctx.load_body_as_json() {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Can't parse request as JSON {e}");
+            return false;
+        }
+    };
+
+    match expression.search(json.as_ref()) {
+        Ok(result) => {
+            if let Some(value) = result.as_string() {
+                return value == eq;
+            }
+            let Ok(expected) = serde_json::from_str::<serde_json::Value>(eq) else {
+                return false;
+            };
+            serde_json::to_value(&*result).is_ok_and(|value| value == expected)
+        }
+        Err(e) => {
+            log::error!("Can't evaluate JMESPath expression \"{expr}\": {e}");
+            false
+        }
+    }
+}
+
+#[cfg(not(feature = "jmespath"))]
+pub fn match_jmespath(expr: &str, _eq: &str, _ctx: &RequestContext) -> bool {
+    log::error!("JMESPath matcher \"{expr}\" requires \"jmespath\" feature");
+    false
+}
+
+/// Expressions are cached by their source, so there is no need to invalidate them on specs change.
+#[cfg(feature = "jmespath")]
+fn jmespath_expression(
+    expr: &str,
+) -> Result<Arc<jmespath::Expression<'static>>, jmespath::JmespathError> {
+    static EXPRESSIONS: LazyLock<RwLock<HashMap<String, Arc<jmespath::Expression<'static>>>>> =
+        LazyLock::new(Default::default);
+
+    if let Some(expression) = EXPRESSIONS.read().expect("RwLock failed").get(expr) {
+        return Ok(expression.clone());
+    }
+
+    let expression = Arc::new(jmespath::compile(expr)?);
+    EXPRESSIONS
+        .write()
+        .expect("RwLock failed")
+        .insert(expr.to_string(), expression.clone());
+    Ok(expression)
+}
+
 pub fn match_rhai_ref(
     rhai: &RhaiState,
     rref: &ResourceRef,
@@ -234,8 +871,9 @@ pub fn match_rhai(
     rref: &ResourceRef,
     script: &str,
     ctx: &RequestContext,
+    args: Vec<String>,
 ) -> bool {
-    let id = rref.to_resource_id("lua-matcher");
+    let id = rref.to_resource_id("rhai-matcher");
 
     let (engine, ast) = match rhai.get_exec(id.clone(), script) {
         Ok(a) => a,
@@ -244,7 +882,8 @@ pub fn match_rhai(
             return false;
         }
     };
-    call_rhai(&engine, &ast, ctx.clone().into(), Array::new())
+    let args = args.into_iter().map(Into::into).collect();
+    call_rhai(&engine, &ast, ctx.clone().into(), args)
 }
 
 fn call_rhai(engine: &Engine, ast: &AST, ctx: RhaiRequestContext, args: Array) -> bool {
@@ -260,3 +899,36 @@ fn call_rhai(engine: &Engine, ast: &AST, ctx: RhaiRequestContext, args: Array) -
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_type_with_params_and_wildcards() {
+        let ct = "application/json; charset=utf-8";
+        assert!(media_type_matches("application/json", ct));
+        assert!(media_type_matches("application/*", ct));
+        assert!(media_type_matches("*/*", ct));
+        assert!(media_type_matches("Application/JSON", ct));
+
+        assert!(!media_type_matches("application/xml", ct));
+        assert!(!media_type_matches("text/*", ct));
+        assert!(!media_type_matches("application/json", "garbage"));
+    }
+
+    #[test]
+    fn accept_header_negotiation() {
+        assert!(accepts_media_type("application/json", "application/json"));
+        assert!(accepts_media_type("application/json", "application/*"));
+        assert!(accepts_media_type(
+            "text/html, application/*;q=0.8",
+            "application/xml"
+        ));
+        assert!(accepts_media_type("*/*", "text/csv"));
+
+        assert!(!accepts_media_type("application/json", "application/xml"));
+        assert!(!accepts_media_type("text/*", "application/json"));
+        assert!(!accepts_media_type("", "application/json"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Bootstrap specs from an OpenAPI 3 document.
+//! Every path + method pair becomes a deceit that returns documented example response.
+
+use color_eyre::eyre::{bail, eyre};
+use serde_json::{Map, Value};
+
+use crate::{
+    ApateSpecs,
+    deceit::{DeceitBuilder, DeceitResponse, DeceitResponseBuilder},
+};
+
+/// HTTP methods that could be defined for OpenAPI path item.
+const OPERATIONS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Stop resolving nested `$ref` and schemas after this depth (recursive schemas).
+const MAX_DEPTH: usize = 16;
+
+/// Read OpenAPI 3 document (JSON or YAML) and convert it into apate specs.
+pub fn from_openapi(path: &str) -> color_eyre::Result<ApateSpecs> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Can't read OpenAPI document {path}. {e}"))?;
+
+    let doc = parse_document(path, &content)
+        .map_err(|e| eyre!("Can't parse OpenAPI document {path}. {e}"))?;
+
+    openapi_to_specs(&doc)
+}
+
+fn parse_document(path: &str, content: &str) -> color_eyre::Result<Value> {
+    if path.ends_with(".yaml") || path.ends_with(".yml") {
+        return Ok(serde_yaml::from_str(content)?);
+    }
+    Ok(serde_json::from_str(content)?)
+}
+
+/// Paths are sorted, so static paths go before templated ones with the same prefix.
+pub fn openapi_to_specs(doc: &Value) -> color_eyre::Result<ApateSpecs> {
+    if doc.get("swagger").is_some() {
+        bail!("Swagger 2.0 documents are not supported, OpenAPI 3 is expected");
+    }
+
+    let Some(paths) = doc.get("paths").and_then(Value::as_object) else {
+        bail!("OpenAPI document has no paths");
+    };
+
+    let mut specs = ApateSpecs::default();
+    for (path, item) in paths {
+        let item = resolve_ref(doc, item);
+        let uri = path_template_to_pattern(path);
+
+        for method in OPERATIONS {
+            let Some(operation) = item.get(*method) else {
+                continue;
+            };
+
+            let deceit = DeceitBuilder::with_uris(&[&uri])
+                .require_method(&method.to_uppercase())
+                .add_response(operation_response(doc, operation))
+                .build();
+            specs.deceit.push(deceit);
+        }
+    }
+
+    log::debug!("Imported {} deceit(s) from OpenAPI", specs.deceit.len());
+    Ok(specs)
+}
+
+/// OpenAPI and actix both use `{param}` placeholders,
+/// but parameter names are reduced to alphanumerics and `_` to be usable from templates.
+pub(crate) fn path_template_to_pattern(path: &str) -> String {
+    let mut pattern = String::with_capacity(path.len());
+    let mut in_param = false;
+
+    for c in path.chars() {
+        match c {
+            '{' => in_param = true,
+            '}' => in_param = false,
+            c if in_param && !(c.is_ascii_alphanumeric() || c == '_') => {
+                pattern.push('_');
+                continue;
+            }
+            _ => {}
+        }
+        pattern.push(c);
+    }
+
+    pattern
+}
+
+fn operation_response(doc: &Value, operation: &Value) -> DeceitResponse {
+    let Some((code, response)) = operation
+        .get("responses")
+        .and_then(Value::as_object)
+        .and_then(pick_response)
+    else {
+        return DeceitResponseBuilder::default().build();
+    };
+
+    let response = resolve_ref(doc, response);
+    let mut builder = DeceitResponseBuilder::default().code(code);
+
+    let content = response
+        .get("content")
+        .and_then(Value::as_object)
+        .and_then(pick_media_type);
+
+    if let Some((media_type, media)) = content {
+        builder = builder.add_header("Content-Type", media_type);
+        if let Some(example) = media_example(doc, media) {
+            builder = builder.with_output(&example_output(media_type, example));
+        }
+    }
+
+    builder.build()
+}
+
+/// Prefer the lowest success code, then the lowest documented one, then `default` as 200.
+fn pick_response(responses: &Map<String, Value>) -> Option<(u16, &Value)> {
+    let mut codes: Vec<(u16, &Value)> = responses
+        .iter()
+        .filter_map(|(code, response)| parse_status_code(code).map(|c| (c, response)))
+        .collect();
+    codes.sort_by_key(|(code, _)| *code);
+
+    codes
+        .iter()
+        .find(|(code, _)| (200..300).contains(code))
+        .or(codes.first())
+        .copied()
+        .or_else(|| responses.get("default").map(|r| (200, r)))
+}
+
+/// Supports exact codes and ranges like `2XX` (converted into 200).
+fn parse_status_code(code: &str) -> Option<u16> {
+    if let Ok(code) = code.parse() {
+        return Some(code);
+    }
+
+    let range = code
+        .strip_suffix("XX")
+        .or_else(|| code.strip_suffix("xx"))?;
+    range.parse::<u16>().ok().map(|r| r * 100)
+}
+
+fn pick_media_type(content: &Map<String, Value>) -> Option<(&str, &Value)> {
+    content
+        .iter()
+        .find(|(media_type, _)| media_type.contains("json"))
+        .or_else(|| content.iter().next())
+        .map(|(media_type, media)| (media_type.as_str(), media))
+}
+
+fn media_example(doc: &Value, media: &Value) -> Option<Value> {
+    if let Some(example) = media.get("example") {
+        return Some(example.clone());
+    }
+
+    let named = media
+        .get("examples")
+        .and_then(Value::as_object)
+        .and_then(|examples| examples.values().next())
+        .and_then(|example| resolve_ref(doc, example).get("value"));
+    if let Some(example) = named {
+        return Some(example.clone());
+    }
+
+    media
+        .get("schema")
+        .map(|schema| schema_example(doc, schema, 0))
+}
+
+/// Build example value from schema using declared examples, defaults or type placeholders.
+fn schema_example(doc: &Value, schema: &Value, depth: usize) -> Value {
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    let schema = resolve_ref(doc, schema);
+
+    if let Some(example) = schema.get("example").or_else(|| schema.get("default")) {
+        return example.clone();
+    }
+    if let Some(first) = schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .and_then(|e| e.first())
+    {
+        return first.clone();
+    }
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        let mut merged = Map::new();
+        for part in all_of {
+            match schema_example(doc, part, depth + 1) {
+                Value::Object(obj) => merged.extend(obj),
+                other if all_of.len() == 1 => return other,
+                _ => {}
+            }
+        }
+        return Value::Object(merged);
+    }
+    if let Some(first) = ["oneOf", "anyOf"]
+        .iter()
+        .find_map(|key| schema.get(*key).and_then(Value::as_array)?.first())
+    {
+        return schema_example(doc, first, depth + 1);
+    }
+
+    match schema_type(schema) {
+        Some("object") => {
+            let properties = schema
+                .get("properties")
+                .and_then(Value::as_object)
+                .map(|props| {
+                    props
+                        .iter()
+                        .map(|(name, prop)| (name.clone(), schema_example(doc, prop, depth + 1)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Value::Object(properties)
+        }
+        Some("array") => {
+            let item = schema
+                .get("items")
+                .map(|items| schema_example(doc, items, depth + 1))
+                .unwrap_or(Value::Null);
+            Value::Array(vec![item])
+        }
+        Some("string") => Value::String(string_example(schema).to_string()),
+        Some("integer") => Value::from(0),
+        Some("number") => Value::from(0.0),
+        Some("boolean") => Value::Bool(false),
+        _ => Value::Null,
+    }
+}
+
+/// Type could be a list in OpenAPI 3.1, first non null one is used.
+fn schema_type(schema: &Value) -> Option<&str> {
+    match schema.get("type") {
+        Some(Value::String(tp)) => Some(tp.as_str()),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|tp| *tp != "null"),
+        _ if schema.get("properties").is_some() => Some("object"),
+        _ => None,
+    }
+}
+
+fn string_example(schema: &Value) -> &'static str {
+    match schema.get("format").and_then(Value::as_str) {
+        Some("date-time") => "1970-01-01T00:00:00Z",
+        Some("date") => "1970-01-01",
+        Some("uuid") => "00000000-0000-0000-0000-000000000000",
+        Some("email") => "user@example.com",
+        Some("uri") | Some("url") => "https://example.com",
+        _ => "string",
+    }
+}
+
+/// Non JSON media types get string examples as is.
+fn example_output(media_type: &str, example: Value) -> String {
+    match example {
+        Value::String(s) if !media_type.contains("json") => s,
+        other => serde_json::to_string_pretty(&other).unwrap_or_default(),
+    }
+}
+
+/// Follow local `$ref` links like `#/components/schemas/Pet`.
+/// External references are not supported and returned as is.
+fn resolve_ref<'a>(doc: &'a Value, mut value: &'a Value) -> &'a Value {
+    for _ in 0..MAX_DEPTH {
+        let Some(pointer) = value
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix('#'))
+        else {
+            break;
+        };
+
+        match doc.pointer(pointer) {
+            Some(target) => value = target,
+            None => {
+                log::warn!("Can't resolve OpenAPI reference #{pointer}");
+                break;
+            }
+        }
+    }
+    value
+}
@@ -1,8 +1,12 @@
 //! This module responsibility is to build HTTP response message body
-use std::sync::atomic::Ordering;
+use std::sync::{LazyLock, atomic::Ordering};
 
+use actix_web::web::Bytes;
 use base64::Engine as _;
 use color_eyre::eyre::{bail, eyre};
+use futures::channel::mpsc::{UnboundedReceiver, unbounded};
+use jsonpath_rust::JsonPath as _;
+use regex::{Captures, Regex};
 use rhai::{AST, Array, Blob, Dynamic, Engine, Scope};
 use serde::{Deserialize, Serialize};
 
@@ -10,7 +14,7 @@ use crate::{
     ResourceRef,
     deceit::DeceitResponseContext,
     jinja::{MiniJinjaState, build_tpl_context},
-    rhai::{RhaiResponseContext, RhaiState},
+    rhai::{RhaiResponseContext, RhaiState, with_stream_sender},
 };
 
 /// Define an approach how to handle `output` property from configuration.
@@ -23,13 +27,46 @@ pub enum OutputType {
     String,
     /// Handle output as minijinja template.
     Jinja,
-    /// Handle output as binary data that will be decoded from HEX string (no 0x prefix expected).
+    /// Handle output as binary data that will be decoded from HEX string.
+    /// Whitespace between digits is ignored and every line may start with `0x` prefix.
     Hex,
     // #[serde(rename = "base64")]
     /// Handle output as binary data that will be decoded from Base64 string.
     Base64,
+    /// Render output as minijinja template first, then decode result from HEX string.
+    JinjaHex,
+    /// Render output as minijinja template first, then decode result from Base64 string.
+    JinjaBase64,
+    /// Handle output as minijinja template that renders JSON array of rows (arrays of values).
+    /// Rows are written as CSV with proper quoting, first row is usually a header.
+    Csv,
+    /// Handle output as JSON string that will be converted into CBOR binary data.
+    Cbor,
+    /// Handle output as JSON string that will be converted into MessagePack binary data.
+    #[serde(rename = "msgpack")]
+    MsgPack,
+    /// Return output string as is with XML content type (e.g. for SOAP mocks).
+    Xml,
+    /// Handle output as JSON document which string values could have placeholders like
+    /// `${path_args.id}`, `${query.foo}` or `${json:$.user.name}` (JSONPath over request body).
+    JsonTemplate,
+    /// Output is a URL, response body is fetched from it on first use and cached in memory
+    /// (requires "remote-specs" feature).
+    RemoteCached,
+    /// Return request body as is, output is ignored.
+    /// Request headers are returned too if response has `echo_headers` flag.
+    Echo,
+    /// Upgrade connection to WebSocket (requires "websocket" feature).
+    /// Empty output echoes client messages, otherwise it is a JSON array of messages
+    /// sent to the client one by one before closing connection.
+    #[serde(rename = "websocket")]
+    WebSocket,
     /// Output is a Rhai script
     Rhai,
+    /// Output is a Rhai script that streams response body chunks with `emit(blob)` calls.
+    /// Chunks are sent to the client as soon as they are produced.
+    /// Processors are not applied to streamed responses.
+    RhaiStream,
 
     // The output script will be reused from a global registry.
     RhaiRef {
@@ -39,29 +76,135 @@ pub enum OutputType {
     },
 }
 
+impl OutputType {
+    /// Content type that is added to response unless specs define another one.
+    pub fn default_content_type(&self) -> Option<&'static str> {
+        match self {
+            OutputType::Csv => Some("text/csv"),
+            OutputType::Cbor => Some("application/cbor"),
+            OutputType::MsgPack => Some("application/msgpack"),
+            OutputType::Xml => Some("application/xml"),
+            OutputType::JsonTemplate => Some("application/json"),
+            _ => None,
+        }
+    }
+}
+
 pub fn output_response_body(
     deceit_ref: &ResourceRef,
     tp: &OutputType,
     output: &str,
+    json_body: Option<&serde_json::Value>,
     ctx: &DeceitResponseContext,
     mini_jinja_state: &MiniJinjaState,
     rhai_state: &RhaiState,
 ) -> color_eyre::Result<Vec<u8>> {
+    if let Some(json) = json_body {
+        return Ok(serde_json::to_vec(json)?);
+    }
+
     match tp {
-        OutputType::String => Ok(output.as_bytes().to_vec()),
+        OutputType::String | OutputType::Xml => Ok(output.as_bytes().to_vec()),
         OutputType::Jinja => render_using_minijinja(deceit_ref, output, ctx, mini_jinja_state),
-        OutputType::Hex => {
-            let hex_str = output.trim().strip_prefix("0x").unwrap_or(output).trim();
-            Ok(hex::decode(hex_str)?)
+        OutputType::Hex => decode_hex(output),
+        OutputType::Base64 => decode_base64(output),
+        OutputType::JinjaHex => {
+            let rendered = render_using_minijinja(deceit_ref, output, ctx, mini_jinja_state)?;
+            decode_hex(&String::from_utf8(rendered)?)
+        }
+        OutputType::JinjaBase64 => {
+            let rendered = render_using_minijinja(deceit_ref, output, ctx, mini_jinja_state)?;
+            decode_base64(&String::from_utf8(rendered)?)
+        }
+        OutputType::Csv => {
+            let rows = render_using_minijinja(deceit_ref, output, ctx, mini_jinja_state)?;
+            json_rows_to_csv(&rows)
+        }
+        OutputType::Cbor => {
+            let json: serde_json::Value = serde_json::from_str(output)
+                .map_err(|e| eyre!("CBOR output must be a valid JSON: {e}"))?;
+            let mut cbor = Vec::new();
+            ciborium::into_writer(&json, &mut cbor)?;
+            Ok(cbor)
         }
-        OutputType::Base64 => Ok(base64::prelude::BASE64_STANDARD.decode(output.trim())?),
+        OutputType::MsgPack => {
+            let json: serde_json::Value = serde_json::from_str(output)
+                .map_err(|e| eyre!("MessagePack output must be a valid JSON: {e}"))?;
+            Ok(rmp_serde::to_vec(&json)?)
+        }
+        OutputType::JsonTemplate => render_json_template(output, ctx),
+        OutputType::Echo => Ok(ctx.req.body.to_vec()),
         OutputType::Rhai => render_using_rhai(deceit_ref, output, ctx, rhai_state),
+        OutputType::RhaiStream => bail!("Streaming output can't be rendered into a single body"),
+        OutputType::WebSocket => bail!("WebSocket output can't be rendered into a body"),
+        OutputType::RemoteCached => bail!("Remote output must be fetched by the handler"),
         OutputType::RhaiRef { id, args } => {
             render_using_rhai_ref(deceit_ref, id, args.clone(), ctx, rhai_state)
         }
     }
 }
 
+/// Run streaming Rhai script in a blocking thread.
+/// Returns receiver for the chunks produced by `emit(blob)` calls.
+/// The receiver is closed when script execution is finished.
+pub fn output_response_stream(
+    deceit_ref: &ResourceRef,
+    output: &str,
+    ctx: &DeceitResponseContext,
+    rhai: &RhaiState,
+) -> color_eyre::Result<UnboundedReceiver<Bytes>> {
+    let id = deceit_ref.to_resource_id("rhai-stream-output");
+
+    let (engine, ast) = rhai
+        .get_exec(id, output)
+        .map_err(|e| eyre!("Can't load Rhai stream script: {e:?}"))?;
+
+    let (sender, receiver) = unbounded();
+    let rctx: RhaiResponseContext = ctx.clone().into();
+    let rref = deceit_ref.clone();
+
+    actix_web::rt::task::spawn_blocking(move || {
+        with_stream_sender(sender, || {
+            let mut scope = Scope::new();
+            scope.set_value("ctx", rctx);
+            scope.set_value("args", Array::new());
+
+            if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+                log::error!("Rhai stream script failed for deceit:{rref} {e:?}");
+            }
+        })
+    });
+
+    Ok(receiver)
+}
+
+/// Render redirect location that could be a minijinja template.
+pub fn output_redirect_location(
+    rref: &ResourceRef,
+    location: &str,
+    ctx: &DeceitResponseContext,
+    mini_jinja_state: &MiniJinjaState,
+) -> color_eyre::Result<String> {
+    let id = rref.to_resource_id("jinja-redirect");
+    let rendered = render_minijinja_template(&id, location, ctx, mini_jinja_state)?;
+    Ok(String::from_utf8(rendered)?.trim().to_string())
+}
+
+/// Render response code from minijinja template.
+pub fn output_response_code(
+    rref: &ResourceRef,
+    template: &str,
+    ctx: &DeceitResponseContext,
+    mini_jinja_state: &MiniJinjaState,
+) -> color_eyre::Result<u16> {
+    let id = rref.to_resource_id("jinja-code");
+    let rendered = render_minijinja_template(&id, template, ctx, mini_jinja_state)?;
+    let code = String::from_utf8(rendered)?;
+    code.trim()
+        .parse()
+        .map_err(|e| eyre!("Response code template rendered invalid code \"{code}\": {e}"))
+}
+
 fn render_using_minijinja(
     deceit_ref: &ResourceRef,
     template: &str,
@@ -74,7 +217,16 @@ fn render_using_minijinja(
     // env.add_template(&tpl_id, template)?;
 
     let id = deceit_ref.to_resource_id("jinja-output");
-    mini_jinja_state.add_minijinja_template(&id, template)?;
+    render_minijinja_template(&id, template, ctx, mini_jinja_state)
+}
+
+pub(crate) fn render_minijinja_template(
+    id: &str,
+    template: &str,
+    ctx: &DeceitResponseContext,
+    mini_jinja_state: &MiniJinjaState,
+) -> color_eyre::Result<Vec<u8>> {
+    mini_jinja_state.add_minijinja_template(id, template)?;
     let mut env = mini_jinja_state.get_minijinja();
 
     let force_response_code = ctx.response_code.clone();
@@ -82,7 +234,7 @@ fn render_using_minijinja(
         force_response_code.store(code, Ordering::Relaxed);
     });
 
-    let tpl = env.get_template(&id)?;
+    let tpl = env.get_template(id)?;
     let jinja_ctx = build_tpl_context(ctx.clone());
     let response = tpl
         .render(jinja_ctx)
@@ -91,6 +243,129 @@ fn render_using_minijinja(
     Ok(response.into_bytes())
 }
 
+/// Whitespace is ignored, so long HEX blobs could be split into lines.
+/// Each line could have its own `0x` prefix.
+pub(crate) fn decode_hex(output: &str) -> color_eyre::Result<Vec<u8>> {
+    let mut hex_str = String::with_capacity(output.len());
+
+    for (line_idx, line) in output.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let prefix_len = if trimmed.starts_with("0x") { 2 } else { 0 };
+        let start = line.len() - trimmed.len() + prefix_len;
+
+        for (col, c) in line[start..].char_indices() {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            if !c.is_ascii_hexdigit() {
+                bail!(
+                    "Invalid HEX character {c:?} at line {} column {}",
+                    line_idx + 1,
+                    start + col + 1
+                );
+            }
+            hex_str.push(c);
+        }
+    }
+
+    Ok(hex::decode(hex_str)?)
+}
+
+pub(crate) fn decode_base64(output: &str) -> color_eyre::Result<Vec<u8>> {
+    Ok(base64::prelude::BASE64_STANDARD.decode(output.trim())?)
+}
+
+/// Convert JSON array of rows into CSV.
+/// Nested arrays & objects are written as JSON strings, nulls as empty fields.
+fn json_rows_to_csv(json: &[u8]) -> color_eyre::Result<Vec<u8>> {
+    let rows: Vec<Vec<serde_json::Value>> = serde_json::from_slice(json)
+        .map_err(|e| eyre!("CSV template must render JSON array of arrays: {e}"))?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+
+    for row in rows {
+        let fields = row.into_iter().map(|v| match v {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        });
+        writer.write_record(fields)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| eyre!("Can't write CSV: {e}"))
+}
+
+/// Placeholder in JSON template string values, see [`OutputType::JsonTemplate`].
+static JSON_PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$\{(?:path_args\.([^}]+)|query\.([^}]+)|json:([^}]+))\}")
+        .expect("JSON placeholder regex")
+});
+
+fn render_json_template(output: &str, ctx: &DeceitResponseContext) -> color_eyre::Result<Vec<u8>> {
+    let mut json: serde_json::Value = serde_json::from_str(output)
+        .map_err(|e| eyre!("JSON template output must be a valid JSON: {e}"))?;
+    substitute_placeholders(&mut json, ctx);
+    Ok(serde_json::to_vec(&json)?)
+}
+
+/// String that is a single placeholder is replaced with the value itself (keeps JSON type),
+/// otherwise placeholders are interpolated into the string. Missing values become null or empty.
+fn substitute_placeholders(json: &mut serde_json::Value, ctx: &DeceitResponseContext) {
+    match json {
+        serde_json::Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| substitute_placeholders(item, ctx)),
+        serde_json::Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| substitute_placeholders(field, ctx)),
+        serde_json::Value::String(text) => {
+            if let Some(caps) = JSON_PLACEHOLDER.captures(text)
+                && caps[0].len() == text.len()
+            {
+                *json = placeholder_value(&caps, ctx).unwrap_or_default();
+                return;
+            }
+
+            let replaced = JSON_PLACEHOLDER.replace_all(text, |caps: &Captures| {
+                match placeholder_value(caps, ctx) {
+                    Some(serde_json::Value::String(value)) => value,
+                    Some(value) => value.to_string(),
+                    None => String::new(),
+                }
+            });
+            *text = replaced.into_owned();
+        }
+        _ => {}
+    }
+}
+
+fn placeholder_value(caps: &Captures, ctx: &DeceitResponseContext) -> Option<serde_json::Value> {
+    let req = &ctx.req;
+    if let Some(name) = caps.get(1) {
+        return req.path_args.get(name.as_str()).cloned().map(Into::into);
+    }
+    if let Some(name) = caps.get(2) {
+        return req.query_args.get(name.as_str()).cloned().map(Into::into);
+    }
+
+    let path = caps.get(3)?.as_str();
+    let body = req
+        .load_body_as_json()
+        .inspect_err(|e| log::error!("Can't parse request as JSON {e}"))
+        .ok()?;
+    match body.query(path) {
+        Ok(found) => found.first().map(|v| (*v).clone()),
+        Err(e) => {
+            log::error!("Invalid JSONPath {path} in JSON template: {e}");
+            None
+        }
+    }
+}
+
 fn render_using_rhai_ref(
     rref: &ResourceRef,
     script_id: &str,
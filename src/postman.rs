@@ -0,0 +1,198 @@
+//! Bootstrap specs from a Postman v2.1 collection.
+//! Every request becomes a deceit, saved example responses become its responses.
+
+use color_eyre::eyre::{bail, eyre};
+use serde_json::Value;
+
+use crate::{
+    ApateSpecs,
+    deceit::{DeceitBuilder, DeceitResponse, DeceitResponseBuilder},
+    openapi::path_template_to_pattern,
+};
+
+/// Saved response headers that do not describe mock body (it is stored decoded & unchunked).
+const SKIP_RESPONSE_HEADERS: &[&str] = &["content-length", "content-encoding", "transfer-encoding"];
+
+/// Read Postman v2.1 collection JSON and convert it into apate specs.
+pub fn from_postman_collection(path: &str) -> color_eyre::Result<ApateSpecs> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Can't read Postman collection {path}. {e}"))?;
+
+    let collection: Value = serde_json::from_str(&content)
+        .map_err(|e| eyre!("Can't parse Postman collection {path}. {e}"))?;
+
+    postman_to_specs(&collection)
+}
+
+/// Folders are flattened, requests keep collection order.
+pub fn postman_to_specs(collection: &Value) -> color_eyre::Result<ApateSpecs> {
+    let Some(items) = collection.get("item").and_then(Value::as_array) else {
+        bail!("Postman collection has no items");
+    };
+
+    let mut specs = ApateSpecs::default();
+    collect_items(items, &mut specs);
+
+    log::debug!(
+        "Imported {} deceit(s) from Postman collection",
+        specs.deceit.len()
+    );
+    Ok(specs)
+}
+
+fn collect_items(items: &[Value], specs: &mut ApateSpecs) {
+    for item in items {
+        if let Some(folder) = item.get("item").and_then(Value::as_array) {
+            collect_items(folder, specs);
+            continue;
+        }
+
+        let Some(request) = item.get("request") else {
+            continue;
+        };
+
+        let (uri, _) = request_path_and_query(request);
+        let method = request
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or("GET")
+            .to_uppercase();
+
+        let mut builder = DeceitBuilder::with_uris(&[&uri]).require_method(&method);
+        for (key, value) in key_values(request.get("header")) {
+            if !is_templated(&value) {
+                builder = builder.require_header(&key.to_lowercase(), &value);
+            }
+        }
+
+        let mut responses: Vec<(usize, DeceitResponse)> = item
+            .get("response")
+            .and_then(Value::as_array)
+            .map(|saved| saved.iter().map(saved_response).collect())
+            .unwrap_or_default();
+
+        // Responses that require more query args must be checked first.
+        responses.sort_by_key(|(query_args, _)| std::cmp::Reverse(*query_args));
+        let responses: Vec<DeceitResponse> = responses.into_iter().map(|(_, r)| r).collect();
+
+        builder = if responses.is_empty() {
+            builder.add_response(DeceitResponseBuilder::default().build())
+        } else {
+            builder.with_responses(responses)
+        };
+
+        specs.deceit.push(builder.build());
+    }
+}
+
+/// Returns response with number of query arg matchers from the original request.
+fn saved_response(saved: &Value) -> (usize, DeceitResponse) {
+    let mut builder = DeceitResponseBuilder::default();
+
+    if let Some(code) = saved.get("code").and_then(Value::as_u64) {
+        builder = builder.code(code as u16);
+    }
+
+    for (key, value) in key_values(saved.get("header")) {
+        if !SKIP_RESPONSE_HEADERS.contains(&key.to_lowercase().as_str()) {
+            builder = builder.add_header(&key, &value);
+        }
+    }
+
+    if let Some(body) = saved.get("body").and_then(Value::as_str) {
+        builder = builder.with_output(body);
+    }
+
+    let mut query_args = 0;
+    if let Some(original) = saved.get("originalRequest") {
+        for (name, value) in request_path_and_query(original).1 {
+            if !is_templated(&value) {
+                builder = builder.require_query_arg(&name, &value);
+                query_args += 1;
+            }
+        }
+    }
+
+    (query_args, builder.build())
+}
+
+/// URL could be a raw string or an object with `path` segments and `query` list.
+fn request_path_and_query(request: &Value) -> (String, Vec<(String, String)>) {
+    match request.get("url") {
+        Some(Value::Object(url)) => {
+            let path = match url.get("path") {
+                Some(Value::Array(segments)) => segments
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                Some(Value::String(path)) => path.clone(),
+                _ => raw_path_and_query(url.get("raw").and_then(Value::as_str).unwrap_or("")).0,
+            };
+            (to_pattern(&path), key_values(url.get("query")))
+        }
+        Some(Value::String(raw)) => {
+            let (path, query) = raw_path_and_query(raw);
+            (to_pattern(&path), query)
+        }
+        _ => ("/".to_string(), Vec::new()),
+    }
+}
+
+/// Cut scheme & host (or `{{baseUrl}}` like variable) and split query arguments.
+fn raw_path_and_query(raw: &str) -> (String, Vec<(String, String)>) {
+    let raw = raw.split('#').next().unwrap_or_default();
+    let (url, query) = raw.split_once('?').unwrap_or((raw, ""));
+
+    let without_host = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map(|(_, p)| p).unwrap_or_default(),
+        None if url.starts_with("{{") => url.split_once('/').map(|(_, p)| p).unwrap_or_default(),
+        None => url,
+    };
+
+    let query = serde_urlencoded::from_str(query).unwrap_or_default();
+    (without_host.to_string(), query)
+}
+
+/// Postman `:id` and `{{id}}` path variables are converted into `{id}` patterns.
+fn to_pattern(path: &str) -> String {
+    let segments: Vec<String> = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                format!("{{{name}}}")
+            } else if let Some(name) = segment
+                .strip_prefix("{{")
+                .and_then(|s| s.strip_suffix("}}"))
+            {
+                format!("{{{name}}}")
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect();
+
+    path_template_to_pattern(&format!("/{}", segments.join("/")))
+}
+
+/// Read enabled `[{"key": .., "value": ..}]` pairs used for headers and query arguments.
+fn key_values(list: Option<&Value>) -> Vec<(String, String)> {
+    let Some(list) = list.and_then(Value::as_array) else {
+        return Vec::new();
+    };
+
+    list.iter()
+        .filter(|kv| !kv.get("disabled").and_then(Value::as_bool).unwrap_or(false))
+        .filter_map(|kv| {
+            let key = kv.get("key")?.as_str()?;
+            let value = kv.get("value").and_then(Value::as_str).unwrap_or_default();
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Values with Postman variables could not be matched literally.
+fn is_templated(value: &str) -> bool {
+    value.contains("{{")
+}
@@ -1,12 +1,22 @@
-use std::{collections::HashMap, fmt::Debug};
+#[cfg(feature = "jsonschema")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use std::{fmt::Debug, sync::atomic::Ordering};
 
+use actix_web::HttpResponse;
+use base64::Engine as _;
 use color_eyre::eyre::{bail, eyre};
+use ed25519_dalek::{Signer as _, SigningKey};
 use rhai::{AST, Array, Blob, Dynamic, Engine, Scope};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    ResourceRef,
+    ApateState, RequestContext, ResourceRef,
     deceit::DeceitResponseContext,
+    matchers::cached_regex,
+    output::render_minijinja_template,
     rhai::{RhaiResponseContext, RhaiState},
 };
 
@@ -20,6 +30,16 @@ pub trait PostProcessor: Sync + Send {
     ) -> Result<Option<Vec<u8>>, Box<dyn core::error::Error>>;
 }
 
+/// Trait for custom user-defined logic to run on incoming request before deceits matching.
+pub trait PreProcessor: Sync + Send {
+    /// Request context could be rewritten in place.
+    /// Returned response is sent to the client as is, deceits are not matched then.
+    fn process(
+        &self,
+        context: &mut RequestContext,
+    ) -> Result<Option<HttpResponse>, Box<dyn core::error::Error>>;
+}
+
 /// Custom logic to execute after output content was prepared (rendered).
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -32,6 +52,46 @@ pub enum Processor {
         #[serde(default)]
         args: Vec<String>,
     },
+    /// Adds weak `ETag` header computed from response body.
+    /// Responds with 304 and empty body when request `If-None-Match` matches it.
+    /// Processors after this one are skipped for 304 responses.
+    #[serde(rename = "etag")]
+    ETag,
+    /// Validate response body against JSON Schema (requires "jsonschema" feature).
+    /// On failure response is replaced with 500 and validation errors.
+    /// Processors after this one are skipped on failure.
+    ValidateResponse {
+        schema: serde_json::Value,
+    },
+    /// Sign response body with Ed25519 key given as HEX encoded 32 bytes seed.
+    /// Base64 encoded signature is added as a response header with given name.
+    /// Body is not changed, so it should go after processors that modify it.
+    Sign {
+        key_hex: String,
+        header: String,
+    },
+    /// Copy request headers with given names into response, missing ones are skipped.
+    ForwardHeaders {
+        headers: Vec<String>,
+    },
+    /// Replace all occurrences of `pattern` in response body.
+    /// In regex mode replacement could reference capture groups like `$1` or `${name}`.
+    Replace {
+        pattern: String,
+        replacement: String,
+        #[serde(default)]
+        regex: bool,
+    },
+    /// Apply RFC 7386 JSON merge patch to response body, `null` values remove keys.
+    /// Patch is a minijinja template rendered before applying.
+    JsonMergePatch {
+        patch: String,
+    },
+    /// Convert response body from one format into another.
+    Transcode {
+        from: Format,
+        to: Format,
+    },
     /// References to custom embedded rust user processor.
     Embedded {
         /// Processor with this ID should be added on server initialization.
@@ -43,6 +103,15 @@ pub enum Processor {
     },
 }
 
+/// Structured body format for [`Processor::Transcode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
 pub struct ApateProcessor {
     pub id: String,
     pub post: Box<dyn PostProcessor>,
@@ -69,6 +138,50 @@ impl ApateProcessor {
     }
 }
 
+/// Request pre processor, all registered ones run in registration order.
+pub struct ApatePreProcessor {
+    pub id: String,
+    pub pre: Box<dyn PreProcessor>,
+}
+
+impl ApatePreProcessor {
+    /// Creates pre processor.
+    pub fn pre(id: &str, callback: Box<dyn PreProcessor>) -> Self {
+        Self {
+            id: id.to_string(),
+            pre: callback,
+        }
+    }
+
+    pub fn apply_pre(&self, ctx: &mut RequestContext) -> color_eyre::Result<Option<HttpResponse>> {
+        (*self.pre)
+            .process(ctx)
+            .map_err(|e| eyre!("Pre processor {} execution failed: {e}", self.id))
+    }
+}
+
+impl Debug for ApatePreProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApatePreProcessor")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// Stops on the first pre processor that returns early response.
+pub(crate) fn apply_pre_processors(
+    pre_processors: &[ApatePreProcessor],
+    ctx: &mut RequestContext,
+) -> color_eyre::Result<Option<HttpResponse>> {
+    for p in pre_processors {
+        if let Some(response) = p.apply_pre(ctx)? {
+            log::debug!("Pre processor {} answered early", p.id);
+            return Ok(Some(response));
+        }
+    }
+    Ok(None)
+}
+
 impl Debug for ApateProcessor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ApateProcessor")
@@ -79,12 +192,14 @@ impl Debug for ApateProcessor {
 
 pub(crate) fn apply_processors(
     rref: &ResourceRef,
-    custom_registry: &HashMap<String, ApateProcessor>,
+    state: &ApateState,
     processors: &[&Processor],
     rctx: &DeceitResponseContext,
     body: &[u8],
-    rhai: &RhaiState,
 ) -> color_eyre::Result<Option<Vec<u8>>> {
+    let custom_registry = &state.processors;
+    let rhai = &state.rhai;
+    let minijinja = &state.minijinja;
     let mut result: Option<Vec<u8>> = None;
 
     for (pid, p) in processors.iter().enumerate() {
@@ -117,6 +232,46 @@ pub(crate) fn apply_processors(
                     result = Some(new_body)
                 }
             }
+            Processor::ETag => {
+                if apply_etag(rctx, input_bytes) {
+                    return Ok(Some(Vec::new()));
+                }
+            }
+            Processor::Sign { key_hex, header } => {
+                let signature = sign_ed25519(key_hex, input_bytes)?;
+                rctx.add_response_header(header, &signature);
+            }
+            Processor::ValidateResponse { schema } => {
+                let validator_id = processor_ref.to_resource_id("schema-processor");
+                if let Some(errors) =
+                    validate_response(&state.schemas, &validator_id, schema, input_bytes)?
+                {
+                    rctx.response_code.store(500, Ordering::Relaxed);
+                    return Ok(Some(errors));
+                }
+            }
+            Processor::ForwardHeaders { headers } => {
+                for name in headers {
+                    if let Some(value) = rctx.req.headers.get(&name.to_ascii_lowercase()) {
+                        rctx.add_response_header(name, value);
+                    }
+                }
+            }
+            Processor::Replace {
+                pattern,
+                replacement,
+                regex,
+            } => {
+                result = Some(replace(pattern, replacement, *regex, input_bytes)?);
+            }
+            Processor::JsonMergePatch { patch } => {
+                let id = processor_ref.to_resource_id("jinja-processor");
+                let patch = render_minijinja_template(&id, patch, rctx, minijinja)?;
+                result = Some(apply_merge_patch(input_bytes, &patch)?);
+            }
+            Processor::Transcode { from, to } => {
+                result = Some(transcode(*from, *to, input_bytes)?);
+            }
             Processor::RhaiRef { id, args } => {
                 if let Some(new_body) = apply_rhai_ref(
                     rhai,
@@ -135,6 +290,197 @@ pub(crate) fn apply_processors(
     Ok(result)
 }
 
+/// Returns true if response must be replaced with 304 Not Modified.
+fn apply_etag(rctx: &DeceitResponseContext, body: &[u8]) -> bool {
+    let etag = format!("W/\"{:x}-{:08x}\"", body.len(), crc32fast::hash(body));
+    rctx.add_response_header("etag", &etag);
+
+    let Some(if_none_match) = rctx.req.headers.get("if-none-match") else {
+        return false;
+    };
+
+    if etag_matches(if_none_match, &etag) {
+        rctx.response_code.store(304, Ordering::Relaxed);
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns Base64 encoded signature.
+fn sign_ed25519(key_hex: &str, body: &[u8]) -> color_eyre::Result<String> {
+    let seed: [u8; 32] = hex::decode(key_hex.trim())
+        .map_err(|e| eyre!("Invalid Ed25519 key HEX: {e}"))?
+        .try_into()
+        .map_err(|_| eyre!("Ed25519 key must be a 32 bytes seed"))?;
+
+    let signature = SigningKey::from_bytes(&seed).sign(body);
+    Ok(base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()))
+}
+
+/// Weak comparison of `If-None-Match` header value against ETag.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    fn opaque(tag: &str) -> &str {
+        let tag = tag.trim();
+        tag.strip_prefix("W/").unwrap_or(tag)
+    }
+
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// Returns validation errors as a new response body if validation failed.
+#[cfg(feature = "jsonschema")]
+fn validate_response(
+    schemas: &SchemaValidators,
+    id: &str,
+    schema: &serde_json::Value,
+    body: &[u8],
+) -> color_eyre::Result<Option<Vec<u8>>> {
+    let validator = schemas.get_or_compile(id, schema)?;
+
+    let errors = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(json) => validator
+            .iter_errors(&json)
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>(),
+        Err(e) => vec![format!("Response body is not a valid JSON: {e}")],
+    };
+
+    if errors.is_empty() {
+        return Ok(None);
+    }
+
+    log::warn!("Response validation failed: {errors:?}");
+    let mut report = String::from("Response validation failed:\n");
+    for e in errors {
+        report.push_str(&format!(" - {e}\n"));
+    }
+    Ok(Some(report.into_bytes()))
+}
+
+#[cfg(not(feature = "jsonschema"))]
+fn validate_response(
+    _schemas: &SchemaValidators,
+    _id: &str,
+    _schema: &serde_json::Value,
+    _body: &[u8],
+) -> color_eyre::Result<Option<Vec<u8>>> {
+    bail!("Response validation processor requires \"jsonschema\" feature")
+}
+
+/// Compiled JSON Schemas of response validation processors keyed by processor resource id.
+/// Must be cleared on specs change, because processors could be moved to other indexes.
+#[derive(Clone, Default)]
+pub struct SchemaValidators {
+    #[cfg(feature = "jsonschema")]
+    validators: Arc<RwLock<HashMap<String, Arc<jsonschema::Validator>>>>,
+}
+
+impl SchemaValidators {
+    #[cfg(feature = "jsonschema")]
+    fn get_or_compile(
+        &self,
+        id: &str,
+        schema: &serde_json::Value,
+    ) -> color_eyre::Result<Arc<jsonschema::Validator>> {
+        if let Some(validator) = self.validators.read().map_err(|e| eyre!("{e}"))?.get(id) {
+            return Ok(validator.clone());
+        }
+
+        let validator = Arc::new(
+            jsonschema::validator_for(schema).map_err(|e| eyre!("Invalid JSON Schema: {e}"))?,
+        );
+        self.validators
+            .write()
+            .map_err(|e| eyre!("{e}"))?
+            .insert(id.to_string(), validator.clone());
+        Ok(validator)
+    }
+
+    pub fn clear(&self) {
+        #[cfg(feature = "jsonschema")]
+        match self.validators.write() {
+            Ok(mut validators) => validators.clear(),
+            Err(e) => log::error!("Can't clear JSON Schema validators: {e}"),
+        }
+    }
+}
+
+/// Body is handled as UTF-8 text, invalid sequences are replaced.
+fn replace(
+    pattern: &str,
+    replacement: &str,
+    regex: bool,
+    body: &[u8],
+) -> color_eyre::Result<Vec<u8>> {
+    let text = String::from_utf8_lossy(body);
+    if !regex {
+        return Ok(text.replace(pattern, replacement).into_bytes());
+    }
+
+    let re = cached_regex(pattern).map_err(|e| eyre!("Invalid replace regex: {e}"))?;
+    Ok(re.replace_all(&text, replacement).into_owned().into_bytes())
+}
+
+fn apply_merge_patch(body: &[u8], patch: &[u8]) -> color_eyre::Result<Vec<u8>> {
+    let mut target: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| eyre!("Can't apply merge patch, body is not a valid JSON: {e}"))?;
+    let patch: serde_json::Value =
+        serde_json::from_slice(patch).map_err(|e| eyre!("Merge patch is not a valid JSON: {e}"))?;
+
+    merge_patch(&mut target, &patch);
+    Ok(serde_json::to_vec(&target)?)
+}
+
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = serde_json::Value::Object(Default::default());
+    }
+    if let serde_json::Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Body is parsed into generic value first, so only data (not comments or formatting) is kept.
+fn transcode(from: Format, to: Format, body: &[u8]) -> color_eyre::Result<Vec<u8>> {
+    let value: serde_json::Value = match from {
+        Format::Json => serde_json::from_slice(body)
+            .map_err(|e| eyre!("Can't transcode body, invalid JSON: {e}"))?,
+        Format::Yaml => from_yaml(body)?,
+        Format::Toml => toml::from_str(&String::from_utf8_lossy(body))
+            .map_err(|e| eyre!("Can't transcode body, invalid TOML: {e}"))?,
+    };
+
+    match to {
+        Format::Json => Ok(serde_json::to_vec(&value)?),
+        Format::Yaml => to_yaml(&value),
+        Format::Toml => Ok(toml::to_string(&value)
+            .map_err(|e| eyre!("Can't transcode body into TOML: {e}"))?
+            .into_bytes()),
+    }
+}
+
+fn from_yaml(body: &[u8]) -> color_eyre::Result<serde_json::Value> {
+    serde_yaml::from_slice(body).map_err(|e| eyre!("Can't transcode body, invalid YAML: {e}"))
+}
+
+fn to_yaml(value: &serde_json::Value) -> color_eyre::Result<Vec<u8>> {
+    Ok(serde_yaml::to_string(value)?.into_bytes())
+}
+
 pub(crate) fn apply_rhai(
     rhai: &RhaiState,
     rref: ResourceRef,
@@ -148,7 +494,7 @@ pub(crate) fn apply_rhai(
         .get_exec(id.clone(), script)
         .map_err(|e| eyre!("Can't load Rhai matcher by path:{rref} {e:?}"))?;
 
-    call_rhai(&engine, &ast, rctx.into(), Array::new(), body)
+    call_rhai(&engine, &ast, rctx, Array::new(), body)
 }
 
 pub(crate) fn apply_rhai_ref(
@@ -164,25 +510,30 @@ pub(crate) fn apply_rhai_ref(
     })?;
 
     let args = args.into_iter().map(Into::into).collect();
-    call_rhai(&engine, &ast, rctx.into(), args, body)
+    call_rhai(&engine, &ast, rctx, args, body)
 }
 
+/// Returned blob replaces the body, otherwise the one set with `ctx.set_body(blob)` is used.
 fn call_rhai(
     engine: &Engine,
     ast: &AST,
-    ctx: RhaiResponseContext,
+    rctx: DeceitResponseContext,
     args: Array,
     body: &[u8],
 ) -> color_eyre::Result<Option<Vec<u8>>> {
+    let response_body = rctx.response_body.clone();
+    *response_body.lock().expect("Response body lock failed") = body.to_vec();
+
     let mut scope = Scope::new();
-    scope.set_value("ctx", ctx);
+    scope.set_value("ctx", RhaiResponseContext::from(rctx));
     scope.set_value("args", args);
     scope.set_value("body", Blob::from(body));
 
     let result = engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast)?;
+    let set_body = std::mem::take(&mut *response_body.lock().expect("Response body lock failed"));
 
     let value = if result.is_unit() {
-        None
+        (set_body != body).then_some(set_body)
     } else if result.is_blob() {
         let blob = result
             .try_cast_result::<Blob>()
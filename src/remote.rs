@@ -0,0 +1,85 @@
+//! Load specs from remote HTTP(S) location.
+
+use std::time::Duration;
+
+use color_eyre::eyre::{bail, eyre};
+use reqwest::header::CONTENT_TYPE;
+
+use crate::ApateSpecs;
+
+/// Do not let unresponsive specs server to hang startup forever.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch specs by URL and parse them according to response content type.
+/// TOML is expected when content type is not JSON or YAML.
+/// Env variables are expanded in fetched text the same way as in spec files.
+pub(crate) fn fetch_specs(url: &str) -> color_eyre::Result<ApateSpecs> {
+    let target = url.to_string();
+    // Blocking client panics inside async runtime, so it lives in its own thread
+    let (content_type, body) = std::thread::spawn(move || fetch(&target))
+        .join()
+        .map_err(|_| eyre!("Specs fetching from {url} panicked"))??;
+
+    let content = crate::expanded_specs_text(url, &body)?;
+    parse_specs(&content_type, &content).map_err(|e| eyre!("Can't parse specs from {url}. {e}"))
+}
+
+fn fetch(url: &str) -> color_eyre::Result<(String, Vec<u8>)> {
+    let response = reqwest::blocking::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()?
+        .get(url)
+        .send()
+        .map_err(|e| eyre!("Can't fetch specs from {url}. {e}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Can't fetch specs from {url}. Server responded with {status}");
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let body = response
+        .bytes()
+        .map_err(|e| eyre!("Can't read specs from {url}. {e}"))?;
+
+    Ok((content_type, body.to_vec()))
+}
+
+/// Fetch response body for remote cached output.
+pub(crate) async fn fetch_output(url: &str) -> color_eyre::Result<Vec<u8>> {
+    let response = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| eyre!("Can't fetch output from {url}. {e}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Can't fetch output from {url}. Server responded with {status}");
+    }
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| eyre!("Can't read output from {url}. {e}"))?;
+
+    Ok(body.to_vec())
+}
+
+fn parse_specs(content_type: &str, content: &str) -> color_eyre::Result<ApateSpecs> {
+    if content_type.contains("json") {
+        Ok(serde_json::from_str(content)?)
+    } else if content_type.contains("yaml") {
+        Ok(serde_yaml::from_str(content)?)
+    } else {
+        Ok(toml::from_str(content)?)
+    }
+}
@@ -1,19 +1,45 @@
 use std::{
+    cell::RefCell,
     collections::{BTreeMap, HashMap},
+    net::IpAddr,
     sync::{Arc, RwLock, atomic::Ordering},
+    time::Duration,
 };
 
+use actix_web::web::Bytes;
+use futures::channel::mpsc::UnboundedSender;
+
+use base64::Engine as _;
+use hmac::{Hmac, Mac as _};
 use rand::{Rng as _, RngCore as _};
 use rhai::{
     AST, Blob, Dynamic, Engine, EvalAltResult, Map as RhaiMap, ParseError, ParseErrorType, Position,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use uuid::Uuid;
 
 use crate::{RequestContext, deceit::DeceitResponseContext};
 
 type RhaiStorage = Arc<RwLock<BTreeMap<String, String>>>;
 
+/// Longest delay `sleep_ms(n)` could produce, bigger values are capped.
+pub const MAX_SCRIPT_SLEEP_MS: u64 = 10_000;
+
+thread_local! {
+    /// Sender for chunks produced by `emit(blob)` calls of streaming output script
+    /// that is running on the current thread.
+    static STREAM_SENDER: RefCell<Option<UnboundedSender<Bytes>>> = const { RefCell::new(None) };
+}
+
+/// Run function with `emit(blob)` Rhai calls redirected to the sender.
+pub(crate) fn with_stream_sender<T>(sender: UnboundedSender<Bytes>, f: impl FnOnce() -> T) -> T {
+    STREAM_SENDER.with_borrow_mut(|s| *s = Some(sender));
+    let result = f();
+    STREAM_SENDER.with_borrow_mut(|s| *s = None);
+    result
+}
+
 /// Thai script specification that can be used as a matcher or processor.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct RhaiScript {
@@ -88,6 +114,42 @@ impl RhaiState {
         Ok(ast)
     }
 
+    /// Eagerly compile all global scripts, so broken ones are reported at startup.
+    /// Inline matcher, processor and output scripts are still compiled lazily.
+    pub fn compile_all(&self) -> color_eyre::Result<()> {
+        let scripts = self.scripts.read().expect("Rhai RwLock read failed");
+        let mut failed: Vec<String> = Vec::new();
+
+        for (id, script) in scripts.iter() {
+            if let Err(e) = self.rhai_build_ast(&self.engine, format!("global:{id}"), script) {
+                failed.push(format!("{id}: {e}"));
+            }
+        }
+
+        if !failed.is_empty() {
+            failed.sort();
+            color_eyre::eyre::bail!("Can't compile Rhai script(s):\n{}", failed.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Compile script without caching it, e.g. to validate specs before applying them.
+    pub fn compile(&self, script: &str) -> Result<AST, ParseError> {
+        self.engine.compile(script)
+    }
+
+    /// Returns state that shares engine, global scripts and storage with current one
+    /// but has own cache for compiled scripts.
+    /// Use it to run ad-hoc scripts that are not part of the specs.
+    pub fn with_isolated_cache(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            scripts: self.scripts.clone(),
+            asts: Default::default(),
+            storage: self.storage.clone(),
+        }
+    }
+
     fn clear(&self) {
         let mut bytecode_guard = self.asts.write().expect("Write guard for Rhai failed");
         (*bytecode_guard).clear();
@@ -111,10 +173,16 @@ impl RhaiState {
 /// Expose next API:
 ///  - ctx.method -> returns request method
 ///  - ctx.path -> returns request path
+///  - ctx.remote_ip -> returns connection peer IP address (empty string if unknown)
+///  - ctx.forwarded_ip -> returns first `X-Forwarded-For` address (empty string without the header)
 ///  - ctx.load_headers() -> build request headers map (lowercase keys)
 ///  - ctx.load_query_args() -> build map with URL query arguments
+///  - ctx.load_query_args_multi() -> build map with arrays of all values for every query argument
 ///  - ctx.load_path_args() -> build arguments map from specs URIs like /mypath/{user_id}/{item_id}
 ///  - ctx.load_body() -> reads request body as Blob
+///  - ctx.load_body_json() -> parse request body as JSON (unit for empty body)
+///  - ctx.load_form() -> build map with fields of urlencoded form body
+///  - ctx.load_multipart() -> build map with text fields of multipart form body
 #[derive(Debug, Clone)]
 pub struct RhaiRequestContext {
     pub req: RequestContext,
@@ -129,6 +197,14 @@ impl RhaiRequestContext {
         self.req.path.as_ref().clone()
     }
 
+    pub fn get_remote_ip(&mut self) -> String {
+        ip_string(self.req.peer_ip)
+    }
+
+    pub fn get_forwarded_ip(&mut self) -> String {
+        ip_string(self.req.forwarded_ip)
+    }
+
     pub fn load_headers(&mut self) -> RhaiMap {
         self.req
             .headers
@@ -153,9 +229,25 @@ impl RhaiRequestContext {
             .collect()
     }
 
+    pub fn load_query_args_multi(&mut self) -> RhaiMap {
+        query_args_multi_map(&self.req)
+    }
+
     pub fn load_body(&mut self) -> Blob {
         Blob::from(self.req.body.to_vec())
     }
+
+    pub fn load_body_json(&mut self) -> Result<Dynamic, Box<EvalAltResult>> {
+        body_json(&self.req)
+    }
+
+    pub fn load_form(&mut self) -> RhaiMap {
+        string_map(self.req.load_form())
+    }
+
+    pub fn load_multipart(&mut self) -> RhaiMap {
+        string_map(self.req.load_multipart())
+    }
 }
 
 impl From<RequestContext> for RhaiRequestContext {
@@ -164,17 +256,43 @@ impl From<RequestContext> for RhaiRequestContext {
     }
 }
 
+fn counter_result(
+    method: &str,
+    result: color_eyre::Result<u64>,
+) -> Result<i64, Box<EvalAltResult>> {
+    result
+        .map_err(|e| {
+            Box::new(EvalAltResult::ErrorSystem(
+                format!("Failed {method}"),
+                e.into(),
+            ))
+        })
+        .map(|v| v as i64)
+}
+
 /// Context available in Rhai processors under `ctx` variable.
 ///
 /// Expose next API:
 ///  - ctx.method -> returns request method
 ///  - ctx.path -> returns request path
+///  - ctx.remote_ip -> returns connection peer IP address (empty string if unknown)
+///  - ctx.forwarded_ip -> returns first `X-Forwarded-For` address (empty string without the header)
 ///  - ctx.response_code -> get set custom response code if any (default 0 if not set)
 ///  - ctx.load_headers() -> build request headers map (lowercase keys)
 ///  - ctx.load_query_args() -> build map with URL query arguments
+///  - ctx.load_query_args_multi() -> build map with arrays of all values for every query argument
 ///  - ctx.load_path_args() -> build arguments map from specs URIs like /mypath/{user_id}/{item_id}
 ///  - ctx.load_body() -> reads request body as Blob
+///  - ctx.load_body_json() -> parse request body as JSON (unit for empty body)
+///  - ctx.load_form() -> build map with fields of urlencoded form body
+///  - ctx.load_multipart() -> build map with text fields of multipart form body
 ///  - ctx.inc_counter("key") -> increment counter by key and returns previous value
+///  - ctx.dec_counter("key") -> decrement counter by key (not below zero) and returns previous value
+///  - ctx.set_counter("key", value) -> set counter value and returns previous value
+///  - ctx.reset_counter("key") -> set counter to zero and returns previous value
+///  - ctx.get_counter("key") -> returns current counter value
+///
+/// Counter keys prefixed with `@local:` are visible only for current deceit.
 #[derive(Clone)]
 pub struct RhaiResponseContext {
     ctx: DeceitResponseContext,
@@ -195,6 +313,14 @@ impl RhaiResponseContext {
         self.ctx.req.path.to_string()
     }
 
+    pub fn get_remote_ip(&mut self) -> String {
+        ip_string(self.ctx.req.peer_ip)
+    }
+
+    pub fn get_forwarded_ip(&mut self) -> String {
+        ip_string(self.ctx.req.forwarded_ip)
+    }
+
     pub fn get_response_code(&mut self) -> i64 {
         self.ctx.response_code.load(Ordering::Relaxed) as i64
     }
@@ -206,16 +332,48 @@ impl RhaiResponseContext {
     }
 
     pub fn inc_counter(&mut self, key: &str) -> Result<i64, Box<EvalAltResult>> {
-        self.ctx
-            .counters
-            .get_and_increment(key)
-            .map_err(|e| {
-                Box::new(EvalAltResult::ErrorSystem(
-                    "Failed inc_counter".to_string(),
-                    e.into(),
-                ))
-            })
-            .map(|v| v as i64)
+        counter_result(
+            "inc_counter",
+            self.ctx
+                .counters
+                .get_and_increment(&self.ctx.counter_key(key)),
+        )
+    }
+
+    pub fn dec_counter(&mut self, key: &str) -> Result<i64, Box<EvalAltResult>> {
+        counter_result(
+            "dec_counter",
+            self.ctx
+                .counters
+                .get_and_decrement(&self.ctx.counter_key(key)),
+        )
+    }
+
+    pub fn set_counter(&mut self, key: &str, value: i64) -> Result<i64, Box<EvalAltResult>> {
+        let value = u64::try_from(value).map_err(|e| {
+            Box::new(EvalAltResult::ErrorSystem(
+                "Counter value must not be negative".to_string(),
+                e.into(),
+            ))
+        })?;
+        counter_result(
+            "set_counter",
+            self.ctx.counters.set(&self.ctx.counter_key(key), value),
+        )
+    }
+
+    pub fn reset_counter(&mut self, key: &str) -> Result<i64, Box<EvalAltResult>> {
+        counter_result(
+            "reset_counter",
+            self.ctx.counters.reset(&self.ctx.counter_key(key)),
+        )
+    }
+
+    pub fn get_counter(&mut self, key: &str) -> Result<i64, Box<EvalAltResult>> {
+        counter_result(
+            "get_counter",
+            self.ctx.counters.get_or_default(&self.ctx.counter_key(key)),
+        )
     }
 
     pub fn load_headers(&mut self) -> RhaiMap {
@@ -245,9 +403,71 @@ impl RhaiResponseContext {
             .collect()
     }
 
+    pub fn load_query_args_multi(&mut self) -> RhaiMap {
+        query_args_multi_map(&self.ctx.req)
+    }
+
     pub fn load_body(&mut self) -> Blob {
         Blob::from(self.ctx.req.body.to_vec())
     }
+
+    pub fn load_body_json(&mut self) -> Result<Dynamic, Box<EvalAltResult>> {
+        body_json(&self.ctx.req)
+    }
+
+    /// Current response body in processors, empty in output scripts.
+    pub fn get_body(&mut self) -> Blob {
+        self.ctx
+            .response_body
+            .lock()
+            .expect("Response body lock failed")
+            .clone()
+    }
+
+    pub fn set_body(&mut self, body: Blob) {
+        *self
+            .ctx
+            .response_body
+            .lock()
+            .expect("Response body lock failed") = body;
+    }
+
+    pub fn load_form(&mut self) -> RhaiMap {
+        string_map(self.ctx.req.load_form())
+    }
+
+    pub fn load_multipart(&mut self) -> RhaiMap {
+        string_map(self.ctx.req.load_multipart())
+    }
+}
+
+/// Parsed body is cached in request context, so it is shared with other scripts & templates.
+fn body_json(req: &RequestContext) -> Result<Dynamic, Box<EvalAltResult>> {
+    let json = req.load_body_as_json().map_err(|e| {
+        Box::new(EvalAltResult::ErrorSystem(
+            "Can't parse request body as JSON".to_string(),
+            e.into(),
+        ))
+    })?;
+    rhai::serde::to_dynamic(json.as_ref())
+}
+
+fn ip_string(ip: Option<IpAddr>) -> String {
+    ip.map(|ip| ip.to_string()).unwrap_or_default()
+}
+
+fn string_map(map: HashMap<String, String>) -> RhaiMap {
+    map.into_iter().map(|(k, v)| (k.into(), v.into())).collect()
+}
+
+fn query_args_multi_map(req: &RequestContext) -> RhaiMap {
+    req.query_args_multi
+        .iter()
+        .map(|(k, values)| {
+            let values: rhai::Array = values.iter().map(|v| v.into()).collect();
+            (k.into(), values.into())
+        })
+        .collect()
 }
 
 fn build_rhai_engine(rs: RhaiStorage) -> Engine {
@@ -256,6 +476,15 @@ fn build_rhai_engine(rs: RhaiStorage) -> Engine {
     engine.register_fn("to_json_blob", to_json_blob);
     engine.register_fn("from_json_blob", from_json_blob);
     engine.register_fn("uuid_v4", ctx_uuid_v4);
+    engine.register_fn("uuid_v5", ctx_uuid_v5);
+    engine.register_fn("emit", stream_emit);
+    engine.register_fn("sleep_ms", sleep_ms);
+    engine.register_fn("sha256", ctx_sha256);
+    engine.register_fn("hmac_sha256", ctx_hmac_sha256);
+    engine.register_fn("b64_encode", b64_encode);
+    engine.register_fn("b64_decode", b64_decode);
+    engine.register_fn("hex_encode", hex_encode);
+    engine.register_fn("hex_decode", hex_decode);
 
     engine
         .register_fn("random_num", ctx_random_num)
@@ -286,16 +515,31 @@ fn build_rhai_engine(rs: RhaiStorage) -> Engine {
         .register_type::<RhaiRequestContext>()
         .register_get("method", RhaiRequestContext::get_method)
         .register_get("path", RhaiRequestContext::get_path)
+        .register_get("remote_ip", RhaiRequestContext::get_remote_ip)
+        .register_get("forwarded_ip", RhaiRequestContext::get_forwarded_ip)
         .register_fn("load_headers", RhaiRequestContext::load_headers)
         .register_fn("load_query_args", RhaiRequestContext::load_query_args)
+        .register_fn(
+            "load_query_args_multi",
+            RhaiRequestContext::load_query_args_multi,
+        )
         .register_fn("load_path_args", RhaiRequestContext::load_path_args)
-        .register_fn("load_body", RhaiRequestContext::load_body);
+        .register_fn("load_body", RhaiRequestContext::load_body)
+        .register_fn("load_body_json", RhaiRequestContext::load_body_json)
+        .register_fn("load_form", RhaiRequestContext::load_form)
+        .register_fn("load_multipart", RhaiRequestContext::load_multipart);
 
     engine
         .register_type::<RhaiResponseContext>()
         .register_get("method", RhaiResponseContext::get_method)
         .register_get("path", RhaiResponseContext::get_path)
+        .register_get("remote_ip", RhaiResponseContext::get_remote_ip)
+        .register_get("forwarded_ip", RhaiResponseContext::get_forwarded_ip)
         .register_fn("inc_counter", RhaiResponseContext::inc_counter)
+        .register_fn("dec_counter", RhaiResponseContext::dec_counter)
+        .register_fn("set_counter", RhaiResponseContext::set_counter)
+        .register_fn("reset_counter", RhaiResponseContext::reset_counter)
+        .register_fn("get_counter", RhaiResponseContext::get_counter)
         .register_get_set(
             "response_code",
             RhaiResponseContext::get_response_code,
@@ -303,8 +547,17 @@ fn build_rhai_engine(rs: RhaiStorage) -> Engine {
         )
         .register_fn("load_headers", RhaiResponseContext::load_headers)
         .register_fn("load_query_args", RhaiResponseContext::load_query_args)
+        .register_fn(
+            "load_query_args_multi",
+            RhaiResponseContext::load_query_args_multi,
+        )
         .register_fn("load_path_args", RhaiResponseContext::load_path_args)
-        .register_fn("load_body", RhaiResponseContext::load_body);
+        .register_fn("load_body", RhaiResponseContext::load_body)
+        .register_fn("load_body_json", RhaiResponseContext::load_body_json)
+        .register_fn("get_body", RhaiResponseContext::get_body)
+        .register_fn("set_body", RhaiResponseContext::set_body)
+        .register_fn("load_form", RhaiResponseContext::load_form)
+        .register_fn("load_multipart", RhaiResponseContext::load_multipart);
 
     engine
 }
@@ -353,6 +606,32 @@ fn storage_write(
     Ok(())
 }
 
+fn stream_emit(chunk: Blob) -> Result<(), Box<EvalAltResult>> {
+    STREAM_SENDER.with_borrow(|sender| {
+        let Some(sender) = sender else {
+            return Err("emit() is available only for rhai_stream output".into());
+        };
+
+        // Failing here stops the script when client is gone
+        sender
+            .unbounded_send(Bytes::from(chunk))
+            .map_err(|e| format!("Can't emit response chunk: {e}").into())
+    })
+}
+
+/// Scripts are executed synchronously, so it blocks the whole worker thread.
+fn sleep_ms(ms: i64) -> Result<(), Box<EvalAltResult>> {
+    let Ok(ms) = u64::try_from(ms) else {
+        return Err(format!("sleep_ms() expects non negative delay, got {ms}").into());
+    };
+
+    if ms > MAX_SCRIPT_SLEEP_MS {
+        log::warn!("sleep_ms({ms}) is capped to {MAX_SCRIPT_SLEEP_MS}ms");
+    }
+    std::thread::sleep(Duration::from_millis(ms.min(MAX_SCRIPT_SLEEP_MS)));
+    Ok(())
+}
+
 fn to_json_blob(value: &mut Dynamic) -> Result<Blob, Box<EvalAltResult>> {
     serde_json::to_string(value)
         .map_err(|e| {
@@ -397,6 +676,43 @@ fn ctx_random_hex(length: i64) -> String {
     hex::encode(bytes)
 }
 
+/// Returns HEX string of the data SHA-256 hash.
+fn ctx_sha256(data: Blob) -> String {
+    hex::encode(Sha256::digest(&data))
+}
+
+/// Returns HEX string of the data HMAC-SHA256 signature, e.g. to sign webhook payloads.
+fn ctx_hmac_sha256(key: Blob, data: Blob) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC key of any size");
+    mac.update(&data);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn b64_encode(data: Blob) -> String {
+    base64::prelude::BASE64_STANDARD.encode(data)
+}
+
+fn b64_decode(value: &str) -> Result<Blob, Box<EvalAltResult>> {
+    base64::prelude::BASE64_STANDARD
+        .decode(value.trim())
+        .map_err(|e| format!("Can't decode Base64 string: {e}").into())
+}
+
+fn hex_encode(data: Blob) -> String {
+    hex::encode(data)
+}
+
+fn hex_decode(value: &str) -> Result<Blob, Box<EvalAltResult>> {
+    hex::decode(value.trim()).map_err(|e| format!("Can't decode HEX string: {e}").into())
+}
+
 fn ctx_uuid_v4() -> String {
     Uuid::new_v4().to_string()
 }
+
+fn ctx_uuid_v5(namespace: &str, name: &str) -> Result<String, Box<EvalAltResult>> {
+    crate::uuid_v5(namespace, name)
+        .map(|uuid| uuid.to_string())
+        .map_err(Into::into)
+}
@@ -1,6 +1,8 @@
-use actix_web::dev::ServerHandle;
+use std::sync::atomic::Ordering;
 
-use crate::{ApateConfig, init_actix_web_server};
+use actix_web::{dev::ServerHandle, web::Data};
+
+use crate::{ApateConfig, ApateState, init_actix_web_server};
 
 pub use crate::{DEFAULT_PORT, DEFAULT_RUST_LOG};
 
@@ -15,6 +17,8 @@ pub fn init_env_logger(log: &str) {
 
 pub struct ApateTestServer {
     server_handle: ServerHandle,
+    state: Data<ApateState>,
+    strict: bool,
     #[allow(dead_code)]
     handle: std::thread::JoinHandle<Result<(), std::io::Error>>,
 }
@@ -35,6 +39,11 @@ impl Drop for ApateTestServer {
             let trt = tokio::runtime::Runtime::new().unwrap();
             trt.block_on(stopping);
         }
+
+        // Verify after server is stopped, so failed check does not keep the port busy
+        if self.strict && !std::thread::panicking() {
+            self.verify_no_unmatched();
+        }
     }
 }
 
@@ -48,7 +57,8 @@ impl ApateTestServer {
             log::warn!("Starting server without deceits in specs");
         }
 
-        let server = init_actix_web_server(config).expect("Test server must be initialized");
+        let (server, state) =
+            init_actix_web_server(config).expect("Test server must be initialized");
         let server_handle = server.handle();
         let handle = std::thread::spawn(move || {
             actix_web::rt::Runtime::new()
@@ -63,6 +73,30 @@ impl ApateTestServer {
         Self {
             handle,
             server_handle,
+            state,
+            strict: false,
         }
     }
+
+    /// Enable strict mode: [`Self::verify_no_unmatched`] is called when server is dropped.
+    /// Catches requests to unexpected URIs (like typos in client URLs) without explicit checks.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Number of requests that were not handled by any deceit so far.
+    pub fn unmatched_requests(&self) -> u64 {
+        self.state.unmatched_requests.load(Ordering::Relaxed)
+    }
+
+    /// Panics if any request was not handled by any deceit (got 404 fallback response).
+    /// Use journal (see [`crate::ApateConfigBuilder::with_journal_capacity`]) to find out which ones.
+    pub fn verify_no_unmatched(&self) {
+        let unmatched = self.unmatched_requests();
+        assert!(
+            unmatched == 0,
+            "Apate server received {unmatched} unmatched request(s)"
+        );
+    }
 }
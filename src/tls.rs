@@ -0,0 +1,26 @@
+//! HTTPS listener support.
+//! HTTP/2 is negotiated with ALPN by actix, so it comes together with TLS.
+
+use std::io;
+use std::sync::Arc;
+
+use rustls::ServerConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject as _};
+
+/// Read PEM encoded certificate chain & private key.
+pub(crate) fn load_server_config(cert_path: &str, key_path: &str) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| io::Error::other(format!("Can't read TLS certificate {cert_path}. {e}")))?;
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| io::Error::other(format!("Can't read TLS private key {key_path}. {e}")))?;
+
+    // Explicit provider, so it does not depend on what other crates enable in rustls
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| io::Error::other(format!("Invalid TLS certificate or key. {e}")))
+}
@@ -0,0 +1,112 @@
+//! Reload specs from disk when spec files are changed.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak, mpsc},
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher as _};
+
+use crate::{ApateConfig, ApateState, STDIN_SPECS, SpecsImport, is_remote_specs};
+
+/// Editors usually produce a bunch of events on save, wait until they calm down.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Spawn a thread that watches spec files and swaps specs in the state on change.
+/// Imported API descriptions are watched too and merged again on every reload.
+/// Thread exits when the state is dropped.
+pub(crate) fn spawn_specs_watcher(
+    files: Vec<String>,
+    imports: Vec<SpecsImport>,
+    state: Arc<ApateState>,
+) -> color_eyre::Result<()> {
+    if files.is_empty() && imports.is_empty() {
+        log::warn!("Specs watching is enabled but there are no spec files to watch");
+        return Ok(());
+    }
+
+    // Remote and stdin specs are read again on reload but there is nothing to watch for them
+    let watched: HashSet<PathBuf> = files
+        .iter()
+        .filter(|f| !is_remote_specs(f) && f.as_str() != STDIN_SPECS)
+        .map(|f| absolute_path(f))
+        .chain(imports.iter().map(|i| absolute_path(i.path())))
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    // Watching directories because editors often replace files instead of writing into them
+    let dirs: HashSet<PathBuf> = watched
+        .iter()
+        .filter_map(|p| p.parent().map(Path::to_path_buf))
+        .collect();
+    for dir in &dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+
+    log::info!("Watching specs files for changes: {files:?} imports: {imports:?}");
+
+    let state = Arc::downgrade(&state);
+    std::thread::spawn(move || {
+        // Watcher must live as long as the thread
+        let _watcher = watcher;
+
+        while let Ok(event) = rx.recv() {
+            if !is_relevant(&event, &watched) {
+                continue;
+            }
+
+            // Debounce: swallow everything that comes right after the first event
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+            if !reload_specs(&files, &imports, &state) {
+                break;
+            }
+        }
+        log::debug!("Specs watcher stopped");
+    });
+
+    Ok(())
+}
+
+fn is_relevant(event: &notify::Result<notify::Event>, watched: &HashSet<PathBuf>) -> bool {
+    match event {
+        Ok(e) => {
+            matches!(
+                e.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) && e.paths.iter().any(|p| watched.contains(p))
+        }
+        Err(e) => {
+            log::warn!("Specs watcher error: {e}");
+            false
+        }
+    }
+}
+
+/// Returns false if state does not exist anymore.
+fn reload_specs(files: &[String], imports: &[SpecsImport], state: &Weak<ApateState>) -> bool {
+    let Some(state) = state.upgrade() else {
+        return false;
+    };
+
+    match ApateConfig::read_specs(files, imports).and_then(|s| s.check_uris().map(|_| s)) {
+        Ok(new_specs) => {
+            let mut specs = state.specs.write_blocking();
+            *specs = new_specs;
+            specs.sort_by_priority();
+            state.reload_caches(&specs);
+            log::info!("Specs reloaded from files: {files:?} imports: {imports:?}");
+        }
+        Err(e) => log::error!("Specs were not reloaded, keeping the old ones. {e}"),
+    }
+    true
+}
+
+fn absolute_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
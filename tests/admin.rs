@@ -0,0 +1,649 @@
+use apate::{
+    ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    matchers::Matcher,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+const REPLACED_SPECS: &str = r#"
+[[deceit]]
+uris = ["/rhai/check"]
+
+[[deceit.matchers]]
+type = "rhai"
+script = "ctx.method == \"GET\""
+
+[[deceit.responses]]
+output = "replaced"
+"#;
+
+#[test]
+#[serial]
+fn test_specs_replace_recompiles_rhai() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai/check"])
+                .add_matcher(Matcher::Rhai {
+                    script: "false".to_string(),
+                    args: vec![],
+                    negate: false,
+                })
+                .add_response(DeceitResponseBuilder::default().with_output("old").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    // Compiles and caches inline script
+    let response = client.get(api_url("/rhai/check")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(api_url("/apate/specs/replace"))
+        .body(REPLACED_SPECS)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(api_url("/rhai/check")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "replaced");
+
+    let response = client.post(api_url("/rhai/check")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_deceit_get_and_delete() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/first"])
+                .add_response(DeceitResponseBuilder::default().with_output("1").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/second"])
+                .add_response(DeceitResponseBuilder::default().with_output("2").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/apate/specs/deceit/1")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    let deceit: serde_json::Value = response.json().unwrap();
+    assert_eq!(deceit["uris"][0], "/second");
+
+    let response = client.get(api_url("/apate/specs/deceit/2")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .delete(api_url("/apate/specs/deceit/2"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .delete(api_url("/apate/specs/deceit/0"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(api_url("/first")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client.get(api_url("/apate/specs/deceit/0")).send().unwrap();
+    let deceit: serde_json::Value = response.json().unwrap();
+    assert_eq!(deceit["uris"][0], "/second");
+}
+
+#[test]
+#[serial]
+fn test_deceit_add() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/first"])
+                .add_response(DeceitResponseBuilder::default().with_output("1").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/added/7")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let deceit = serde_json::json!({
+        "uris": ["/added/{id}"],
+        "headers": {"X-Added": "yes"},
+        "responses": [{"type": "jinja", "output": "added {{ ctx.load_path_args().id }}"}]
+    });
+    let response = client
+        .post(api_url("/apate/specs/deceit"))
+        .json(&deceit)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, serde_json::json!({"index": 1}));
+
+    let response = client.get(api_url("/added/7")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers().get("x-added").unwrap(), "yes");
+    assert_eq!(response.text().unwrap(), "added 7");
+
+    let response = client
+        .post(api_url("/apate/specs/deceit"))
+        .body(r#"{"uris": "#)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    for invalid in [
+        serde_json::json!({"uri_glob": ["/files/[a"]}),
+        serde_json::json!({"uris": ["/items/{category}/{id}"], "path_arg_defaults": {"category": "all"}}),
+    ] {
+        let response = client
+            .post(api_url("/apate/specs/deceit"))
+            .json(&invalid)
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 400, "{invalid}");
+    }
+    let specs = client
+        .get(api_url("/apate/specs"))
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(
+        !specs.contains("/files/[a"),
+        "Invalid deceit must not be added"
+    );
+}
+
+#[test]
+#[serial]
+fn test_specs_update_rejects_invalid_uris() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/current"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let invalid_specs = [
+        r#"
+[[deceit]]
+uri_glob = ["/files/[a"]
+"#,
+        r#"
+[[deceit]]
+uris = ["/items/{category}/{id}"]
+path_arg_defaults = { category = "all" }
+"#,
+    ];
+
+    for action in ["replace", "prepend", "append"] {
+        for specs in invalid_specs {
+            let response = client
+                .post(api_url(&format!("/apate/specs/{action}")))
+                .body(specs)
+                .send()
+                .unwrap();
+            assert_eq!(response.status(), 400, "{action} {specs}");
+        }
+    }
+
+    // Nothing was applied
+    let specs = client
+        .get(api_url("/apate/specs"))
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(!specs.contains("/files/[a"), "{specs}");
+    assert!(!specs.contains("/items/"), "{specs}");
+    let response = client.get(api_url("/current")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+}
+
+#[test]
+#[serial]
+fn test_deceit_toggle() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/toggle"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("disabled response")
+                        .disable()
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("first")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/toggle"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("second")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/toggle")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "first");
+
+    let response = client
+        .post(api_url("/apate/specs/deceit/0/toggle"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, serde_json::json!({"enabled": false}));
+
+    let response = client.get(api_url("/toggle")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "second");
+
+    client
+        .post(api_url("/apate/specs/deceit/1/toggle"))
+        .send()
+        .unwrap();
+    let response = client.get(api_url("/toggle")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(api_url("/apate/specs/deceit/0/toggle"))
+        .send()
+        .unwrap();
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, serde_json::json!({"enabled": true}));
+
+    let response = client.get(api_url("/toggle")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "first");
+
+    let response = client
+        .post(api_url("/apate/specs/deceit/9/toggle"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_requests_journal() {
+    let config = ApateConfigBuilder::default()
+        .with_journal_capacity(10)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/journal"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    client.get(api_url("/journal?id=1")).send().unwrap();
+    client
+        .post(api_url("/journal"))
+        .body("second")
+        .send()
+        .unwrap();
+
+    let requests: Vec<serde_json::Value> = client
+        .get(api_url("/apate/requests"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(requests.len(), 2, "{requests:?}");
+    assert_eq!(requests[0]["method"], "GET");
+    assert_eq!(requests[0]["path"], "/journal");
+    assert_eq!(requests[0]["query_args"]["id"], "1");
+    assert_eq!(requests[1]["method"], "POST");
+    assert_eq!(requests[1]["body"], "second");
+
+    let response = client.delete(api_url("/apate/requests")).send().unwrap();
+    assert_eq!(response.status(), 200);
+
+    let requests: Vec<serde_json::Value> = client
+        .get(api_url("/apate/requests"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(requests.is_empty());
+}
+
+#[test]
+#[serial]
+fn test_requests_count() {
+    let config = ApateConfigBuilder::default()
+        .with_journal_capacity(10)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    client
+        .post(api_url("/user/1"))
+        .body("Apate")
+        .send()
+        .unwrap();
+    client.post(api_url("/user/1")).send().unwrap();
+    client.post(api_url("/user/2")).send().unwrap();
+    client.get(api_url("/user/1")).send().unwrap();
+
+    let count = |criteria: &str| -> u64 {
+        let response: serde_json::Value = client
+            .post(api_url("/apate/requests/count"))
+            .body(criteria.to_string())
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        response["count"].as_u64().unwrap()
+    };
+
+    assert_eq!(count("{}"), 4);
+    assert_eq!(count(r#"{"method": "post"}"#), 3);
+    assert_eq!(count(r#"{"path": "/user/{id}"}"#), 4);
+    assert_eq!(
+        count(
+            r#"{"method": "POST", "path": "/user/{id}", "matchers": [{"type": "path_arg", "name": "id", "value": "1"}]}"#
+        ),
+        2
+    );
+    assert_eq!(
+        count(
+            r#"{"matchers": [{"type": "rhai", "script": "ctx.load_body().as_string() == \"Apate\""}]}"#
+        ),
+        1
+    );
+
+    let response = client
+        .post(api_url("/apate/requests/count"))
+        .body("not a json")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[test]
+#[serial]
+fn test_drain() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/drain/me"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/drain/me")).send().unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.post(api_url("/apate/drain")).send().unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(api_url("/drain/me")).send().unwrap();
+    assert_eq!(response.status(), 503);
+    assert!(response.headers().contains_key("retry-after"));
+
+    let response = client.get(api_url("/apate/health")).send().unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+const BROKEN_SPECS: &str = r#"
+[[rhai]]
+id = "ok"
+script = "true"
+
+[[deceit]]
+uris = ["/broken"]
+
+[[deceit.matchers]]
+type = "rhai"
+script = "ctx.method == "
+
+[[deceit.responses]]
+type = "jinja"
+output = "{{ ctx.path }"
+
+[[deceit.responses]]
+type = { rhai_ref = { id = "missing" } }
+"#;
+
+#[test]
+#[serial]
+fn test_specs_validate() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/current"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .post(api_url("/apate/specs/validate"))
+        .body(BROKEN_SPECS)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let report: serde_json::Value = response.json().unwrap();
+    assert_eq!(report["valid"], false);
+    let ids: Vec<&str> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![
+            "deceit[0].matchers[0]",
+            "deceit[0].responses[0].output",
+            "deceit[0].responses[1].output"
+        ]
+    );
+
+    let response = client
+        .post(api_url("/apate/specs/validate"))
+        .body(REPLACED_SPECS)
+        .send()
+        .unwrap();
+    let report: serde_json::Value = response.json().unwrap();
+    assert_eq!(report, serde_json::json!({"valid": true, "errors": []}));
+
+    // Nothing was applied
+    let response = client.get(api_url("/current")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+    let response = client.get(api_url("/rhai/check")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_requests_har() {
+    let config = ApateConfigBuilder::default()
+        .with_journal_capacity(10)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/har"])
+                .add_header("Content-Type", "application/json")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .code(201)
+                        .with_output(r#"{"created":true}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    client
+        .post(api_url("/har?id=7"))
+        .header("Content-Type", "text/plain")
+        .body("payload")
+        .send()
+        .unwrap();
+
+    let har: serde_json::Value = client
+        .get(api_url("/apate/requests/har"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+
+    assert_eq!(har["log"]["version"], "1.2");
+    assert_eq!(har["log"]["creator"]["name"], "apate");
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1, "{har}");
+
+    let entry = &entries[0];
+    assert!(entry["startedDateTime"].as_str().unwrap().ends_with('Z'));
+
+    let request = &entry["request"];
+    assert_eq!(request["method"], "POST");
+    assert_eq!(request["url"], api_url("/har?id=7"));
+    assert_eq!(
+        request["queryString"],
+        serde_json::json!([{"name": "id", "value": "7"}])
+    );
+    assert_eq!(request["postData"]["mimeType"], "text/plain");
+    assert_eq!(request["postData"]["text"], "payload");
+
+    let response = &entry["response"];
+    assert_eq!(response["status"], 201);
+    assert_eq!(response["statusText"], "Created");
+    assert_eq!(response["content"]["mimeType"], "application/json");
+    assert_eq!(response["content"]["text"], r#"{"created":true}"#);
+}
+
+#[test]
+#[serial]
+fn test_specs_curl_script() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .require_method("POST")
+                .require_header("x-api-key", "it's secret")
+                .require_path_arg("id", "42")
+                .require_query_arg("mode", "full")
+                .require_form_arg("name", "Ann Lee")
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/items/{id}"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/apate/specs/curl")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/plain"
+    );
+
+    let script = response.text().unwrap();
+    assert!(script.starts_with("#!/bin/sh"), "{script}");
+    assert!(
+        script.contains(&format!(
+            "BASE_URL=\"${{BASE_URL:-http://localhost:{DEFAULT_PORT}}}\""
+        )),
+        "{script}"
+    );
+    assert!(
+        script.contains(
+            r#"curl -X POST -H 'x-api-key: it'\''s secret' --data-urlencode 'name=Ann Lee' "$BASE_URL"'/user/42?mode=full'"#
+        ),
+        "{script}"
+    );
+    assert!(
+        script.contains(r#"curl -X GET -g "$BASE_URL"'/items/{id}'"#),
+        "{script}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_health_and_ready_probes() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/apate/health", "/apate/ready"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .code(500)
+                        .with_output("shadowed")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/apate/health")).send().unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(api_url("/apate/ready")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body["deceits"], 1);
+
+    client.post(api_url("/apate/drain")).send().unwrap();
+
+    let response = client.get(api_url("/apate/ready")).send().unwrap();
+    assert_eq!(response.status(), 503);
+
+    let response = client.get(api_url("/apate/health")).send().unwrap();
+    assert_eq!(response.status(), 200);
+}
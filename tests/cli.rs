@@ -0,0 +1,336 @@
+use std::io::{Read as _, Write as _};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+const APATE_BIN: &str = env!("CARGO_BIN_EXE_apate");
+
+const SPECS_FILE: &str = "examples/apate-specs.toml";
+
+fn apate() -> Command {
+    let mut cmd = Command::new(APATE_BIN);
+    // Do not let specs from cargo config env to interfere with tests
+    for (key, _) in std::env::vars() {
+        if key.starts_with("APATHE_SPECS_FILE") {
+            cmd.env_remove(key);
+        }
+    }
+    cmd
+}
+
+/// Wait until server at port responds or panic after a few seconds.
+fn wait_for_server(port: u16) {
+    let client = reqwest::blocking::Client::new();
+    for _ in 0..100 {
+        if client
+            .get(format!("http://localhost:{port}/apate/info"))
+            .send()
+            .is_ok()
+        {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    panic!("Apate server was not started on port {port}");
+}
+
+fn stop_and_read_stderr(mut child: Child) -> String {
+    child.kill().expect("Apate process must be killed");
+    child.wait().expect("Apate process must exit");
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .expect("Stderr must be piped")
+        .read_to_string(&mut stderr)
+        .expect("Stderr must be readable");
+    stderr
+}
+
+#[test]
+fn cli_check() {
+    let output = apate().args(["check", SPECS_FILE]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Specs are OK"), "{stdout}");
+
+    let output = apate()
+        .args(["check", "examples/does-not-exist.toml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+}
+
+#[test]
+fn cli_dump() {
+    let output = apate().args(["dump", SPECS_FILE]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let specs: toml::Value = toml::from_str(&stdout).expect("Dump output must be valid TOML");
+    let deceit = specs.get("deceit").unwrap().as_array().unwrap();
+    assert_eq!(deceit.len(), 3, "{stdout}");
+}
+
+#[test]
+fn cli_serve() {
+    let port = 18301;
+    for args in [
+        vec!["serve", "-p", "18301", SPECS_FILE],
+        vec!["-p", "18301", SPECS_FILE],
+    ] {
+        let mut child = apate()
+            .args(args)
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Apate must start");
+        wait_for_server(port);
+
+        let response =
+            reqwest::blocking::get(format!("http://localhost:{port}/user/list")).unwrap();
+        assert_eq!(response.status(), 200);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn cli_record() {
+    let port = 18302;
+    let child = apate()
+        .args(["record", "-p", "18302", "-l", "info", SPECS_FILE])
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Apate must start");
+    wait_for_server(port);
+
+    let response = reqwest::blocking::Client::new()
+        .post(format!("http://localhost:{port}/user/add"))
+        .body(r#"{"name":"Recorded"}"#)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let stderr = stop_and_read_stderr(child);
+    assert!(
+        stderr.contains("Recorded request: POST /user/add"),
+        "{stderr}"
+    );
+    assert!(stderr.contains(r#"{"name":"Recorded"}"#), "{stderr}");
+}
+
+#[test]
+fn cli_bind() {
+    let port = 18303;
+    for args in [
+        vec!["-b", "127.0.0.1", "-p", "18303", SPECS_FILE],
+        vec!["--bind=127.0.0.1", "--watch", "-p", "18303", SPECS_FILE],
+        vec!["--bind", "127.0.0.1", "-w", "-p", "18303", SPECS_FILE],
+    ] {
+        let mut child = apate()
+            .args(&args)
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Apate must start");
+        wait_for_server(port);
+
+        let response =
+            reqwest::blocking::get(format!("http://127.0.0.1:{port}/user/list")).unwrap();
+        assert_eq!(response.status(), 200, "{args:?}");
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    let output = apate()
+        .args(["-b", "not-an-address", SPECS_FILE])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid bind address"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn cli_graceful_shutdown() {
+    let port = 18304;
+    let mut child = apate()
+        .args(["-p", "18304", "-l", "info", SPECS_FILE])
+        .env("APATHE_SHUTDOWN_TIMEOUT", "5")
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Apate must start");
+    wait_for_server(port);
+
+    let killed = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+
+    let status = child.wait().expect("Apate process must exit");
+    assert!(status.success(), "{status:?}");
+
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .expect("Stderr must be piped")
+        .read_to_string(&mut stderr)
+        .expect("Stderr must be readable");
+    assert!(stderr.contains("Received SIGTERM"), "{stderr}");
+    assert!(stderr.contains("timeout 5s"), "{stderr}");
+    assert!(stderr.contains("Apate server stopped"), "{stderr}");
+}
+
+#[test]
+fn cli_broken_rhai_script() {
+    let specs_file = std::env::temp_dir().join("apate-broken-rhai.toml");
+    std::fs::write(
+        &specs_file,
+        r#"
+[[rhai]]
+id = "broken_script"
+script = "let x = ;"
+
+[[deceit]]
+uris = ["/"]
+
+[[deceit.responses]]
+output = "ok"
+"#,
+    )
+    .unwrap();
+    let specs_file = specs_file.to_str().unwrap();
+
+    for args in [vec!["check", specs_file], vec!["-p", "18306", specs_file]] {
+        let output = apate().args(&args).output().unwrap();
+        assert!(!output.status.success(), "{args:?} {output:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("broken_script"), "{args:?} {stderr}");
+    }
+}
+
+#[test]
+fn cli_import_openapi() {
+    let openapi_file = std::env::temp_dir().join("apate-cli-openapi.json");
+    std::fs::write(
+        &openapi_file,
+        r#"{"openapi": "3.0.0", "paths": {"/openapi/ping": {"get": {"responses": {"200": {"description": "OK"}}}}}}"#,
+    )
+    .unwrap();
+    let openapi_file = openapi_file.to_str().unwrap();
+
+    let output = apate()
+        .args(["check", "--import-openapi", openapi_file, SPECS_FILE])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("4 deceit(s)"), "{stdout}");
+}
+
+#[test]
+fn cli_stdin_specs() {
+    let port = 18308;
+    let mut child = apate()
+        .args(["-p", "18308", "-"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Apate must start");
+
+    let mut stdin = child.stdin.take().expect("Stdin must be piped");
+    stdin
+        .write_all(
+            br#"
+[[deceit]]
+uris = ["/stdin"]
+
+[[deceit.responses]]
+output = "from stdin"
+"#,
+        )
+        .unwrap();
+    drop(stdin);
+    wait_for_server(port);
+
+    let response = reqwest::blocking::get(format!("http://localhost:{port}/stdin")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "from stdin");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn cli_stdin_yaml_specs() {
+    let mut child = apate()
+        .args(["check", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Apate must start");
+
+    let mut stdin = child.stdin.take().expect("Stdin must be piped");
+    stdin
+        .write_all(
+            br#"
+deceit:
+  - uris: ["/stdin/yaml"]
+    responses:
+      - output: "from yaml"
+"#,
+        )
+        .unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 deceit(s)"), "{stdout}");
+}
+
+#[test]
+fn cli_env_interpolation() {
+    let specs_file = std::env::temp_dir().join("apate-cli-env.toml");
+    std::fs::write(
+        &specs_file,
+        r#"
+[[deceit]]
+uris = ["/${APATE_TEST_PREFIX}/ping"]
+
+[[deceit.responses]]
+output = "${APATE_TEST_UNDEFINED:-pong}"
+"#,
+    )
+    .unwrap();
+    let specs_file = specs_file.to_str().unwrap();
+
+    let port = 18309;
+    let mut child = apate()
+        .args(["-p", "18309", specs_file])
+        .env("APATE_TEST_PREFIX", "env")
+        .env_remove("APATE_TEST_UNDEFINED")
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Apate must start");
+    wait_for_server(port);
+
+    let response = reqwest::blocking::get(format!("http://localhost:{port}/env/ping")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "pong");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let output = apate()
+        .args(["check", specs_file])
+        .env_remove("APATE_TEST_PREFIX")
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("APATE_TEST_PREFIX"), "{stderr}");
+}
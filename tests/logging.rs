@@ -0,0 +1,141 @@
+use std::sync::{Mutex, OnceLock};
+
+use apate::{
+    ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+/// Logger that keeps apate log messages in memory to assert against them.
+struct CaptureLogger {
+    lines: Mutex<Vec<String>>,
+}
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("apate")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let mut lines = self.lines.lock().unwrap();
+            lines.push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn capture_logs() -> &'static CaptureLogger {
+    static LOGGER: OnceLock<CaptureLogger> = OnceLock::new();
+    let logger = LOGGER.get_or_init(|| CaptureLogger {
+        lines: Default::default(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(log::LevelFilter::Debug);
+    }
+    logger.lines.lock().unwrap().clear();
+    logger
+}
+
+fn logged(logger: &CaptureLogger, needle: &str) -> bool {
+    let lines = logger.lines.lock().unwrap();
+    lines.iter().any(|l| l.contains(needle))
+}
+
+#[test]
+#[serial]
+fn test_log_bodies_only_for_flagged_deceit() {
+    let logger = capture_logs();
+
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/logged"])
+                .with_log_bodies(true)
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("logged_response")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/silent"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("silent_response")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .post(api_url("/logged"))
+        .body("logged_request")
+        .send()
+        .unwrap();
+    assert_eq!(response.text().unwrap(), "logged_response");
+
+    let response = client
+        .post(api_url("/silent"))
+        .body("silent_request")
+        .send()
+        .unwrap();
+    assert_eq!(response.text().unwrap(), "silent_response");
+
+    assert!(logged(logger, "request body: logged_request"));
+    assert!(logged(logger, "response body: logged_response"));
+    assert!(!logged(logger, "silent_request"));
+    assert!(!logged(logger, "silent_response"));
+}
+
+#[test]
+#[serial]
+fn test_deceit_name_in_logs() {
+    let logger = capture_logs();
+
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/named"])
+                .with_name("named-deceit")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_name("named-response")
+                        .with_output("named")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/unnamed"])
+                .add_response(DeceitResponseBuilder::default().build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/named")).unwrap();
+    assert_eq!(response.text().unwrap(), "named");
+    reqwest::blocking::get(api_url("/unnamed")).unwrap();
+
+    assert!(logged(
+        logger,
+        "Deceit named-deceit matched (^_^). Processing response: named-response"
+    ));
+    assert!(logged(
+        logger,
+        "Deceit 1 matched (^_^). Processing response: 0"
+    ));
+}
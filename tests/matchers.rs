@@ -0,0 +1,804 @@
+use apate::{
+    ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    matchers::{BinaryEncoding, CompareOp, MatchMode, Matcher},
+    output::OutputType,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+#[test]
+#[serial]
+fn test_raw_path_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/files/{path:.*}"])
+                .add_matcher(Matcher::RawPath {
+                    eq: "%2F".to_string(),
+                    regex: true,
+                    negate: false,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("encoded")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/files/{path:.*}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("plain")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/files/a%2Fb")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "encoded");
+
+    let response = client.get(api_url("/files/a/b")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "plain");
+}
+
+#[test]
+#[serial]
+fn test_path_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/{rest:.*}"])
+                .require_path("^/api/v[0-9]+/")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("versioned")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/{rest:.*}"])
+                .add_matcher(Matcher::Path {
+                    regex: "^/api/".to_string(),
+                    negate: true,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("other")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/api/v2/users")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "versioned");
+
+    let response = client.get(api_url("/static/app.js")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "other");
+
+    let response = client.get(api_url("/api/latest/users")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[cfg(feature = "jmespath")]
+#[test]
+#[serial]
+fn test_jmespath_matcher() {
+    let jmespath = |expr: &str, eq: &str| Matcher::Jmespath {
+        expr: expr.to_string(),
+        eq: eq.to_string(),
+        negate: false,
+    };
+
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/order"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(jmespath("order.items[?qty > `1`].sku | [0]", "bulk-sku"))
+                        .with_output("filtered")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(jmespath("order.items[*].sku", r#"["a", "b"]"#))
+                        .with_output("projected")
+                        .build(),
+                )
+                .add_response(DeceitResponseBuilder::default().with_output("none").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let post = |body: &str| {
+        client
+            .post(api_url("/order"))
+            .body(body.to_string())
+            .send()
+            .unwrap()
+            .text()
+            .unwrap()
+    };
+
+    let body = r#"{"order": {"items": [{"sku": "one", "qty": 1}, {"sku": "bulk-sku", "qty": 5}]}}"#;
+    assert_eq!(post(body), "filtered");
+
+    let body = r#"{"order": {"items": [{"sku": "a", "qty": 1}, {"sku": "b", "qty": 1}]}}"#;
+    assert_eq!(post(body), "projected");
+
+    let body = r#"{"order": {"items": []}}"#;
+    assert_eq!(post(body), "none");
+}
+
+#[test]
+#[serial]
+fn test_json_depth_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/payload"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(Matcher::JsonDepth {
+                            op: CompareOp::Gt,
+                            depth: 5,
+                            negate: false,
+                        })
+                        .code(413)
+                        .with_output("too deep")
+                        .build(),
+                )
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    // Deeper than serde_json recursion limit, brackets inside strings are ignored
+    let deep = format!(r#"{}"]}}"{}"#, "[".repeat(200), "]".repeat(200));
+    let response = client.post(api_url("/payload")).body(deep).send().unwrap();
+    assert_eq!(response.status(), 413);
+    assert_eq!(response.text().unwrap(), "too deep");
+
+    let shallow = r#"{"a": {"b": [1, 2, "[[[[[["]}}"#;
+    let response = client
+        .post(api_url("/payload"))
+        .body(shallow)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "ok");
+}
+
+#[test]
+#[serial]
+fn test_json_depth_matcher_invalid_json() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/payload"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(Matcher::JsonDepth {
+                            op: CompareOp::Lt,
+                            depth: 5,
+                            negate: false,
+                        })
+                        .with_output("shallow")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .code(400)
+                        .with_output("not a json")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let post = |body: &'static str| {
+        client
+            .post(api_url("/payload"))
+            .body(body)
+            .send()
+            .unwrap()
+            .text()
+            .unwrap()
+    };
+
+    assert_eq!(post(r#"{"a": [1, true, null, "x"]}"#), "shallow");
+    assert_eq!(post("42"), "shallow");
+
+    for body in ["", "hello", "[x y]", "[1 2]", r#"{"a" 1}"#, "[1,]", "{} {}"] {
+        assert_eq!(post(body), "not a json", "{body:?}");
+    }
+}
+
+#[test]
+#[serial]
+fn test_form_arg_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/oauth/token"])
+                .require_method("POST")
+                .require_form_arg("grant_type", "client_credentials")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output(r#"{"access_token":"t0k3n"}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let post = |content_type: &str, body: &'static str| {
+        client
+            .post(api_url("/oauth/token"))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .unwrap()
+    };
+
+    let form = "client_id=app&grant_type=client_credentials";
+    let response = post("application/x-www-form-urlencoded", form);
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), r#"{"access_token":"t0k3n"}"#);
+
+    let response = post("application/x-www-form-urlencoded", "grant_type=password");
+    assert_eq!(response.status(), 404);
+
+    // Form fields are not parsed for other content types
+    let response = post("text/plain", form);
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_auth_matchers() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/basic"])
+                .require_basic_auth("ann", "pa:ss")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("basic")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/bearer"])
+                .require_bearer_token("t0k3n")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("bearer")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let status = |uri: &str, authorization: Option<&str>| {
+        let mut request = client.get(api_url(uri));
+        if let Some(auth) = authorization {
+            request = request.header("Authorization", auth);
+        }
+        request.send().unwrap().status().as_u16()
+    };
+
+    let response = client
+        .get(api_url("/basic"))
+        .basic_auth("ann", Some("pa:ss"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "basic");
+
+    let response = client
+        .get(api_url("/basic"))
+        .basic_auth("ann", Some("wrong"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    assert_eq!(status("/basic", None), 404);
+    assert_eq!(status("/basic", Some("Basic not-base64!")), 404);
+    assert_eq!(status("/basic", Some("Bearer YW5uOnBhOnNz")), 404);
+    // Scheme is case insensitive, "YW5uOnBhOnNz" is "ann:pa:ss"
+    assert_eq!(status("/basic", Some("basic YW5uOnBhOnNz")), 200);
+
+    assert_eq!(status("/bearer", Some("Bearer t0k3n")), 200);
+    assert_eq!(status("/bearer", Some("Bearer wrong")), 404);
+    assert_eq!(status("/bearer", Some("t0k3n")), 404);
+    assert_eq!(status("/bearer", None), 404);
+}
+
+#[test]
+#[serial]
+fn test_remote_ip_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ip"])
+                .require_remote_ip("127.0.0.0/8")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("local")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ip/forwarded"])
+                .add_matcher(Matcher::RemoteIp {
+                    cidr: "203.0.113.0/24".to_string(),
+                    forwarded: true,
+                    negate: false,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("proxied")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ip/external"])
+                .require_remote_ip("10.0.0.0/8")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("external")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .get(format!("http://127.0.0.1:{DEFAULT_PORT}/ip"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "local");
+
+    let response = client.get(api_url("/ip/external")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let forwarded = |xff: &str| {
+        client
+            .get(api_url("/ip/forwarded"))
+            .header("X-Forwarded-For", xff)
+            .send()
+            .unwrap()
+            .status()
+    };
+    assert_eq!(forwarded("203.0.113.7, 10.0.0.1"), 200);
+    assert_eq!(forwarded("198.51.100.1"), 404);
+
+    // Peer address is used without forwarded header
+    let response = client.get(api_url("/ip/forwarded")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_accepts_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/negotiate"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .require_accepts("application/json")
+                        .with_output("json")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .require_accepts("application/xml")
+                        .with_output("xml")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let negotiate = |accept: &str| {
+        let response = client
+            .get(api_url("/negotiate"))
+            .header("Accept", accept)
+            .send()
+            .unwrap();
+        if response.status() == 200 {
+            response.text().unwrap()
+        } else {
+            response.status().to_string()
+        }
+    };
+
+    assert_eq!(negotiate("application/json"), "json");
+    assert_eq!(negotiate("application/*"), "json");
+    assert_eq!(negotiate("text/html, application/xml;q=0.9"), "xml");
+    assert_eq!(negotiate("*/*"), "json");
+    assert_eq!(negotiate("text/html"), "404 Not Found");
+}
+
+const SOAP_REQUEST: &str = r#"<?xml version="1.0"?>
+<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+  <soap:Body>
+    <GetUser><id>42</id></GetUser>
+  </soap:Body>
+</soap:Envelope>"#;
+
+#[test]
+#[serial]
+fn test_xpath_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/soap"])
+                .add_matcher(Matcher::Xpath {
+                    path: "//GetUser/id".to_string(),
+                    eq: "42".to_string(),
+                    negate: false,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Xml)
+                        .with_output("<User><name>Ann</name></User>")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let post = |body: &str| {
+        client
+            .post(api_url("/soap"))
+            .body(body.to_string())
+            .send()
+            .unwrap()
+    };
+
+    let response = post(SOAP_REQUEST);
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/xml"
+    );
+    assert_eq!(response.text().unwrap(), "<User><name>Ann</name></User>");
+
+    let response = post(&SOAP_REQUEST.replace("42", "7"));
+    assert_eq!(response.status(), 404);
+
+    let response = post("<GetUser><id>42</id>");
+    assert_eq!(response.status(), 404, "Broken XML must not match");
+}
+
+#[test]
+#[serial]
+fn test_match_mode_any() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/any"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_match_mode(MatchMode::Any)
+                        .require_header("x-beta", "1")
+                        .require_query_arg("beta", "1")
+                        .with_output("beta")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("stable")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let get = |uri: &str, header: Option<&str>| {
+        let mut request = client.get(api_url(uri));
+        if let Some(value) = header {
+            request = request.header("x-beta", value);
+        }
+        request.send().unwrap().text().unwrap()
+    };
+
+    assert_eq!(get("/any", Some("1")), "beta");
+    assert_eq!(get("/any?beta=1", None), "beta");
+    assert_eq!(get("/any?beta=1", Some("1")), "beta");
+    assert_eq!(get("/any", None), "stable");
+    assert_eq!(get("/any?beta=0", Some("0")), "stable");
+}
+
+#[test]
+#[serial]
+fn test_uri_glob() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/files/{name}.csv"])
+                .add_uri_glob("/files/*.json")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("{{ ctx.load_path_args().name }}")
+                        .with_output_type(OutputType::Jinja)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/files/data.json")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "", "Globs have no path args");
+
+    let response = client.get(api_url("/files/data.csv")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "data");
+
+    let response = client.get(api_url("/files/data.xml")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .get(api_url("/files/nested/data.json"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404, "Single star must not cross '/'");
+}
+
+#[test]
+fn test_invalid_uri_glob() {
+    let config = DeceitBuilder::with_uris::<&str>(&[])
+        .add_uri_glob("/files/[a")
+        .add_response(DeceitResponseBuilder::default().build())
+        .to_app_config();
+
+    let error = apate::apate_specs_check(&config).unwrap_err();
+    assert!(
+        error.to_string().contains("Invalid URI glob \"/files/[a\""),
+        "{error}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_deceit_priority() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Jinja)
+                        .with_output("user {{ ctx.load_path_args().id }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/me"])
+                .with_priority(10)
+                .add_response(DeceitResponseBuilder::default().with_output("me").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .with_priority(-1)
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("never")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/user/me")).unwrap();
+    assert_eq!(response.text().unwrap(), "me");
+
+    let response = reqwest::blocking::get(api_url("/user/7")).unwrap();
+    assert_eq!(response.text().unwrap(), "user 7");
+
+    // Added deceit is placed according to its priority
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(api_url("/apate/specs/deceit"))
+        .json(&serde_json::json!({
+            "uris": ["/user/{id}"],
+            "priority": 5,
+            "responses": [{"output": "added"}]
+        }))
+        .send()
+        .unwrap();
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, serde_json::json!({"index": 1}));
+
+    let response = reqwest::blocking::get(api_url("/user/7")).unwrap();
+    assert_eq!(response.text().unwrap(), "added");
+    let response = reqwest::blocking::get(api_url("/user/me")).unwrap();
+    assert_eq!(response.text().unwrap(), "me");
+}
+
+#[test]
+#[serial]
+fn test_body_bytes_matcher() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/binary"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(Matcher::BodyBytes {
+                            encoding: BinaryEncoding::Hex,
+                            value: "0xCAFE 00 ff\n0x01".to_string(),
+                            negate: false,
+                        })
+                        .with_output("hex")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .add_matcher(Matcher::BodyBytes {
+                            encoding: BinaryEncoding::Base64,
+                            value: "3q2+7w==".to_string(),
+                            negate: false,
+                        })
+                        .with_output("base64")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .post(api_url("/binary"))
+        .body(vec![0xca, 0xfe, 0x00, 0xff, 0x01])
+        .send()
+        .unwrap();
+    assert_eq!(response.text().unwrap(), "hex");
+
+    let response = client
+        .post(api_url("/binary"))
+        .body(vec![0xde, 0xad, 0xbe, 0xef])
+        .send()
+        .unwrap();
+    assert_eq!(response.text().unwrap(), "base64");
+
+    let response = client
+        .post(api_url("/binary"))
+        .body(vec![0xca, 0xfe])
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_path_arg_regex_matcher() {
+    let config = DeceitBuilder::with_uris(&["/user/{id}"])
+        .require_path_arg_regex("id", r"^\d+$")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("numeric")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/user/42")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "numeric");
+
+    let response = client.get(api_url("/user/ann")).send().unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client.get(api_url("/user/42a")).send().unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_path_arg_defaults() {
+    let config = DeceitBuilder::with_uris(&["/items/{category:[^/]*}/{id}"])
+        .with_path_arg_default("category", "all")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("{{ ctx.load_path_args().category }}:{{ ctx.load_path_args().id }}")
+                .with_output_type(OutputType::Jinja)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/items/books/5")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "books:5");
+
+    let response = client.get(api_url("/items//5")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "all:5");
+}
+
+#[test]
+#[serial]
+fn test_path_arg_defaults_missing_arg() {
+    let config = DeceitBuilder::with_uris(&["/items/{id}", "/items/{category}/{id}"])
+        .with_path_arg_default("category", "all")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("{{ ctx.load_path_args().category }}:{{ ctx.load_path_args().id }}")
+                .with_output_type(OutputType::Jinja)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/items/5")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "all:5");
+
+    let response = client.get(api_url("/items/books/5")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "books:5");
+}
+
+#[test]
+fn test_unused_path_arg_defaults() {
+    let config = DeceitBuilder::with_uris(&["/items/{category}/{id}"])
+        .with_path_arg_default("category", "all")
+        .add_response(DeceitResponseBuilder::default().build())
+        .to_app_config();
+
+    let error = apate::apate_specs_check(&config).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Path arg default \"category\" is never used"),
+        "{error}"
+    );
+}
@@ -0,0 +1,157 @@
+use apate::{
+    ApateConfigBuilder,
+    openapi::from_openapi,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serde_json::json;
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+const PETSTORE_JSON: &str = r##"{
+  "openapi": "3.0.0",
+  "info": { "title": "Petstore", "version": "1.0.0" },
+  "paths": {
+    "/pets": {
+      "get": {
+        "responses": {
+          "200": {
+            "description": "List of pets",
+            "content": {
+              "application/json": {
+                "schema": { "type": "array", "items": { "$ref": "#/components/schemas/Pet" } }
+              }
+            }
+          },
+          "default": { "description": "Error" }
+        }
+      },
+      "post": {
+        "responses": {
+          "201": { "description": "Created" },
+          "400": { "description": "Bad request" }
+        }
+      }
+    },
+    "/pets/{pet-id}": {
+      "get": {
+        "responses": {
+          "404": { "description": "Not found" },
+          "200": {
+            "description": "Single pet",
+            "content": {
+              "application/json": {
+                "examples": {
+                  "rex": { "value": { "id": 7, "name": "Rex" } }
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+  },
+  "components": {
+    "schemas": {
+      "Pet": {
+        "type": "object",
+        "properties": {
+          "id": { "type": "integer", "example": 1 },
+          "name": { "type": "string" },
+          "tag": { "type": "string", "enum": ["dog", "cat"] }
+        }
+      }
+    }
+  }
+}"##;
+
+const PETSTORE_YAML: &str = r#"
+openapi: 3.1.0
+info:
+  title: Petstore
+  version: 1.0.0
+paths:
+  /health:
+    get:
+      responses:
+        '2XX':
+          description: OK
+          content:
+            text/plain:
+              example: alive
+        503:
+          description: Unhealthy
+"#;
+
+fn write_spec(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, content).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+fn start_with_openapi(name: &str, content: &str) -> ApateTestServer {
+    let specs = from_openapi(&write_spec(name, content)).expect("OpenAPI must be imported");
+
+    let config = specs
+        .deceit
+        .into_iter()
+        .fold(ApateConfigBuilder::default(), |builder, deceit| {
+            builder.add_deceit(deceit)
+        })
+        .build();
+
+    ApateTestServer::start(config, INIT_DELAY_MS)
+}
+
+#[test]
+#[serial]
+fn test_openapi_petstore() {
+    let _apate = start_with_openapi("apate-petstore.json", PETSTORE_JSON);
+
+    let response = reqwest::blocking::get(api_url("/pets")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, json!([{"id": 1, "name": "string", "tag": "dog"}]));
+
+    let response = reqwest::blocking::Client::new()
+        .post(api_url("/pets"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let response = reqwest::blocking::get(api_url("/pets/7")).unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().unwrap();
+    assert_eq!(body, json!({"id": 7, "name": "Rex"}));
+
+    let response = reqwest::blocking::Client::new()
+        .delete(api_url("/pets/7"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_openapi_yaml() {
+    let _apate = start_with_openapi("apate-petstore.yaml", PETSTORE_YAML);
+
+    let response = reqwest::blocking::get(api_url("/health")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "alive");
+}
+
+#[test]
+fn test_openapi_swagger_is_rejected() {
+    let path = write_spec("apate-swagger.json", r#"{"swagger": "2.0", "paths": {}}"#);
+    let err = from_openapi(&path).unwrap_err();
+    assert!(err.to_string().contains("Swagger 2.0"), "{err}");
+}
@@ -0,0 +1,114 @@
+use apate::{
+    ApateConfigBuilder,
+    postman::from_postman_collection,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+const COLLECTION: &str = r#"{
+  "info": {
+    "name": "Users",
+    "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json"
+  },
+  "item": [
+    {
+      "name": "Users",
+      "item": [
+        {
+          "name": "Get user",
+          "request": {
+            "method": "GET",
+            "header": [
+              { "key": "Authorization", "value": "Bearer {{token}}" },
+              { "key": "X-Debug", "value": "1", "disabled": true }
+            ],
+            "url": {
+              "raw": "{{baseUrl}}/users/:id",
+              "host": ["{{baseUrl}}"],
+              "path": ["users", ":id"],
+              "variable": [{ "key": "id", "value": "1" }]
+            }
+          },
+          "response": [
+            {
+              "name": "Found",
+              "code": 200,
+              "header": [
+                { "key": "Content-Type", "value": "application/json" },
+                { "key": "Content-Length", "value": "100" }
+              ],
+              "body": "{\"id\": 1, \"name\": \"Ann\"}"
+            },
+            {
+              "name": "Missing",
+              "originalRequest": {
+                "method": "GET",
+                "url": "{{baseUrl}}/users/:id?missing=true"
+              },
+              "code": 404,
+              "body": "not found"
+            }
+          ]
+        }
+      ]
+    },
+    {
+      "name": "Create user",
+      "request": {
+        "method": "POST",
+        "header": [{ "key": "X-Api-Key", "value": "secret" }],
+        "url": "https://api.example.com/users"
+      }
+    }
+  ]
+}"#;
+
+#[test]
+#[serial]
+fn test_postman_collection() {
+    let path = std::env::temp_dir().join("apate-postman.json");
+    std::fs::write(&path, COLLECTION).unwrap();
+
+    let specs = from_postman_collection(path.to_str().unwrap()).unwrap();
+    assert_eq!(specs.deceit.len(), 2);
+    assert_eq!(specs.deceit[0].uris, vec!["/users/{id}"]);
+    assert_eq!(specs.deceit[1].uris, vec!["/users"]);
+
+    let config = specs
+        .deceit
+        .into_iter()
+        .fold(ApateConfigBuilder::default(), |builder, deceit| {
+            builder.add_deceit(deceit)
+        })
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/users/1")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(response.text().unwrap(), r#"{"id": 1, "name": "Ann"}"#);
+
+    let response = reqwest::blocking::get(api_url("/users/1?missing=true")).unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.text().unwrap(), "not found");
+
+    let client = reqwest::blocking::Client::new();
+    let response = client.post(api_url("/users")).send().unwrap();
+    assert_eq!(response.status(), 404, "Header matcher must fail");
+
+    let response = client
+        .post(api_url("/users"))
+        .header("X-Api-Key", "secret")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
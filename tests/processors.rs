@@ -1,4 +1,6 @@
-use apate::processors::{ApateProcessor, PostProcessor, Processor};
+use apate::processors::{
+    ApatePreProcessor, ApateProcessor, PostProcessor, PreProcessor, Processor,
+};
 use serial_test::serial;
 
 use std::sync::Arc;
@@ -6,8 +8,8 @@ use std::sync::atomic::{AtomicU32, Ordering};
 
 use apate::deceit::{DeceitBuilder, DeceitResponseBuilder, DeceitResponseContext};
 
-use apate::ApateConfigBuilder;
 use apate::test::{ApateTestServer, DEFAULT_PORT};
+use apate::{ApateConfigBuilder, RequestContext};
 
 const INIT_DELAY_MS: usize = 1;
 
@@ -117,3 +119,64 @@ fn test_custom_user_processor() {
 
     assert_eq!(counter.load(Ordering::SeqCst), 2);
 }
+
+/// Serves `/v1/...` paths with `/v2/...` deceits and blocks requests without API key.
+struct RewritePreProcessor;
+
+impl PreProcessor for RewritePreProcessor {
+    fn process(
+        &self,
+        context: &mut RequestContext,
+    ) -> Result<Option<actix_web::HttpResponse>, Box<dyn core::error::Error>> {
+        if !context.headers.contains_key("x-api-key") {
+            return Ok(Some(
+                actix_web::HttpResponse::Unauthorized().body("API key required"),
+            ));
+        }
+
+        if let Some(rest) = context.request_path.strip_prefix("/v1/") {
+            context.request_path = Arc::new(format!("/v2/{rest}"));
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+#[serial]
+fn test_pre_processor() {
+    let config = ApateConfigBuilder::default()
+        .register_pre_processor(ApatePreProcessor::pre(
+            "rewrite",
+            Box::new(RewritePreProcessor),
+        ))
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/v2/users/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output("v2 user {{ ctx.load_path_args().id }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .get(api_url("/v1/users/7"))
+        .header("X-Api-Key", "key")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "v2 user 7");
+
+    let response = client.get(api_url("/v1/users/7")).send().unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.text().unwrap(), "API key required");
+
+    // Admin API is not affected
+    let response = client.get(api_url("/apate/health")).send().unwrap();
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,160 @@
+#![cfg(feature = "remote-specs")]
+
+use apate::{
+    ApateConfig, ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    output::OutputType,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+/// Port for the stub server that serves specs
+const SPECS_PORT: u16 = 18401;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+fn specs_url(uri: &str) -> String {
+    format!("http://localhost:{SPECS_PORT}{uri}")
+}
+
+const SPECS_JSON: &str = r#"{
+    "deceit": [
+        { "uris": ["/remote/json"], "responses": [{ "output": "from json" }] }
+    ]
+}"#;
+
+const SPECS_YAML: &str = r#"
+deceit:
+  - uris: ["/remote/yaml"]
+    responses:
+      - output: from yaml
+"#;
+
+// Env variables are expanded in fetched specs too
+const SPECS_TOML: &str = r#"
+[[deceit]]
+uris = ["/remote/${APATE_TEST_REMOTE_FORMAT:-toml}"]
+
+[[deceit.responses]]
+output = "from toml"
+"#;
+
+fn specs_server() -> ApateTestServer {
+    let serve = |uri: &str, content_type: &str, specs: &str| {
+        DeceitBuilder::with_uris(&[uri])
+            .add_header("Content-Type", content_type)
+            .add_response(DeceitResponseBuilder::default().with_output(specs).build())
+            .build()
+    };
+
+    let config = ApateConfigBuilder::default()
+        .with_port(SPECS_PORT)
+        .add_deceit(serve("/specs.json", "application/json", SPECS_JSON))
+        .add_deceit(serve("/specs.yaml", "application/yaml", SPECS_YAML))
+        .add_deceit(serve("/specs.toml", "text/plain", SPECS_TOML))
+        .build();
+
+    ApateTestServer::start(config, INIT_DELAY_MS)
+}
+
+#[test]
+#[serial]
+fn test_remote_specs() {
+    let _specs_server = specs_server();
+
+    let files = ["/specs.json", "/specs.yaml", "/specs.toml"]
+        .iter()
+        .map(|uri| specs_url(uri))
+        .collect();
+    let config = ApateConfig::try_new(Some(DEFAULT_PORT), files).unwrap();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    for format in ["json", "yaml", "toml"] {
+        let response = reqwest::blocking::get(api_url(&format!("/remote/{format}"))).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().unwrap(), format!("from {format}"));
+    }
+}
+
+#[test]
+#[serial]
+fn test_remote_specs_fetch_error() {
+    let _specs_server = specs_server();
+
+    let err = ApateConfig::try_new(Some(DEFAULT_PORT), vec![specs_url("/missing.toml")])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Can't fetch specs from"), "{err}");
+    assert!(err.contains("404"), "{err}");
+}
+
+#[test]
+#[serial]
+fn test_remote_cached_output() {
+    let upstream_config = ApateConfigBuilder::default()
+        .with_port(SPECS_PORT)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/fixture"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Jinja)
+                        .with_output("hit {{ ctx.inc_counter('fixture') }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+    let _upstream = ApateTestServer::start(upstream_config, INIT_DELAY_MS);
+
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/cached"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::RemoteCached)
+                        .with_output(&specs_url("/fixture"))
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/broken"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::RemoteCached)
+                        .with_output(&specs_url("/missing"))
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    for _ in 0..2 {
+        let response = client.get(api_url("/cached")).send().unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().unwrap(), "hit 0");
+    }
+
+    // Upstream was hit only once
+    let response = client.get(specs_url("/fixture")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "hit 1");
+
+    let response = client
+        .delete(api_url("/apate/remote-cache"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(api_url("/cached")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "hit 2");
+
+    let response = client.get(api_url("/broken")).send().unwrap();
+    assert_eq!(response.status(), 500);
+}
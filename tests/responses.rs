@@ -0,0 +1,1220 @@
+use apate::{
+    ApateConfig, ApateConfigBuilder, ApateSpecs,
+    deceit::{CorsSpec, DeceitBuilder, DeceitResponseBuilder, Fault, RateLimit, ScenarioSpec},
+    matchers::Matcher,
+    output::OutputType,
+    processors::{Format, Processor},
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+#[test]
+#[serial]
+fn test_deceit_cors_preflight() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/cors/write"])
+                .require_method("POST")
+                .with_cors(CorsSpec {
+                    allow_methods: vec!["POST".to_string(), "PUT".to_string()],
+                    allow_headers: vec!["X-Custom".to_string()],
+                    ..Default::default()
+                })
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/cors/read"])
+                .with_cors(CorsSpec {
+                    allow_origin: "http://example.com".to_string(),
+                    allow_methods: vec!["GET".to_string()],
+                    max_age: Some(600),
+                    ..Default::default()
+                })
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .request(reqwest::Method::OPTIONS, api_url("/cors/write"))
+        .header("Origin", "http://example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let headers = response.headers();
+    assert_eq!(headers["access-control-allow-origin"], "*");
+    assert_eq!(headers["access-control-allow-methods"], "POST, PUT");
+    assert_eq!(headers["access-control-allow-headers"], "X-Custom");
+
+    let response = client
+        .request(reqwest::Method::OPTIONS, api_url("/cors/read"))
+        .header("Access-Control-Request-Method", "GET")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let headers = response.headers();
+    assert_eq!(headers["access-control-allow-origin"], "http://example.com");
+    assert_eq!(headers["access-control-allow-methods"], "GET");
+    assert_eq!(headers["access-control-max-age"], "600");
+
+    let response = client.post(api_url("/cors/write")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+}
+
+#[test]
+#[serial]
+fn test_csv_output() {
+    let template = r#"[
+        ["id", "name", "note"],
+        [1, "Ivan", "plain"],
+        [2, {{ ctx.load_query_args().name | tojson }}, "has \"quotes\", and comma"]
+    ]"#;
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/export.csv"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Csv)
+                        .with_output(template)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/export.csv?name=Adolph")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/csv");
+
+    let body = response.text().unwrap();
+    assert!(body.contains(r#""has ""quotes"", and comma""#), "{body}");
+
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    assert_eq!(headers, vec!["id", "name", "note"]);
+
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0], vec!["1", "Ivan", "plain"]);
+    assert_eq!(rows[1], vec!["2", "Adolph", r#"has "quotes", and comma"#]);
+}
+
+#[test]
+#[serial]
+fn test_deceit_default_response() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .require_method("GET")
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .require_query_arg("mode", "full")
+                        .with_output_type(OutputType::Jinja)
+                        .with_output("full {{ ctx.load_path_args().id }}")
+                        .build(),
+                )
+                .with_default_response(
+                    DeceitResponseBuilder::default()
+                        .code(422)
+                        .with_output_type(OutputType::Jinja)
+                        .with_output("fallback {{ ctx.load_path_args().id }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/user/7?mode=full")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "full 7");
+
+    // URI matched but response matcher did not
+    let response = reqwest::blocking::get(api_url("/user/7?mode=short")).unwrap();
+    assert_eq!(response.status(), 422);
+    assert_eq!(response.text().unwrap(), "fallback 7");
+
+    // Deceit level matchers still apply, so it is a global 404
+    let response = reqwest::blocking::Client::new()
+        .post(api_url("/user/7"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_default_deceit() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/known"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("known")
+                        .build(),
+                )
+                .build(),
+        )
+        .with_default_deceit(
+            DeceitResponseBuilder::default()
+                .add_header("Content-Type", "application/json")
+                .with_output_type(OutputType::Jinja)
+                .with_output(r#"{"error": "not_found", "path": "{{ ctx.path }}"}"#)
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/known")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "known");
+
+    let response = reqwest::blocking::get(api_url("/unknown/path")).unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.headers()["content-type"], "application/json");
+
+    let json: serde_json::Value = response.json().unwrap();
+    assert_eq!(json["error"], "not_found");
+    assert_eq!(json["path"], "/unknown/path");
+}
+
+const JSON_BODY_SPECS: &str = r#"
+[[deceit]]
+uris = ["/profile"]
+
+[[deceit.responses]]
+code = 201
+
+[deceit.responses.json_body]
+id = 42
+name = "Ivan \"the\" Ivanov"
+tags = ["admin", "dev"]
+
+[deceit.responses.json_body.address]
+city = "Kyiv"
+zip = "01001"
+"#;
+
+#[test]
+#[serial]
+fn test_json_body_from_toml_table() {
+    let config = ApateConfig {
+        specs: toml::from_str::<ApateSpecs>(JSON_BODY_SPECS).unwrap(),
+        ..Default::default()
+    };
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/profile")).unwrap();
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.headers()["content-type"], "application/json");
+
+    let json: serde_json::Value = response.json().unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "id": 42,
+            "name": "Ivan \"the\" Ivanov",
+            "tags": ["admin", "dev"],
+            "address": {"city": "Kyiv", "zip": "01001"}
+        })
+    );
+}
+
+#[test]
+#[serial]
+fn test_fault_connection_reset() {
+    let config = DeceitBuilder::with_uris(&["/crash"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_fault(Fault::ConnectionReset)
+                .with_output("never delivered")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let result = reqwest::blocking::get(api_url("/crash"));
+    assert!(result.is_err(), "{result:?}");
+
+    // Server is still alive after that
+    let response = reqwest::blocking::get(api_url("/unknown")).unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_fault_broken_responses() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/empty"])
+                .with_fault(Fault::EmptyResponse)
+                .add_response(DeceitResponseBuilder::default().with_output("lost").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/chunk"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_fault(Fault::MalformedChunk)
+                        .with_output("lost")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let result = reqwest::blocking::get(api_url("/empty"));
+    assert!(result.is_err(), "{result:?}");
+
+    let result = reqwest::blocking::get(api_url("/chunk")).and_then(|r| r.bytes());
+    assert!(result.is_err(), "{result:?}");
+
+    let response = reqwest::blocking::get(api_url("/unknown")).unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn test_fault_body_corruption() {
+    let body = "0123456789abcdef";
+    let config = DeceitBuilder::with_uris(&["/flaky"])
+        .with_fault(Fault::RandomBodyCorruption { probability: 0.5 })
+        .add_response(DeceitResponseBuilder::default().with_output(body).build())
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let requests = 200;
+    let corrupted = (0..requests)
+        .filter(|_| {
+            let response = reqwest::blocking::get(api_url("/flaky")).unwrap();
+            assert_eq!(response.status(), 200);
+            let bytes = response.bytes().unwrap();
+            assert_eq!(bytes.len(), body.len());
+            bytes != body.as_bytes()
+        })
+        .count();
+
+    // Binomial standard deviation is ~7 requests here
+    assert!((60..=140).contains(&corrupted), "{corrupted} of {requests}");
+}
+
+#[test]
+#[serial]
+fn test_json_template_output() {
+    let config = DeceitBuilder::with_uris(&["/users/{id}"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::JsonTemplate)
+                .with_output(
+                    r#"{
+                        "id": "${path_args.id}",
+                        "name": "${json:$.user.name}",
+                        "roles": "${json:$.user.roles}",
+                        "greeting": "Hello ${json:$.user.name} from ${query.city}!",
+                        "missing": ["${json:$.nope}", "x${query.nope}"]
+                    }"#,
+                )
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::Client::new()
+        .post(api_url("/users/7?city=Kyiv"))
+        .json(&serde_json::json!({"user": {"name": "Ann", "roles": ["admin", "dev"]}}))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+
+    let json: serde_json::Value = response.json().unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "id": "7",
+            "name": "Ann",
+            "roles": ["admin", "dev"],
+            "greeting": "Hello Ann from Kyiv!",
+            "missing": [null, "x"]
+        })
+    );
+}
+
+#[test]
+#[serial]
+fn test_repeated_headers() {
+    let config = DeceitBuilder::with_uris(&["/cookies"])
+        .add_header("Set-Cookie", "deceit=1")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .add_header("Set-Cookie", "a=1")
+                .add_header("Set-Cookie", "b=2")
+                .with_output("ok")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/cookies")).unwrap();
+    assert_eq!(response.status(), 200);
+    let cookies: Vec<_> = response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|v| v.to_str().unwrap())
+        .collect();
+    // Response level headers replace deceit level ones
+    assert_eq!(cookies, ["a=1", "b=2"]);
+}
+
+#[test]
+#[serial]
+fn test_conditional_headers() {
+    let config = DeceitBuilder::with_uris(&["/cached"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .add_header("X-Cache", "MISS")
+                .add_conditional_header(
+                    Matcher::QueryArg {
+                        name: "cached".to_string(),
+                        value: "1".to_string(),
+                        negate: false,
+                    },
+                    "X-Cache",
+                    "HIT",
+                )
+                .add_conditional_header(
+                    Matcher::Header {
+                        key: "x-debug".to_string(),
+                        value: "1".to_string(),
+                        negate: false,
+                    },
+                    "X-Debug-Info",
+                    "enabled",
+                )
+                .with_output("ok")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/cached")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-cache"], "MISS");
+    assert!(response.headers().get("x-debug-info").is_none());
+
+    let response = reqwest::blocking::Client::new()
+        .get(api_url("/cached?cached=1"))
+        .header("X-Debug", "1")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-cache"], "HIT");
+    assert_eq!(response.headers()["x-debug-info"], "enabled");
+}
+
+#[test]
+#[serial]
+fn test_msgpack_output() {
+    let config = DeceitBuilder::with_uris(&["/msgpack"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::MsgPack)
+                .with_output(r#"{"id": 42, "name": "Ivan", "tags": ["a", "b"], "active": true}"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/msgpack")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/msgpack");
+
+    let body = response.bytes().unwrap();
+    let value: serde_json::Value = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"id": 42, "name": "Ivan", "tags": ["a", "b"], "active": true})
+    );
+}
+
+#[test]
+#[serial]
+fn test_redirect() {
+    let config = DeceitBuilder::with_uris(&["/old/user/{id}"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_redirect("/new/user/{{ ctx.load_path_args().id }}", 302)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client.get(api_url("/old/user/42")).send().unwrap();
+    assert_eq!(response.status(), 302);
+    assert_eq!(response.headers()["location"], "/new/user/42");
+    assert!(response.text().unwrap().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_cbor_output() {
+    let config = DeceitBuilder::with_uris(&["/cbor"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::Cbor)
+                .with_output(r#"{"device": "sensor-1", "temp": 21.5, "readings": [1, 2, 3]}"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/cbor")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/cbor");
+
+    let body = response.bytes().unwrap();
+    let value: serde_json::Value = ciborium::from_reader(body.as_ref()).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"device": "sensor-1", "temp": 21.5, "readings": [1, 2, 3]})
+    );
+}
+
+#[test]
+#[serial]
+fn test_jinja_base64_output() {
+    let config = DeceitBuilder::with_uris(&["/bin/{id}"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::JinjaBase64)
+                // "Hello " followed by base64 chunk taken from the path
+                .with_output("SGVsbG8g{{ ctx.load_path_args().id }}")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/bin/QUJD")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().unwrap().as_ref(), b"Hello ABC");
+}
+
+#[test]
+#[serial]
+fn test_hex_output_with_whitespace() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/hex"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Hex)
+                        .with_output("\n  0x48 65 6c\n\t6c 6f\r\n  0x2021\n")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/hex/invalid"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Hex)
+                        .with_output("48 65\n6c zz")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/hex")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().unwrap().as_ref(), b"Hello !");
+
+    let response = reqwest::blocking::get(api_url("/hex/invalid")).unwrap();
+    assert_eq!(response.status(), 500);
+    let error = response.text().unwrap();
+    assert!(error.contains("'z' at line 2 column 4"), "{error}");
+}
+
+#[test]
+#[serial]
+fn test_etag_processor() {
+    let config = DeceitBuilder::with_uris(&["/cached"])
+        .add_processor(Processor::ETag)
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output(r#"{"data": "cached"}"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/cached")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "{etag}");
+    assert_eq!(response.text().unwrap(), r#"{"data": "cached"}"#);
+
+    let response = client
+        .get(api_url("/cached"))
+        .header("If-None-Match", format!("\"other\", {etag}"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.bytes().unwrap().is_empty());
+
+    let response = client
+        .get(api_url("/cached"))
+        .header("If-None-Match", "W/\"stale\"")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+#[serial]
+fn test_last_modified() {
+    let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+    let config = DeceitBuilder::with_uris(&["/doc"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_last_modified(last_modified)
+                .with_output("document")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/doc")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["last-modified"], last_modified);
+    assert_eq!(response.text().unwrap(), "document");
+
+    let response = client
+        .get(api_url("/doc"))
+        .header("If-Modified-Since", "Thu, 22 Oct 2015 07:28:00 GMT")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["last-modified"], last_modified);
+    assert!(response.bytes().unwrap().is_empty());
+
+    let response = client
+        .get(api_url("/doc"))
+        .header("If-Modified-Since", "Tue, 20 Oct 2015 07:28:00 GMT")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+#[serial]
+fn test_deceit_last_modified() {
+    let deceit_date = "Wed, 21 Oct 2015 07:28:00 GMT";
+    let response_date = "Sun, 01 Jan 2023 00:00:00 GMT";
+    let config = DeceitBuilder::with_uris(&["/doc"])
+        .with_last_modified(deceit_date)
+        .add_response(
+            DeceitResponseBuilder::default()
+                .require_query_arg("v", "2")
+                .with_last_modified(response_date)
+                .with_output("v2")
+                .build(),
+        )
+        .add_response(DeceitResponseBuilder::default().with_output("v1").build())
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/doc")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["last-modified"], deceit_date);
+    assert_eq!(response.text().unwrap(), "v1");
+
+    let response = client
+        .get(api_url("/doc"))
+        .header("If-Modified-Since", deceit_date)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert!(response.bytes().unwrap().is_empty());
+
+    let response = client
+        .get(api_url("/doc?v=2"))
+        .header("If-Modified-Since", deceit_date)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200, "Response date wins over deceit one");
+    assert_eq!(response.headers()["last-modified"], response_date);
+    assert_eq!(response.text().unwrap(), "v2");
+}
+
+#[test]
+#[serial]
+fn test_pretty_param() {
+    let config = DeceitBuilder::with_uris(&["/json"])
+        .with_respect_pretty_param(true)
+        .add_header("Content-Type", "application/json; charset=utf-8")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("{\n  \"id\": 1,   \"tags\": [\"a\"]\n}")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let get = |uri: &str| {
+        reqwest::blocking::get(api_url(uri))
+            .unwrap()
+            .text()
+            .unwrap()
+    };
+
+    assert_eq!(get("/json?pretty=false"), r#"{"id":1,"tags":["a"]}"#);
+    assert_eq!(
+        get("/json?pretty=true"),
+        "{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}"
+    );
+    // Body is returned as is without the argument
+    assert_eq!(get("/json"), "{\n  \"id\": 1,   \"tags\": [\"a\"]\n}");
+}
+
+#[cfg(feature = "jsonschema")]
+#[test]
+#[serial]
+fn test_validate_response_processor() {
+    let config = DeceitBuilder::with_uris(&["/user/{id}"])
+        .add_processor(Processor::ValidateResponse {
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": {"id": {"type": "integer"}},
+                "required": ["id"]
+            }),
+        })
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::Jinja)
+                .with_output(r#"{"id": {{ ctx.load_path_args().id }}}"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/user/42")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), r#"{"id": 42}"#);
+
+    // Template renders float id that does not fit the schema
+    let response = reqwest::blocking::get(api_url("/user/4.2")).unwrap();
+    assert_eq!(response.status(), 500);
+    let body = response.text().unwrap();
+    assert!(body.starts_with("Response validation failed:"), "{body}");
+}
+
+#[test]
+#[serial]
+fn test_forward_headers_processor() {
+    let config = DeceitBuilder::with_uris(&["/echo"])
+        .add_processor(Processor::ForwardHeaders {
+            headers: vec!["X-Correlation-Id".to_string(), "X-Missing".to_string()],
+        })
+        .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::Client::new()
+        .get(api_url("/echo"))
+        .header("X-Correlation-Id", "corr-42")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-correlation-id"], "corr-42");
+    assert!(!response.headers().contains_key("x-missing"));
+}
+
+#[test]
+#[serial]
+fn test_code_template_from_counter() {
+    let config = DeceitBuilder::with_uris(&["/job"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_code_template(r#"{{ 202 if ctx.inc_counter("job") < 3 else 200 }}"#)
+                .with_output("job")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let codes: Vec<u16> = (0..5)
+        .map(|_| {
+            reqwest::blocking::get(api_url("/job"))
+                .unwrap()
+                .status()
+                .as_u16()
+        })
+        .collect();
+    assert_eq!(codes, vec![202, 202, 202, 200, 200]);
+}
+
+#[test]
+#[serial]
+fn test_jinja_tojson_filter() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/echo"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Jinja)
+                        .with_output(
+                            r#"{"name": {{ ctx.load_query_args().name | tojson }}, "args": {{ ctx.load_query_args() | tojson(indent=2) }}}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::Client::new()
+        .get(api_url("/echo"))
+        .query(&[("name", r#"say "hi" <it's>"#)])
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body = response.text().unwrap();
+    assert!(body.contains(r#""name": "say \"hi\" <it's>""#), "{body}");
+    assert!(body.contains("{\n  \"name\""), "{body}");
+
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["name"], r#"say "hi" <it's>"#);
+    assert_eq!(json["args"]["name"], r#"say "hi" <it's>"#);
+}
+
+#[test]
+#[serial]
+fn test_response_max_matches() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/quota"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_max_matches(2)
+                        .with_output("allowed")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .code(429)
+                        .with_output("exhausted")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    for expected in ["allowed", "allowed", "exhausted", "exhausted"] {
+        let response = reqwest::blocking::get(api_url("/quota")).unwrap();
+        assert_eq!(response.text().unwrap(), expected);
+    }
+}
+
+#[test]
+#[serial]
+fn test_response_scenario() {
+    let step = |required: &str, new: Option<&str>, output: &str| {
+        DeceitResponseBuilder::default()
+            .with_scenario(ScenarioSpec::new("order", Some(required), new))
+            .with_output(output)
+            .build()
+    };
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/order"])
+                .add_response(step("Started", Some("Step2"), "started"))
+                .add_response(step("Step2", Some("Done"), "step2"))
+                .add_response(step("Done", None, "done"))
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let scenarios = || {
+        reqwest::blocking::get(api_url("/apate/scenarios"))
+            .unwrap()
+            .json::<serde_json::Value>()
+            .unwrap()
+    };
+    assert_eq!(scenarios(), serde_json::json!({"order": "Started"}));
+
+    for expected in ["started", "step2", "done", "done"] {
+        let response = reqwest::blocking::get(api_url("/order")).unwrap();
+        assert_eq!(response.text().unwrap(), expected);
+    }
+    assert_eq!(scenarios(), serde_json::json!({"order": "Done"}));
+
+    let response = reqwest::blocking::Client::new()
+        .delete(api_url("/apate/scenarios"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(scenarios(), serde_json::json!({"order": "Started"}));
+
+    let response = reqwest::blocking::get(api_url("/order")).unwrap();
+    assert_eq!(response.text().unwrap(), "started");
+}
+
+#[test]
+#[serial]
+fn test_echo_output() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/echo"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Echo)
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/echo/headers"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Echo)
+                        .with_echo_headers(true)
+                        .add_header("X-Custom", "from-specs")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let body = r#"{"hello": "world", "bytes": [1, 2, 3]}"#;
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .post(api_url("/echo"))
+        .header("X-Custom", "from-request")
+        .body(body)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("x-custom").is_none());
+    assert_eq!(response.text().unwrap(), body);
+
+    let response = client
+        .post(api_url("/echo/headers"))
+        .header("Content-Type", "application/json")
+        .header("X-Request-Id", "42")
+        .header("X-Custom", "from-request")
+        .body(body)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert_eq!(headers.get("content-type").unwrap(), "application/json");
+    assert_eq!(headers.get("x-request-id").unwrap(), "42");
+    assert_eq!(headers.get("x-custom").unwrap(), "from-specs");
+    assert_eq!(response.text().unwrap(), body);
+}
+
+#[test]
+#[serial]
+fn test_transcode_processor() {
+    let json =
+        r#"{"id": 42, "name": "Ann", "tags": ["admin", "dev"], "address": {"city": "Kyiv"}}"#;
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/yaml"])
+                .add_processor(Processor::Transcode {
+                    from: Format::Json,
+                    to: Format::Yaml,
+                })
+                .add_response(DeceitResponseBuilder::default().with_output(json).build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/broken"])
+                .add_processor(Processor::Transcode {
+                    from: Format::Json,
+                    to: Format::Toml,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("{oops")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/yaml")).unwrap();
+    assert_eq!(response.status(), 200);
+    let yaml: serde_json::Value = serde_yaml::from_str(&response.text().unwrap()).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(yaml, expected);
+
+    let response = reqwest::blocking::get(api_url("/broken")).unwrap();
+    assert_eq!(response.status(), 500);
+}
+
+#[test]
+#[serial]
+fn test_replace_processor() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/literal"])
+                .add_processor(Processor::Replace {
+                    pattern: "$name".to_string(),
+                    replacement: "Ann".to_string(),
+                    regex: false,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("Hello $name, bye $name")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/regex"])
+                .add_processor(Processor::Replace {
+                    pattern: r"(?<year>\d{4})-(\d{2})-(\d{2})".to_string(),
+                    replacement: "$3.$2.${year}".to_string(),
+                    regex: true,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("from 2024-01-31 to 2024-02-29")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/literal")).unwrap();
+    assert_eq!(response.text().unwrap(), "Hello Ann, bye Ann");
+
+    let response = reqwest::blocking::get(api_url("/regex")).unwrap();
+    assert_eq!(response.text().unwrap(), "from 31.01.2024 to 29.02.2024");
+}
+
+#[test]
+#[serial]
+fn test_json_merge_patch_processor() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/order/{id}"])
+                .add_processor(Processor::JsonMergePatch {
+                    patch: r#"{"status":"ok","extra":null,"meta":{"id":"{{ ctx.load_path_args().id }}","tmp":null}}"#
+                        .to_string(),
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output(
+                            r#"{"status":"new","extra":"drop me","items":[1,2],"meta":{"tmp":1,"v":2}}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/order/42")).unwrap();
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"status": "ok", "items": [1, 2], "meta": {"id": "42", "v": 2}})
+    );
+    assert!(json.get("extra").is_none());
+}
+
+#[test]
+#[serial]
+fn test_sign_processor() {
+    use base64::Engine as _;
+    use ed25519_dalek::{Signature, SigningKey, Verifier as _};
+
+    let seed = [7u8; 32];
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/webhook"])
+                .add_processor(Processor::Sign {
+                    key_hex: hex::encode(seed),
+                    header: "X-Signature".to_string(),
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output(r#"{"event":"paid"}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/webhook")).unwrap();
+    assert_eq!(response.status(), 200);
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(response.headers()["x-signature"].as_bytes())
+        .unwrap();
+    let signature = Signature::from_slice(&signature).unwrap();
+    let body = response.bytes().unwrap();
+    assert_eq!(body.as_ref(), br#"{"event":"paid"}"#);
+
+    let public_key = SigningKey::from_bytes(&seed).verifying_key();
+    assert!(public_key.verify(&body, &signature).is_ok());
+}
+
+#[test]
+#[serial]
+fn test_rate_limit() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/limited"])
+                .require_method("GET")
+                .with_rate_limit(RateLimit {
+                    requests: 3,
+                    per_secs: 60,
+                    per_client: false,
+                    forwarded: false,
+                })
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/limited"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("other")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let statuses: Vec<u16> = (0..6)
+        .map(|_| {
+            client
+                .get(api_url("/limited"))
+                .send()
+                .unwrap()
+                .status()
+                .as_u16()
+        })
+        .collect();
+    assert_eq!(statuses, vec![200, 200, 200, 429, 429, 429]);
+
+    let response = client.get(api_url("/limited")).send().unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["retry-after"], "20");
+
+    // Requests that do not pass deceit matchers are not limited
+    let response = client.post(api_url("/limited")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "other");
+}
+
+#[test]
+#[serial]
+fn test_rate_limit_per_client_forwarded() {
+    let limited = |uri: &str, forwarded: bool| {
+        DeceitBuilder::with_uris(&[uri])
+            .with_rate_limit(RateLimit {
+                requests: 1,
+                per_secs: 60,
+                per_client: true,
+                forwarded,
+            })
+            .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+            .build()
+    };
+    let config = ApateConfigBuilder::default()
+        .add_deceit(limited("/peer", false))
+        .add_deceit(limited("/forwarded", true))
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let statuses = |uri: &str| -> Vec<u16> {
+        ["10.0.0.1", "10.0.0.2"]
+            .iter()
+            .map(|ip| {
+                client
+                    .get(api_url(uri))
+                    .header("X-Forwarded-For", *ip)
+                    .send()
+                    .unwrap()
+                    .status()
+                    .as_u16()
+            })
+            .collect()
+    };
+
+    // Forged header does not bypass the limit keyed by peer address
+    assert_eq!(statuses("/peer"), vec![200, 429]);
+    assert_eq!(statuses("/forwarded"), vec![200, 200]);
+}
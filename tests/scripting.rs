@@ -117,6 +117,8 @@ fn build_config() -> ApateConfig {
             DeceitBuilder::with_uris(&["/match/{name}", "/matcher"])
                 .add_matcher(Matcher::Rhai {
                     script: MATCHER_SCRIPT.to_string(),
+                    args: vec![],
+                    negate: false,
                 })
                 .add_response(
                     DeceitResponseBuilder::default()
@@ -354,3 +356,709 @@ fn template_json_basic_assert(v: &serde_json::Value, method: &str, path: &str, c
     assert_eq!(v.get("method").unwrap().as_str().unwrap(), method);
     assert_eq!(v.get("counter").unwrap().as_u64().unwrap(), cnt as u64);
 }
+
+const STREAM_SCRIPT: &str = r#"
+for i in 0..3 {
+    emit(("chunk " + i + "\n").to_blob());
+}
+"#;
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_stream() {
+    let config = DeceitBuilder::with_uris(&["/stream"])
+        .add_header("Content-Type", "application/x-ndjson")
+        .add_response(
+            DeceitResponseBuilder::default()
+                .code(201)
+                .with_output_type(apate::output::OutputType::RhaiStream)
+                .with_output(STREAM_SCRIPT)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let mut response = client.get(api_url("/stream")).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert!(response.content_length().is_none(), "{response:?}");
+
+    let mut chunks = Vec::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        chunks.extend(chunk);
+    }
+    assert_eq!(
+        String::from_utf8_lossy(&chunks),
+        "chunk 0\nchunk 1\nchunk 2\n"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_matcher_negate() {
+    let config = ApateConfigBuilder::default()
+        .add_script("is-get", r#"ctx.method == "GET""#)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/inline"])
+                .add_matcher(Matcher::Rhai {
+                    script: r#"ctx.method == "GET""#.to_string(),
+                    args: vec![],
+                    negate: true,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("not GET")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ref"])
+                .add_matcher(Matcher::RhaiRef {
+                    id: "is-get".to_string(),
+                    args: vec![],
+                    negate: true,
+                })
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("not GET")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    for uri in ["/inline", "/ref"] {
+        let response = client.post(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "not GET", "{uri}");
+
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 404, "{uri}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_matcher_args() {
+    let config = DeceitBuilder::with_uris(&["/args"])
+        .add_matcher(Matcher::Rhai {
+            script: r#"ctx.load_query_args().role == args[0]"#.to_string(),
+            args: vec!["admin".to_string()],
+            negate: false,
+        })
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("admin")
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(api_url("/args?role=admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "admin");
+
+    let response = client.get(api_url("/args?role=user")).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+const UUID_V5_SCRIPT: &str = r#"
+let path = ctx.path;
+path.replace("/rhai", "/jinja");
+uuid_v5("url", path).to_blob()
+"#;
+
+#[tokio::test]
+#[serial]
+async fn test_uuid_v5() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/jinja/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(r#"{{ uuid_v5("url", ctx.path) }}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Rhai)
+                        .with_output(UUID_V5_SCRIPT)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    let first = get("/jinja/42").await;
+    assert_eq!(first.len(), 36, "{first}");
+    assert_eq!(first, get("/jinja/42").await);
+    assert_ne!(first, get("/jinja/43").await);
+
+    // Same input gives same UUID in Rhai scripts too
+    assert_eq!(first, get("/rhai/42").await);
+}
+
+#[tokio::test]
+#[serial]
+async fn jinja_fragments_test() {
+    let config = ApateConfigBuilder::default()
+        .add_fragment(
+            "user",
+            r#"{"id": "{{ ctx.load_path_args().id }}", "kind": "user"}"#,
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(r#"{{ fragment("user") }}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/users/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(r#"{"items": [{{ fragment("user") }}]}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.json::<serde_json::Value>().await.unwrap()
+    };
+
+    let user = serde_json::json!({"id": "1", "kind": "user"});
+    assert_eq!(get("/user/1").await, user);
+    assert_eq!(get("/users/1").await, serde_json::json!({"items": [user]}));
+}
+
+const COUNTERS_SCRIPT: &str = r#"
+ctx.set_counter("stock", 5);
+ctx.dec_counter("stock");
+ctx.dec_counter("stock");
+let stock = ctx.get_counter("stock");
+ctx.reset_counter("empty");
+ctx.dec_counter("empty");
+`${stock}:${ctx.get_counter("empty")}`.to_blob()
+"#;
+
+#[tokio::test]
+#[serial]
+async fn test_counters_api() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai"])
+                .add_processor(Processor::Rhai {
+                    script: COUNTERS_SCRIPT.to_string(),
+                })
+                .add_response(DeceitResponseBuilder::default().build())
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/jinja"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(
+                            r#"{{ ctx.set_counter("stock", 10) }}:{{ ctx.dec_counter("stock") }}:{{ ctx.get_counter("stock") }}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    // Counter never goes below zero
+    assert_eq!(get("/rhai").await, "3:0");
+    // Counters are shared between Rhai scripts & Jinja templates
+    assert_eq!(get("/jinja").await, "3:10:9");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_counter_does_not_mutate() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/inc"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(r#"{{ ctx.inc_counter("views") }}"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/show"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(
+                            r#"{{ ctx.get_counter("views") }}:{{ ctx.get_counter("views") }}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    assert_eq!(get("/show").await, "0:0");
+    assert_eq!(get("/inc").await, "0");
+    assert_eq!(get("/show").await, "1:1");
+    assert_eq!(get("/show").await, "1:1");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_local_counters() {
+    let counter_tpl = r#"{{ ctx.inc_counter("@local:cnt") }}:{{ ctx.inc_counter("cnt") }}"#;
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/first"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(counter_tpl)
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/second"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Rhai)
+                        .with_output(
+                            r#"`${ctx.inc_counter("@local:cnt")}:${ctx.inc_counter("cnt")}`.to_blob()"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    // Local counters are independent while global one is shared
+    assert_eq!(get("/first").await, "0:0");
+    assert_eq!(get("/first").await, "1:1");
+    assert_eq!(get("/second").await, "0:2");
+    assert_eq!(get("/second").await, "1:3");
+    assert_eq!(get("/first").await, "2:4");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_sleep() {
+    let config = DeceitBuilder::with_uris(&["/sleep"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(apate::output::OutputType::Rhai)
+                .with_output(r#"sleep_ms(200); "slept".to_blob()"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let started = std::time::Instant::now();
+    let response = reqwest::get(api_url("/sleep")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "slept");
+    assert!(
+        started.elapsed() >= std::time::Duration::from_millis(200),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_signing() {
+    let config = DeceitBuilder::with_uris(&["/hmac"])
+        .add_processor(Processor::Rhai {
+            script: r#"hmac_sha256("secret".to_blob(), body).to_blob()"#.to_string(),
+        })
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output(r#"{"event":"paid"}"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::get(api_url("/hmac")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "d10706b9b0313fa2f968e2643f982865d2b912ad94a13de0b9c4af48fb3930a9"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_sha256() {
+    let config = DeceitBuilder::with_uris(&["/sha256"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(apate::output::OutputType::Rhai)
+                .with_output(r#"sha256(`{"event":"paid"}`.to_blob()).to_blob()"#)
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::get(api_url("/sha256")).await.unwrap();
+    assert_eq!(
+        response.text().await.unwrap(),
+        "4b22bc72f2548e97c5226fe4b9ccb303b58bdfa574917794b737ae0d8757b84d"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_binary_encoding() {
+    let config = DeceitBuilder::with_uris(&["/encoding"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(apate::output::OutputType::Rhai)
+                .with_output(
+                    r#"
+                    let encoded = b64_encode("apate".to_blob());
+                    let decoded = b64_decode(encoded);
+                    `${encoded}|${hex_encode(decoded)}|${hex_decode("6f6b").as_string()}`.to_blob()
+                    "#,
+                )
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::get(api_url("/encoding")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "YXBhdGU=|6170617465|ok");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_args_multi() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/jinja"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(
+                            r#"{{ ctx.load_query_args_multi().tag | join(",") }}|{{ ctx.load_query_args().tag }}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Rhai)
+                        .with_output(
+                            r#"let tags = ctx.load_query_args_multi().tag; `${tags.len()}:${tags[0]}:${tags[2]}`.to_blob()"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let get = async |uri: &str| {
+        let response = client.get(api_url(uri)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    assert_eq!(get("/jinja?tag=a&tag=b&x=1&tag=c").await, "a,b,c|a");
+    assert_eq!(get("/rhai?tag=a&tag=b&tag=c%20d").await, "3:a:c d");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_form_parsing() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/jinja"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(
+                            r#"{{ ctx.load_form().user }}:{{ ctx.load_form().note }}:{{ ctx.load_form() | length }}"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Rhai)
+                        .with_output(
+                            r#"let form = ctx.load_multipart(); `${form.user}:${form.len()}`.to_blob()"#,
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let post = async |uri: &str, content_type: &str, body: &'static str| {
+        let response = client
+            .post(api_url(uri))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        response.text().await.unwrap()
+    };
+
+    let form = "user=ann&note=hello+world%21&user=bob";
+    assert_eq!(
+        post("/jinja", "application/x-www-form-urlencoded", form).await,
+        "ann:hello world!:2"
+    );
+    assert_eq!(post("/jinja", "text/plain", form).await, "::0");
+
+    let multipart = "--XYZ\r\n\
+        Content-Disposition: form-data; name=\"user\"\r\n\r\n\
+        ann\r\n\
+        --XYZ\r\n\
+        Content-Disposition: form-data; name=\"avatar\"; filename=\"a.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        PNG\r\n\
+        --XYZ--\r\n";
+    assert_eq!(
+        post("/rhai", "multipart/form-data; boundary=XYZ", multipart).await,
+        "ann:1"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_remote_ip() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/jinja"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output("{{ ctx.remote_ip }}|{{ ctx.forwarded_ip }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/rhai"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Rhai)
+                        .with_output(r#"`${ctx.remote_ip}|${ctx.forwarded_ip}`.to_blob()"#)
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    for uri in ["/jinja", "/rhai"] {
+        let response = client
+            .get(format!("http://127.0.0.1:{DEFAULT_PORT}{uri}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "127.0.0.1|", "{uri}");
+
+        let response = client
+            .get(format!("http://127.0.0.1:{DEFAULT_PORT}{uri}"))
+            .header("X-Forwarded-For", "203.0.113.7, 10.0.0.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.text().await.unwrap(),
+            "127.0.0.1|203.0.113.7",
+            "{uri}"
+        );
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_jinja_request_data() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/{id}"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(apate::output::OutputType::Jinja)
+                        .with_output(
+                            "{{ ctx.load_body_json().name }}|{{ ctx.method }}|{{ ctx.load_path_args().id }}|\
+                            {{ ctx.load_query_args().lang }}|{{ ctx.load_headers()['x-tenant'] }}",
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::Client::new()
+        .post(api_url("/user/42?lang=uk"))
+        .header("X-Tenant", "acme")
+        .json(&serde_json::json!({"name": "Ann"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "Ann|POST|42|uk|acme");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_load_body_json() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/greet"])
+                .add_matcher(Matcher::Rhai {
+                    script: r#"ctx.load_body_json()["name"] != ()"#.to_string(),
+                    args: vec![],
+                    negate: false,
+                })
+                .add_processor(Processor::Rhai {
+                    script: r#"`Hello ${ctx.load_body_json()["name"]}`.to_blob()"#.to_string(),
+                })
+                .add_response(DeceitResponseBuilder::default().build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(api_url("/greet"))
+        .json(&serde_json::json!({"name": "Ann"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "Hello Ann");
+
+    let response = client
+        .post(api_url("/greet"))
+        .body("not a json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404, "Invalid JSON must fail the matcher");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rhai_set_body() {
+    let config = DeceitBuilder::with_uris(&["/set-body"])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output("hello")
+                .add_processor(Processor::Rhai {
+                    script: r#"
+let body = ctx.get_body();
+body.append(" world".to_blob());
+ctx.set_body(body);
+"#
+                    .to_string(),
+                })
+                .add_processor(Processor::Rhai {
+                    script: r#"ctx.get_body().as_string().to_upper().to_blob()"#.to_string(),
+                })
+                .add_processor(Processor::Rhai {
+                    script: r#"ctx.response_code = 201;"#.to_string(),
+                })
+                .build(),
+        )
+        .to_app_config();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::get(api_url("/set-body")).await.unwrap();
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.text().await.unwrap(), "HELLO WORLD");
+}
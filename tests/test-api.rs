@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use apate::deceit::{Deceit, DeceitBuilder, DeceitResponse, DeceitResponseBuilder};
 use apate::matchers::Matcher;
 use apate::test::{ApateTestServer, DEFAULT_PORT};
-use apate::{ApateConfig, ApateConfigBuilder, ApateSpecs};
+use apate::{ApateConfig, ApateConfigBuilder, ApateSpecs, LogFormat};
 
 const INIT_DELAY_MS: usize = 1;
 
@@ -191,3 +191,317 @@ async fn complex_configuration_test() {
 
     assert_eq!(response_json["id"], "1133");
 }
+
+/// Strict mode catches requests that no deceit was able to handle (like typos in URLs).
+#[test]
+#[serial]
+#[should_panic(expected = "received 1 unmatched request(s)")]
+fn strict_mode_test() {
+    let config = DeceitBuilder::with_uris(&["/user/check"])
+        .add_response(DeceitResponseBuilder::default().with_output("OK").build())
+        .to_app_config();
+
+    let apate = ApateTestServer::start(config, INIT_DELAY_MS).strict();
+
+    let response = reqwest::blocking::get(api_url("/user/check")).expect("Request failed");
+    assert_eq!(response.status(), 200);
+    apate.verify_no_unmatched();
+
+    // Oops, typo in the client URL
+    let response = reqwest::blocking::get(api_url("/usr/check")).expect("Request failed");
+    assert_eq!(response.status(), 404);
+    assert_eq!(apate.unmatched_requests(), 1);
+
+    // No explicit verification, strict server panics on drop
+}
+
+#[test]
+#[serial]
+fn strict_mode_releases_port_test() {
+    let config = || {
+        DeceitBuilder::with_uris(&["/user/check"])
+            .add_response(DeceitResponseBuilder::default().with_output("OK").build())
+            .to_app_config()
+    };
+
+    let strict_run = std::panic::catch_unwind(|| {
+        let _apate = ApateTestServer::start(config(), INIT_DELAY_MS).strict();
+        let response = reqwest::blocking::get(api_url("/usr/check")).expect("Request failed");
+        assert_eq!(response.status(), 404);
+    });
+    assert!(strict_run.is_err(), "Strict server must panic on drop");
+
+    // Same port is free again after the panic
+    let _apate = ApateTestServer::start(config(), INIT_DELAY_MS);
+    let response = reqwest::blocking::get(api_url("/user/check")).expect("Request failed");
+    assert_eq!(response.text().unwrap(), "OK");
+}
+
+#[test]
+#[serial]
+fn multiple_binds_test() {
+    let config = ApateConfigBuilder::default()
+        .with_bind("127.0.0.1".parse().unwrap())
+        .add_bind(format!("[::1]:{DEFAULT_PORT}").parse().unwrap())
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/dual"])
+                .add_response(DeceitResponseBuilder::default().with_output("both").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    for host in ["127.0.0.1", "[::1]"] {
+        let response =
+            reqwest::blocking::get(format!("http://{host}:{DEFAULT_PORT}/dual")).unwrap();
+        assert_eq!(response.status(), 200, "{host}");
+        assert_eq!(response.text().unwrap(), "both");
+    }
+}
+
+#[test]
+#[serial]
+fn path_prefix_test() {
+    let config = ApateConfigBuilder::default()
+        .with_path_prefix("/mock/")
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/user/check", "/"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output_type(OutputType::Jinja)
+                        .with_output("{{ ctx.path }}")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+
+    let apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/mock/user/check")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "/user/check");
+
+    let response = reqwest::blocking::get(api_url("/mock")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "/");
+
+    for uri in ["/user/check", "/mockery/user/check"] {
+        let response = reqwest::blocking::get(api_url(uri)).unwrap();
+        assert_eq!(response.status(), 404, "{uri}");
+    }
+    assert_eq!(apate.unmatched_requests(), 2);
+
+    // Admin API is not affected by prefix
+    let response = reqwest::blocking::get(api_url("/apate/health")).unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+#[serial]
+fn single_worker_test() {
+    let config = ApateConfigBuilder::default()
+        .with_workers(1)
+        .with_max_connections(16)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ping"])
+                .add_response(DeceitResponseBuilder::default().with_output("pong").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    for _ in 0..3 {
+        let response = reqwest::blocking::get(api_url("/ping")).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().unwrap(), "pong");
+    }
+}
+
+#[test]
+#[serial]
+fn max_body_bytes_test() {
+    let config = ApateConfigBuilder::default()
+        .with_max_body_bytes(16)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/upload"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client
+        .post(api_url("/upload"))
+        .body("small body")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "ok");
+
+    let response = client
+        .post(api_url("/upload"))
+        .body("body that is over the limit")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 413);
+}
+
+/// Send GET request over existing connection and read response till the expected body.
+fn get_over_connection(stream: &mut std::net::TcpStream, uri: &str, body: &str) -> String {
+    use std::io::{Read as _, Write as _};
+
+    write!(stream, "GET {uri} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !response.ends_with(body.as_bytes()) {
+        let read = stream.read(&mut buf).unwrap();
+        assert!(
+            read > 0,
+            "Connection closed: {}",
+            String::from_utf8_lossy(&response)
+        );
+        response.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8(response).unwrap().to_lowercase()
+}
+
+#[test]
+#[serial]
+fn keep_alive_test() {
+    let config = ApateConfigBuilder::default()
+        .with_keep_alive(5)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/ping"])
+                .add_response(DeceitResponseBuilder::default().with_output("pong").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let mut stream = std::net::TcpStream::connect(("localhost", DEFAULT_PORT)).unwrap();
+    stream
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+
+    for _ in 0..2 {
+        let response = get_over_connection(&mut stream, "/ping", "pong");
+        assert!(response.starts_with("http/1.1 200 ok"), "{response}");
+        assert!(!response.contains("connection: close"), "{response}");
+    }
+}
+
+#[test]
+#[serial]
+fn json_access_log_test() {
+    let config = ApateConfigBuilder::default()
+        .with_log_format(LogFormat::Json)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/logged"])
+                .add_response(DeceitResponseBuilder::default().with_output("ok").build())
+                .build(),
+        )
+        .build();
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = reqwest::blocking::get(api_url("/logged")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "ok");
+
+    let response = reqwest::blocking::get(api_url("/not/logged")).unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[test]
+#[serial]
+fn match_trace_test() {
+    let deceits = || {
+        vec![
+            DeceitBuilder::with_uris(&["/traced"])
+                .with_name("traced")
+                .require_method("POST")
+                .add_response(DeceitResponseBuilder::default().build())
+                .build(),
+            DeceitBuilder::with_uris(&["/traced"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .require_query_arg("fail", "no")
+                        .build(),
+                )
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_name("fallback")
+                        .build(),
+                )
+                .build(),
+            DeceitBuilder::with_uris(&["/strict"])
+                .with_name("strict")
+                .require_header("x-token", "secret")
+                .add_response(DeceitResponseBuilder::default().build())
+                .build(),
+        ]
+    };
+    let traced_get = |uri: &str| {
+        reqwest::blocking::Client::new()
+            .get(api_url(uri))
+            .header("X-Apate-Trace", "1")
+            .send()
+            .unwrap()
+    };
+
+    let config = deceits()
+        .into_iter()
+        .fold(ApateConfigBuilder::default(), |b, d| b.add_deceit(d))
+        .build();
+    let apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = traced_get("/traced");
+    assert!(!response.headers().contains_key("x-apate-matched-deceit"));
+    drop(apate);
+
+    let config = deceits()
+        .into_iter()
+        .fold(
+            ApateConfigBuilder::default().with_match_trace(true),
+            |b, d| b.add_deceit(d),
+        )
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let response = traced_get("/traced");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("x-apate-matched-deceit").unwrap(),
+        "1"
+    );
+    assert_eq!(
+        response.headers().get("x-apate-matched-response").unwrap(),
+        "fallback"
+    );
+
+    let response = reqwest::blocking::get(api_url("/traced")).unwrap();
+    assert!(!response.headers().contains_key("x-apate-matched-deceit"));
+
+    let response = traced_get("/traced?fail=yes");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("x-apate-matched-response").unwrap(),
+        "fallback"
+    );
+
+    let response = traced_get("/strict");
+    assert_eq!(response.status(), 404);
+    let body = response.text().unwrap();
+    assert!(body.contains("Evaluated deceits: 1"), "{body}");
+    assert!(
+        body.contains(r#"deceit strict ["/strict"]: URI matched, matchers did not"#),
+        "{body}"
+    );
+}
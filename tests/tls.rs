@@ -0,0 +1,63 @@
+#![cfg(feature = "tls")]
+
+use apate::{
+    ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("https://localhost:{DEFAULT_PORT}{uri}")
+}
+
+/// Write self signed certificate for localhost, returns cert & key paths.
+fn self_signed_cert() -> (String, String) {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+    let dir = std::env::temp_dir();
+    let cert_path = dir.join("apate-tls-cert.pem");
+    let key_path = dir.join("apate-tls-key.pem");
+    std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+    std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+    (
+        cert_path.to_string_lossy().to_string(),
+        key_path.to_string_lossy().to_string(),
+    )
+}
+
+#[test]
+#[serial]
+fn test_https_listener() {
+    let (cert_path, key_path) = self_signed_cert();
+    let config = ApateConfigBuilder::default()
+        .with_tls(&cert_path, &key_path)
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/secure"])
+                .add_response(
+                    DeceitResponseBuilder::default()
+                        .with_output("secret")
+                        .build(),
+                )
+                .build(),
+        )
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let client = reqwest::blocking::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+
+    let response = client.get(api_url("/secure")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.version(), reqwest::Version::HTTP_2);
+    assert_eq!(response.text().unwrap(), "secret");
+
+    let plain = reqwest::blocking::get(format!("http://localhost:{DEFAULT_PORT}/secure"));
+    assert!(plain.is_err(), "Plain HTTP must not be served");
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "watch")]
+
+use std::time::{Duration, Instant};
+
+use apate::{
+    ApateConfigBuilder, SpecsImport,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn api_url(uri: &str) -> String {
+    format!("http://localhost:{DEFAULT_PORT}{uri}")
+}
+
+const SPECS_V2: &str = r#"
+[[deceit]]
+uris = ["/watched"]
+
+[[deceit.responses]]
+output = "v2"
+"#;
+
+#[test]
+#[serial]
+fn test_specs_reloaded_on_file_change() {
+    let dir = std::env::temp_dir().join(format!("apate-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let specs_file = dir.join("specs.toml");
+    std::fs::write(&specs_file, "").unwrap();
+
+    let mut config = ApateConfigBuilder::default()
+        .add_deceit(
+            DeceitBuilder::with_uris(&["/watched"])
+                .add_response(DeceitResponseBuilder::default().with_output("v1").build())
+                .build(),
+        )
+        .build();
+    config.specs_files = vec![specs_file.to_string_lossy().to_string()];
+    config.watch = true;
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/watched")).send().unwrap();
+    assert_eq!(response.text().unwrap(), "v1");
+
+    std::fs::write(&specs_file, SPECS_V2).unwrap();
+
+    let started = Instant::now();
+    let mut body = String::new();
+    while started.elapsed() < Duration::from_secs(10) {
+        body = client
+            .get(api_url("/watched"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        if body == "v2" {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(body, "v2");
+}
+
+#[test]
+#[serial]
+fn test_specs_imports_kept_on_reload() {
+    let dir = std::env::temp_dir().join(format!("apate-watch-import-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let specs_file = dir.join("specs.toml");
+    std::fs::write(&specs_file, "").unwrap();
+    let openapi_file = dir.join("openapi.json");
+    std::fs::write(
+        &openapi_file,
+        r#"{"openapi": "3.0.0", "paths": {"/imported": {"get": {"responses": {"200": {"description": "OK"}}}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = ApateConfigBuilder::default().build();
+    config.specs_files = vec![specs_file.to_string_lossy().to_string()];
+    config
+        .add_specs_import(SpecsImport::OpenApi(
+            openapi_file.to_string_lossy().to_string(),
+        ))
+        .unwrap();
+    config.watch = true;
+
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.get(api_url("/imported")).send().unwrap();
+    assert_eq!(response.status(), 200);
+
+    std::fs::write(&specs_file, SPECS_V2).unwrap();
+
+    let started = Instant::now();
+    let mut body = String::new();
+    while started.elapsed() < Duration::from_secs(10) {
+        body = client
+            .get(api_url("/watched"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        if body == "v2" {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(body, "v2");
+
+    // Imported deceits are merged again after reload
+    let response = client.get(api_url("/imported")).send().unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "websocket")]
+
+use apate::{
+    ApateConfigBuilder,
+    deceit::{DeceitBuilder, DeceitResponseBuilder},
+    output::OutputType,
+    test::{ApateTestServer, DEFAULT_PORT},
+};
+use serial_test::serial;
+use tungstenite::Message;
+
+const INIT_DELAY_MS: usize = 1;
+
+fn ws_url(uri: &str) -> String {
+    format!("ws://localhost:{DEFAULT_PORT}{uri}")
+}
+
+fn websocket_deceit(uri: &str, output: &str) -> apate::deceit::Deceit {
+    DeceitBuilder::with_uris(&[uri])
+        .add_response(
+            DeceitResponseBuilder::default()
+                .with_output_type(OutputType::WebSocket)
+                .with_output(output)
+                .build(),
+        )
+        .build()
+}
+
+#[test]
+#[serial]
+fn test_websocket_echo_and_replay() {
+    let config = ApateConfigBuilder::default()
+        .add_deceit(websocket_deceit("/ws/echo", ""))
+        .add_deceit(websocket_deceit("/ws/replay", r#"["hello", {"id": 1}]"#))
+        .build();
+    let _apate = ApateTestServer::start(config, INIT_DELAY_MS);
+
+    let (mut socket, _) = tungstenite::connect(ws_url("/ws/echo")).unwrap();
+    socket.send(Message::text("ping me")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("ping me"));
+    socket.send(Message::binary(vec![1, 2, 3])).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::binary(vec![1, 2, 3]));
+    socket.close(None).unwrap();
+
+    let (mut socket, _) = tungstenite::connect(ws_url("/ws/replay")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("hello"));
+    assert_eq!(socket.read().unwrap(), Message::text(r#"{"id":1}"#));
+    assert!(matches!(socket.read().unwrap(), Message::Close(_)));
+
+    // Plain HTTP request to WebSocket deceit
+    let response =
+        reqwest::blocking::get(format!("http://localhost:{DEFAULT_PORT}/ws/echo")).unwrap();
+    assert_eq!(response.status(), 426);
+}